@@ -1,94 +1,639 @@
 use crate::{
-    archive::{ArchiveResult, WebArchiveClient},
-    aur::AurPackageManager,
-    config::Config,
+    archive::{self, ArchiveResult, ArchiveSource, Archiver, OriginComparison},
+    aur::{self, AurPackageManager},
+    config::{Config, DEFAULT_GITIGNORE, OriginMismatchPolicy, managed_work_dir},
+    diff,
     error::{AppError, Result},
-    git::GitHelper,
+    git::{GitHelper, RepoManager},
+    history::{History, HistoryEntry, PhaseTimings},
+    lock::WorkDirLock,
+    metrics::Metrics,
+    run_state::RunState,
+    signature,
+    summary::RunSummary,
 };
-use std::{fs, path::PathBuf};
-use tracing::{error, info, warn};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{Span, error, field, info, instrument, warn};
+
+/// What a completed run did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// A new version was committed and pushed; `old` is `None` for a new package
+    Updated { old: Option<String>, new: String },
+    /// The AUR package already matches the archive
+    UpToDate,
+    /// A dry run regenerated the package files without committing; `changes` is their diff
+    DryRun { changes: String },
+    /// The run finished without pushing for another reason (rejected archive, `--no-git`)
+    Skipped { reason: String },
+}
+
+/// Whether the package is behind the latest archive, as reported by [`App::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateCheck {
+    pub update_available: bool,
+    /// Version in the AUR PKGBUILD; `None` for a package that doesn't exist yet
+    pub current_version: Option<String>,
+    /// Version of the latest archive
+    pub candidate_version: String,
+}
+
+impl std::fmt::Display for UpdateCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let current = self.current_version.as_deref().unwrap_or("none");
+        if self.update_available {
+            write!(
+                f,
+                "update available: {current} -> {}",
+                self.candidate_version
+            )
+        } else {
+            write!(f, "up to date: {current}")
+        }
+    }
+}
+
+/// File written next to the PKGBUILD with `write_tracking_file`
+pub const TRACKING_FILE_NAME: &str = "last-update.json";
+
+/// Machine-readable record of the archive a package was last updated to, committed as
+/// [`TRACKING_FILE_NAME`] for nvchecker-style tooling
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackingFile {
+    pub original_url: String,
+    pub archive_url: String,
+    pub version: String,
+    pub sha256: String,
+}
+
+impl TrackingFile {
+    pub fn new(archive_result: &ArchiveResult) -> Self {
+        Self {
+            original_url: archive_result.original_url.clone(),
+            archive_url: archive_result.archive_url.clone(),
+            version: archive_result.version.clone(),
+            sha256: archive_result.sha256.clone(),
+        }
+    }
+
+    /// Pretty JSON with a trailing newline
+    pub fn render(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        json.push('\n');
+        Ok(json)
+    }
+}
 
 pub struct App {
     config: Config,
-    archive_client: WebArchiveClient,
-    git_helper: GitHelper,
+    archiver: Box<dyn Archiver>,
+    repo_manager: Box<dyn RepoManager>,
     aur_manager: AurPackageManager,
+    metrics: Option<Metrics>,
 }
 
 impl App {
+    /// Validate `config` and build the clients used by the update process
     pub fn new(config: Config) -> Result<Self> {
+        let git_helper = GitHelper::from_config(&config);
+        let archiver = archive::archiver_from_config(&config)?;
+        Self::with_backends(config, archiver, Box::new(git_helper))
+    }
+
+    /// Like [`App::new`], but archive through `archiver` and manage the AUR repository
+    /// through `repo_manager` instead of the Wayback Machine and git
+    pub fn with_backends(
+        config: Config,
+        archiver: Box<dyn Archiver>,
+        repo_manager: Box<dyn RepoManager>,
+    ) -> Result<Self> {
         config.validate()?;
 
-        let git_helper = GitHelper::new(config.ssh_key_path.clone());
-        let archive_client = WebArchiveClient::new();
-        let aur_manager = AurPackageManager::new(config.package_name.clone(), config.original_url.clone());
+        let aur_manager = AurPackageManager::new(
+            config.package_name.clone(),
+            config.source_filename_template.clone(),
+        )
+        .with_pkgbuild_template(config.pkgbuild_template_path.clone().map(PathBuf::from))
+        .with_pkgrel(config.pkgrel)
+        .with_install_target(config.install_target())
+        .with_wayback_host(&config.wayback_host);
+
+        let metrics = config
+            .pushgateway_url
+            .clone()
+            .map(|url| Metrics::new(url, config.package_name.clone()))
+            .transpose()?;
 
         Ok(Self {
             config,
-            archive_client,
-            git_helper,
+            archiver,
+            repo_manager,
             aur_manager,
+            metrics,
         })
     }
 
-    pub async fn run(&self) -> Result<()> {
-        info!("Starting AACS KeyDB Daily Update Process");
-        info!("Package: {}", self.config.package_name);
-        info!("Original URL: {}", self.config.original_url);
+    /// Run the full update process: archive, compare, regenerate, commit and push.
+    ///
+    /// When `cancel` fires, the in-flight step (e.g. waiting for the archive) is dropped
+    /// at its next await point, the work dir lock is released and `AppError::Cancelled`
+    /// is returned.
+    ///
+    /// With `output_summary_path` set, a [`RunSummary`] of the run is written there
+    /// whether it succeeded or not.
+    #[instrument(name = "run", skip_all, fields(package = %self.config.package_name))]
+    pub async fn run(&self, cancel: &CancellationToken) -> Result<RunOutcome> {
+        let mut summary = RunSummary::new(&self.config.package_name);
+        let result = self.run_locked(cancel, &mut summary).await;
+
+        if let Some(path) = &self.config.output_summary_path {
+            // `{package}` keeps the summaries of a manifest's packages apart
+            let path = PathBuf::from(
+                path.to_string_lossy()
+                    .replace("{package}", &self.config.package_name),
+            );
+            summary.finish(&result);
+            // The summary reports the run, failing to write it must not change the result
+            if let Err(e) = summary.write(&path) {
+                warn!("Failed to write run summary {}: {e}", path.display());
+            }
+        }
+
+        result
+    }
+
+    async fn run_locked(
+        &self,
+        cancel: &CancellationToken,
+        summary: &mut RunSummary,
+    ) -> Result<RunOutcome> {
+        self.config.check_work_dir_writable()?;
+        let _lock = if self.config.lock_work_dir {
+            Some(WorkDirLock::acquire(&self.config.lock_path())?)
+        } else {
+            None
+        };
+
+        if let Some(min_interval) = self.config.min_interval
+            && !self.config.force
+            && let Some(reason) = RunState::load(&self.config.run_state_path())
+                .and_then(|state| state.too_recent(Utc::now(), min_interval))
+        {
+            info!("Skipping run: {reason}");
+            return Ok(RunOutcome::Skipped { reason });
+        }
+
+        let result = tokio::select! {
+            result = self.run_steps(summary) => result,
+            _ = cancel.cancelled() => {
+                warn!(
+                    work_dir = %self.config.package_work_dir().display(),
+                    "Run cancelled, the work dir may hold partial changes; they are discarded when the next run resets or re-clones it"
+                );
+                return Err(AppError::Cancelled);
+            }
+        };
+
+        if let Some(metrics) = &self.metrics
+            && let Err(e) = metrics.push().await
+        {
+            warn!("Failed to push metrics: {e}");
+        }
+
+        // Only a run that checked the AUR repository counts, so a skipped one doesn't delay the next
+        if matches!(
+            result,
+            Ok(RunOutcome::Updated { .. } | RunOutcome::UpToDate)
+        ) && !self.config.dry_run
+            && !self.config.no_git
+        {
+            // The state only throttles later runs, failing to write it must not fail this one
+            if let Err(e) = RunState::update(&self.config.run_state_path(), |state| {
+                state.last_success = Some(Utc::now());
+            }) {
+                warn!("Failed to write run state: {e}");
+            }
+        }
+
+        if self.config.cleanup_work_dir && (result.is_ok() || self.config.cleanup_on_failure) {
+            self.cleanup_work_dir();
+        }
+
+        result
+    }
+
+    /// Remove the package's work dir, unless this is a dry run (whose output is meant to be
+    /// inspected) or the dir lies outside the managed temp root
+    fn cleanup_work_dir(&self) {
+        let work_path = self.config.package_work_dir();
+        if self.config.dry_run {
+            info!("Dry run, keeping work dir {}", work_path.display());
+            return;
+        }
+
+        // Canonicalize so `..` components or symlinks can't lead outside the managed root
+        let managed = managed_work_dir();
+        let inside_managed = match (work_path.canonicalize(), managed.canonicalize()) {
+            (Ok(resolved), Ok(root)) => resolved.starts_with(root),
+            // Nothing to remove
+            (Err(e), _) if e.kind() == io::ErrorKind::NotFound => return,
+            _ => false,
+        };
+        if !self.config.work_dir_is_managed() || !inside_managed {
+            warn!(
+                "Not removing work dir {}: it is outside the managed directory {}",
+                work_path.display(),
+                managed.display()
+            );
+            return;
+        }
+
+        info!("Removing work dir {}", work_path.display());
+        if let Err(e) = fs::remove_dir_all(&work_path) {
+            warn!("Failed to remove work dir {}: {e}", work_path.display());
+        }
+    }
+
+    /// Report whether an update is available without committing or pushing anything.
+    ///
+    /// The package repository is still cloned (or refreshed) into the work dir to read
+    /// the current PKGBUILD.
+    #[instrument(name = "check", skip_all, fields(package = %self.config.package_name))]
+    pub async fn check(&self, cancel: &CancellationToken) -> Result<UpdateCheck> {
+        self.config.check_work_dir_writable()?;
+        let _lock = if self.config.lock_work_dir {
+            Some(WorkDirLock::acquire(&self.config.lock_path())?)
+        } else {
+            None
+        };
+
+        tokio::select! {
+            result = self.check_steps() => result,
+            _ = cancel.cancelled() => Err(AppError::Cancelled),
+        }
+    }
 
+    async fn check_steps(&self) -> Result<UpdateCheck> {
         let archive_result = self.create_archive().await?;
-        let repo = self.prepare_repository().await?;
+        self.prepare_repository().await?;
 
-        if !self.needs_update(&archive_result).await? {
-            info!("Nothing to do, package is up to date!");
-            return Ok(());
+        let current_version = self
+            .aur_manager
+            .extract_current_version(&self.config.package_work_dir().join("PKGBUILD"))
+            .ok();
+        let update_available = match check_archive_age(
+            archive_result.timestamp,
+            Utc::now(),
+            self.config.min_archive_age,
+            self.config.max_archive_age,
+        ) {
+            Ok(()) => self.needs_update(&archive_result).await?,
+            Err(reason) => {
+                info!("Archive would be rejected: {reason}");
+                false
+            }
+        };
+
+        Ok(UpdateCheck {
+            update_available,
+            current_version,
+            candidate_version: archive_result.version,
+        })
+    }
+
+    async fn run_steps(&self, summary: &mut RunSummary) -> Result<RunOutcome> {
+        info!(
+            package = %self.config.package_name,
+            original_url = %self.config.original_url,
+            "Starting AACS KeyDB Daily Update Process"
+        );
+
+        if self.config.check_reachable {
+            info!("Checking that the original URL is reachable...");
+            self.config.validate_reachable().await?;
+        }
+
+        let mut timings = PhaseTimings::default();
+
+        let started = Instant::now();
+        let archive_result = self.create_archive().await?;
+        timings.archive = started.elapsed();
+        summary.new_version = Some(archive_result.version.clone());
+        summary.archive_url = Some(archive_result.archive_url.clone());
+        summary.sha256 = Some(archive_result.sha256.clone());
+
+        if let Err(reason) = check_archive_age(
+            archive_result.timestamp,
+            Utc::now(),
+            self.config.min_archive_age,
+            self.config.max_archive_age,
+        ) {
+            warn!(
+                timestamp = %archive_result.timestamp,
+                "Rejecting archive: {reason}"
+            );
+            return Ok(RunOutcome::Skipped { reason });
+        }
+
+        let started = Instant::now();
+        self.prepare_repository().await?;
+        timings.clone = started.elapsed();
+
+        let started = Instant::now();
+        let up_to_date = !self.needs_update(&archive_result).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_update_check(!up_to_date);
+        }
+        if up_to_date {
+            if !self.config.force {
+                info!("Nothing to do, package is up to date!");
+                return Ok(RunOutcome::UpToDate);
+            }
+            info!("Package is up to date, forcing update anyway");
+        }
+
+        let old_version = self
+            .aur_manager
+            .extract_current_version(&self.config.package_work_dir().join("PKGBUILD"))
+            .ok();
+        summary.old_version = old_version.clone();
+        let changes = self.update_package(&archive_result).await?;
+        timings.diff = started.elapsed();
+
+        if self.config.dry_run {
+            info!("Dry run, not committing or pushing");
+            return Ok(RunOutcome::DryRun { changes });
+        }
+
+        if self.config.no_git {
+            info!(
+                "--no-git, package files left in {}",
+                self.config.package_work_dir().display()
+            );
+            return Ok(RunOutcome::Skipped {
+                reason: "no git".to_string(),
+            });
         }
 
-        self.update_package(&archive_result).await?;
-        self.commit_and_push(&repo, &archive_result.version).await?;
+        // Reaching this point while up to date means the update was forced
+        let started = Instant::now();
+        let pushed = self.commit_and_push(&archive_result, up_to_date).await?;
+        timings.push = started.elapsed();
+        if !pushed {
+            info!("Regenerated files are identical to the AUR repository, nothing pushed");
+            info!("Process completed!");
+            return Ok(RunOutcome::UpToDate);
+        }
 
         info!(
-            "Successfully updated and pushed {} version {}",
-            self.config.package_name, archive_result.version
+            package = %self.config.package_name,
+            version = %archive_result.version,
+            "Successfully updated and pushed"
         );
+        let new_version = archive_result.version.clone();
+        self.record_history(archive_result, timings);
         info!("Process completed!");
 
-        Ok(())
+        Ok(RunOutcome::Updated {
+            old: old_version,
+            new: new_version,
+        })
     }
 
+    #[instrument(
+        skip_all,
+        fields(url = %self.config.original_url, version = field::Empty, source = field::Empty)
+    )]
     async fn create_archive(&self) -> Result<ArchiveResult> {
-        info!("Step 1: Creating new archive on web.archive.org and downloading...");
+        info!(
+            mode = ?self.config.archive_mode,
+            host = %self.config.wayback_host,
+            "Step 1: Getting archive from the Wayback Machine and downloading..."
+        );
+
+        let started = Instant::now();
+        let archived = self.archive_first_mirror().await;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_archive(archived.is_ok(), started.elapsed());
+        }
+
+        let mut archive_result = archived.map_err(|e| {
+            error!("Unable to access {}: {e}", self.config.wayback_host);
+            AppError::from_archive(e)
+        })?;
+
+        if self.config.verify_against_origin {
+            archive_result = self.verify_against_origin(archive_result).await?;
+        }
+
+        info!(
+            archive_url = %archive_result.archive_url,
+            sha256 = %archive_result.sha256,
+            version = %archive_result.version,
+            source = ?archive_result.source,
+            capture_status = ?archive_result.capture_status,
+            "Archive ready"
+        );
+        if archive_result.source == ArchiveSource::ExistingFallback {
+            warn!("Using an existing snapshot, it may predate the latest upstream change");
+        }
 
-        let archive_result = self
-            .archive_client
-            .archive_and_download(&self.config.original_url)
-            .await
-            .map_err(|e| {
-                error!("Unable to access web.archive.org: {e}");
-                AppError::Archive(e)
-            })?;
+        archive::validate_source(
+            &archive_result.content,
+            archive_result.content_type.as_deref(),
+            self.config.source_format,
+        )
+        .inspect_err(|e| error!("{e}"))?;
+
+        if let (Some(url), Some(pubkey)) =
+            (&self.config.signature_url, &self.config.gpg_pubkey_path)
+        {
+            self.verify_signature(&archive_result, url, pubkey)
+                .await
+                .inspect_err(|e| error!("{e}"))?;
+        }
 
-        info!("Archive URL: {}", archive_result.archive_url);
-        info!("SHA256: {}", archive_result.sha256);
+        if let Some(dir) = &self.config.save_source_to {
+            self.save_source(dir, &archive_result)?;
+        }
 
+        Span::current()
+            .record("version", archive_result.version.as_str())
+            .record("source", field::debug(archive_result.source));
         Ok(archive_result)
     }
 
-    async fn prepare_repository(&self) -> Result<git2::Repository> {
+    /// Write the archived file into `dir` under its source filename, so `makepkg` can
+    /// build from it, and check the written file hashes to the archived SHA256
+    fn save_source(&self, dir: &Path, archive_result: &ArchiveResult) -> Result<()> {
+        let path = dir.join(self.aur_manager.source_filename(&archive_result.version));
+        info!("Saving source to {}", path.display());
+
+        fs::create_dir_all(dir)?;
+        let mut partial = path.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        fs::write(&partial, &archive_result.content)?;
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(&partial)?, &mut hasher)?;
+        let written_sha256 = format!("{:x}", hasher.finalize());
+        if written_sha256 != archive_result.sha256 {
+            fs::remove_file(&partial)?;
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "Saved source {} hashes to {written_sha256}, expected {}",
+                path.display(),
+                archive_result.sha256
+            )));
+        }
+
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    /// Download the detached signature from `url` and check the archived file against it
+    async fn verify_signature(
+        &self,
+        archive_result: &ArchiveResult,
+        url: &str,
+        pubkey: &Path,
+    ) -> Result<()> {
+        info!("Verifying upstream signature from {url}...");
+        let signature = self
+            .config
+            .http_client()?
+            .get(url)
+            .headers(self.config.origin_header_map()?)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        signature::verify(&archive_result.content, &signature, pubkey)
+    }
+
+    /// Archive the original URL, falling back to each mirror in order until one succeeds.
+    /// The returned result's `original_url` records which mirror produced the archive.
+    async fn archive_first_mirror(&self) -> anyhow::Result<ArchiveResult> {
+        let urls = self.config.original_urls();
+        let mut last_error = None;
+
+        for url in urls {
+            match self
+                .archiver
+                .archive_with_mode(url, self.config.archive_mode)
+                .await
+            {
+                Ok(result) => {
+                    if url != self.config.original_url {
+                        info!(mirror = %url, "Archived from mirror");
+                    }
+                    return Ok(result);
+                }
+                Err(e) => {
+                    warn!(url = %url, "Unable to archive: {e:#}");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No original URL configured")))
+    }
+
+    async fn verify_against_origin(&self, archive_result: ArchiveResult) -> Result<ArchiveResult> {
+        info!("Verifying archived file against the original URL...");
+
+        let comparison = self
+            .archiver
+            .compare_with_origin(&archive_result.original_url, &archive_result.sha256)
+            .await?;
+
+        let OriginComparison::Mismatch {
+            origin_sha256,
+            archived_sha256,
+        } = comparison
+        else {
+            info!("Archived file matches the original");
+            return Ok(archive_result);
+        };
+
+        warn!(
+            origin_sha256 = %origin_sha256,
+            archived_sha256 = %archived_sha256,
+            "Archived file differs from the original"
+        );
+
+        match self.config.origin_mismatch_policy {
+            OriginMismatchPolicy::Warn => {
+                warn!("Proceeding with the archived copy");
+                Ok(archive_result)
+            }
+            OriginMismatchPolicy::Rearchive => {
+                info!("Requesting a fresh snapshot...");
+                match self
+                    .archiver
+                    .archive_fresh(&archive_result.original_url)
+                    .await
+                {
+                    Ok(fresh) => {
+                        if fresh.sha256 != origin_sha256 {
+                            warn!("Fresh snapshot still differs from the original");
+                        }
+                        Ok(fresh)
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Unable to create fresh snapshot: {e}, proceeding with the archived copy"
+                        );
+                        Ok(archive_result)
+                    }
+                }
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(path = %self.config.package_work_dir().display()))]
+    async fn prepare_repository(&self) -> Result<()> {
         info!("Step 2: Preparing AUR repository...");
-        let work_path = PathBuf::from(&self.config.work_dir);
+        let work_path = self.config.package_work_dir();
+
+        if self.config.no_git {
+            // Start from an empty scratch dir, like a package that isn't on the AUR yet
+            info!("--no-git, writing the package to {}", work_path.display());
+            if work_path.exists() {
+                fs::remove_dir_all(&work_path)?;
+            }
+            fs::create_dir_all(&work_path)?;
+            return Ok(());
+        }
 
         info!("Cloning/updating AUR repository...");
-        let repo = self
-            .git_helper
-            .prepare_aur_repo(&work_path, &self.config.package_name)
-            .map_err(AppError::Archive)?;
+        self.repo_manager
+            .prepare(
+                &work_path,
+                &self.config.package_name,
+                self.config.work_dir_is_managed(),
+            )
+            .map_err(AppError::from_archive)?;
 
-        Ok(repo)
+        Ok(())
     }
 
+    #[instrument(skip_all, fields(version = %archive_result.version))]
     async fn needs_update(&self, archive_result: &ArchiveResult) -> Result<bool> {
-        let pkgbuild_path = PathBuf::from(&self.config.work_dir).join("PKGBUILD");
+        let pkgbuild_path = self.config.package_work_dir().join("PKGBUILD");
 
         if !pkgbuild_path.exists() {
             info!("Step 3: Creating new package (PKGBUILD not found)...");
@@ -97,92 +642,379 @@ impl App {
 
         info!("Step 3: Checking if update is needed...");
 
-        let current_version = self
-            .aur_manager
-            .extract_current_version(&pkgbuild_path)
-            .map_err(|_| {
-                warn!("Could not extract current version, assuming update needed");
-                AppError::VersionNotFound
-            })?;
+        if self.config.skip_fallback_updates
+            && archive_result.source == ArchiveSource::ExistingFallback
+        {
+            info!("Not updating from an existing snapshot, waiting for a fresh one");
+            return Ok(false);
+        }
 
-        info!("Current version: {current_version}");
-        info!("Archive version: {}", archive_result.version);
+        let current_version = match self.aur_manager.extract_current_version(&pkgbuild_path) {
+            Ok(version) => version,
+            Err(e) => {
+                warn!("Could not extract current version ({e}), assuming update needed");
+                return Ok(true);
+            }
+        };
 
-        if archive_result.version <= current_version {
-            info!("Current version is not older than archive, no update needed");
-            return Ok(false);
+        info!(
+            current_version = %current_version,
+            archive_version = %archive_result.version,
+            "Comparing versions"
+        );
+
+        // The same version is only updated when the file changed, bumping pkgrel
+        match aur::vercmp(&archive_result.version, &current_version) {
+            Ordering::Greater | Ordering::Equal => {}
+            Ordering::Less if self.config.allow_epoch_bump => {
+                warn!("Archive version is older than current version, epoch will be bumped");
+            }
+            Ordering::Less => {
+                info!("Current version is newer than archive, no update needed");
+                return Ok(false);
+            }
         }
 
         match self.aur_manager.extract_current_sha256(&pkgbuild_path) {
-            Ok(current_sha256) => {
+            Ok(None) => {
+                info!("PKGBUILD checksum is SKIP, update needed");
+                Ok(true)
+            }
+            Ok(Some(current_sha256)) => {
                 if current_sha256 == archive_result.sha256 {
                     info!("Package is already up to date (SHA256 match)");
                     Ok(false)
                 } else {
-                    info!("Update needed");
-                    info!("   Current: {current_sha256}");
-                    info!("   New:     {}", archive_result.sha256);
+                    info!(
+                        current_sha256 = %current_sha256,
+                        new_sha256 = %archive_result.sha256,
+                        "Update needed"
+                    );
                     Ok(true)
                 }
             }
-            Err(_) => {
-                warn!("Could not extract current SHA256, assuming update needed");
+            Err(e) => {
+                warn!("Could not extract current SHA256 ({e}), assuming update needed");
                 Ok(true)
             }
         }
     }
 
-    async fn update_package(&self, archive_result: &ArchiveResult) -> Result<()> {
-        info!("Step 4: Updating package...");
-        info!("New version: {}", archive_result.version);
+    /// Regenerate PKGBUILD and .SRCINFO, returning a unified diff of the changes
+    #[instrument(skip_all, fields(version = %archive_result.version))]
+    async fn update_package(&self, archive_result: &ArchiveResult) -> Result<String> {
+        info!(version = %archive_result.version, "Step 4: Updating package...");
 
-        let work_path = PathBuf::from(&self.config.work_dir);
+        let work_path = self.config.package_work_dir();
         let pkgbuild_path = work_path.join("PKGBUILD");
+        let srcinfo_path = work_path.join(".SRCINFO");
 
-        if pkgbuild_path.exists() {
+        let old_pkgbuild = fs::read_to_string(&pkgbuild_path).ok();
+        let old_srcinfo = fs::read_to_string(&srcinfo_path).ok();
+        let tracking_path = work_path.join(TRACKING_FILE_NAME);
+        let old_tracking = fs::read_to_string(&tracking_path).ok();
+        let mut extra_files = self.config.extra_files.clone();
+        let gitignore = Path::new(".gitignore");
+        if !work_path.join(gitignore).exists()
+            && !extra_files.iter().any(|(dest, _)| dest == gitignore)
+        {
+            extra_files.push((gitignore.to_path_buf(), DEFAULT_GITIGNORE.to_string()));
+        }
+        let old_extra_files: Vec<Option<String>> = extra_files
+            .iter()
+            .map(|(dest, _)| fs::read_to_string(work_path.join(dest)).ok())
+            .collect();
+
+        let release = if pkgbuild_path.exists() {
+            let current_version = self
+                .aur_manager
+                .extract_current_version(&pkgbuild_path)
+                .unwrap_or_default();
+            let bump_epoch = self.config.allow_epoch_bump
+                && aur::vercmp(&archive_result.version, &current_version) == Ordering::Less;
             self.aur_manager.update_pkgbuild(
                 &pkgbuild_path,
+                &archive_result.original_url,
+                &current_version,
                 &archive_result.version,
                 &archive_result.sha256,
-            )?;
+                bump_epoch,
+            )?
         } else {
             self.aur_manager.create_initial_pkgbuild(
+                &pkgbuild_path,
+                &archive_result.original_url,
+                &archive_result.version,
+                &archive_result.sha256,
+            )?
+        };
+        self.aur_manager
+            .update_checksums(&pkgbuild_path, &archive_result.digests)?;
+        info!(pkgrel = release.pkgrel, epoch = ?release.epoch, "PKGBUILD updated");
+
+        let srcinfo_content = if self.config.generate_srcinfo {
+            info!("Generating .SRCINFO...");
+            let content = self.aur_manager.generate_srcinfo(
                 &pkgbuild_path,
                 &archive_result.version,
+                release,
                 &archive_result.sha256,
+                &archive_result.archive_url,
             )?;
+            fs::write(&srcinfo_path, &content)?;
+            Some(content)
+        } else {
+            info!("Not generating .SRCINFO, it is left to an external tool");
+            None
+        };
+
+        for (dest, content) in &extra_files {
+            let path = work_path.join(dest);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
         }
 
-        info!("Generating .SRCINFO...");
-        let srcinfo_content = self.aur_manager.generate_srcinfo(
-            &pkgbuild_path,
-            &archive_result.version,
-            &archive_result.sha256,
-            &archive_result.archive_url,
-        )?;
+        let tracking_content = if self.config.write_tracking_file {
+            let content = TrackingFile::new(archive_result).render()?;
+            fs::write(&tracking_path, &content)?;
+            Some(content)
+        } else {
+            None
+        };
 
-        let srcinfo_path = work_path.join(".SRCINFO");
-        fs::write(&srcinfo_path, srcinfo_content)?;
+        let new_pkgbuild = fs::read_to_string(&pkgbuild_path)?;
+        if let Some(srcinfo_content) = &srcinfo_content {
+            aur::check_srcinfo_sync(&new_pkgbuild, srcinfo_content)
+                .inspect_err(|e| error!("{e}"))?;
+        }
+
+        if self.config.run_namcap {
+            self.lint_pkgbuild(&work_path)?;
+        }
 
+        let mut changes = diff::render(
+            "PKGBUILD",
+            old_pkgbuild.as_deref().unwrap_or_default(),
+            &new_pkgbuild,
+        );
+        if let Some(srcinfo_content) = &srcinfo_content {
+            changes.push_str(&diff::render(
+                ".SRCINFO",
+                old_srcinfo.as_deref().unwrap_or_default(),
+                srcinfo_content,
+            ));
+        }
+        for ((dest, content), old) in extra_files.iter().zip(&old_extra_files) {
+            changes.push_str(&diff::render(
+                &dest.to_string_lossy(),
+                old.as_deref().unwrap_or_default(),
+                content,
+            ));
+        }
+        if let Some(tracking_content) = &tracking_content {
+            changes.push_str(&diff::render(
+                TRACKING_FILE_NAME,
+                old_tracking.as_deref().unwrap_or_default(),
+                tracking_content,
+            ));
+        }
+        info!("Changes:\n{changes}");
+
+        if self.config.dry_run {
+            // Leave the work dir as it was so the next run starts from the real state
+            Self::restore_file(&pkgbuild_path, old_pkgbuild.as_deref())?;
+            if srcinfo_content.is_some() {
+                Self::restore_file(&srcinfo_path, old_srcinfo.as_deref())?;
+            }
+            for ((dest, _), old) in extra_files.iter().zip(&old_extra_files) {
+                Self::restore_file(&work_path.join(dest), old.as_deref())?;
+            }
+            if tracking_content.is_some() {
+                Self::restore_file(&tracking_path, old_tracking.as_deref())?;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Append the pushed update and the time its steps took to the history file, if one
+    /// is configured
+    fn record_history(&self, archive_result: ArchiveResult, timings: PhaseTimings) {
+        let Some(path) = &self.config.history_path else {
+            return;
+        };
+
+        let history = History::new(path, self.config.history_max_entries);
+        let entry = HistoryEntry {
+            package: self.config.package_name.clone(),
+            run_at: Utc::now(),
+            archive: archive_result,
+            timings,
+        };
+        // The push already happened, so a history failure must not fail the run
+        if let Err(e) = history.append(&entry) {
+            warn!("Failed to record update in history file {path}: {e}");
+        }
+    }
+
+    fn restore_file(path: &Path, content: Option<&str>) -> Result<()> {
+        match content {
+            Some(content) => fs::write(path, content)?,
+            None => fs::remove_file(path)?,
+        }
+        Ok(())
+    }
+
+    fn lint_pkgbuild(&self, work_path: &Path) -> Result<()> {
+        info!("Running namcap on PKGBUILD...");
+
+        let output = match Command::new("namcap")
+            .arg("PKGBUILD")
+            .current_dir(work_path)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                warn!("namcap is not installed, skipping PKGBUILD lint");
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+        let mut has_errors = false;
+        for line in stdout.lines().chain(stderr.lines()) {
+            if line.contains(" E: ") {
+                error!("namcap: {line}");
+                has_errors = true;
+            } else if line.contains(" W: ") {
+                warn!("namcap: {line}");
+            }
+        }
+
+        if has_errors || !output.status.success() {
+            return Err(AppError::Namcap { stdout, stderr });
+        }
+
+        info!("namcap found no errors");
         Ok(())
     }
 
-    async fn commit_and_push(&self, repo: &git2::Repository, version: &str) -> Result<()> {
+    /// Commit and push the regenerated files, returning whether anything was pushed
+    #[instrument(skip_all, fields(version = %archive_result.version, forced))]
+    async fn commit_and_push(&self, archive_result: &ArchiveResult, forced: bool) -> Result<bool> {
         info!("Step 5: Committing and pushing changes...");
-        let commit_message = format!("Update to {version}");
+        let mut commit_message = render_commit_message(
+            &self.config.commit_message_template,
+            &self.config.package_name,
+            &archive_result.version,
+            &archive_result.sha256,
+        )?;
+        if forced {
+            commit_message.push_str(" (forced)");
+        }
 
-        let work_path = PathBuf::from(&self.config.work_dir);
+        let work_path = self.config.package_work_dir();
         info!("Files updated:");
         info!("   - {}", work_path.join("PKGBUILD").display());
-        info!("   - {}", work_path.join(".SRCINFO").display());
+        if self.config.generate_srcinfo {
+            info!("   - {}", work_path.join(".SRCINFO").display());
+        }
 
-        info!("Commit message: {commit_message}");
+        info!(commit_message = %commit_message, "Prepared commit");
         info!("Committing and pushing to AUR...");
 
-        self.git_helper
-            .commit_and_push(repo, &commit_message)
-            .map_err(AppError::Archive)?;
+        let pushed = self
+            .repo_manager
+            .commit_and_push(&work_path, &commit_message)
+            .map_err(AppError::from_archive)?;
 
-        Ok(())
+        if let Some(metrics) = &self.metrics {
+            metrics.record_success();
+        }
+
+        Ok(pushed)
+    }
+}
+
+/// Process exit code for a set of successful run outcomes.
+///
+/// `0` when any package was updated, otherwise `noop_code`. Failed runs exit with `1`.
+pub fn exit_code(outcomes: &[RunOutcome], noop_code: u8) -> u8 {
+    if outcomes
+        .iter()
+        .any(|outcome| matches!(outcome, RunOutcome::Updated { .. }))
+    {
+        0
+    } else {
+        noop_code
     }
 }
+
+/// Process exit code for the `check` subcommand, following `checkupdates`:
+/// `0` when any package has an update available, `2` when all are up to date.
+pub fn check_exit_code(checks: &[UpdateCheck]) -> u8 {
+    if checks.iter().any(|check| check.update_available) {
+        0
+    } else {
+        2
+    }
+}
+
+/// Check that an archive taken at `timestamp` is at least `min` and at most `max` old
+pub fn check_archive_age(
+    timestamp: DateTime<Utc>,
+    now: DateTime<Utc>,
+    min: Option<Duration>,
+    max: Option<Duration>,
+) -> std::result::Result<(), String> {
+    let age = (now - timestamp).to_std().unwrap_or(Duration::ZERO);
+
+    if let Some(min) = min
+        && age < min
+    {
+        return Err(format!(
+            "snapshot is {}s old, younger than the minimum of {}s",
+            age.as_secs(),
+            min.as_secs()
+        ));
+    }
+    if let Some(max) = max
+        && age > max
+    {
+        return Err(format!(
+            "snapshot is {}s old, older than the maximum of {}s",
+            age.as_secs(),
+            max.as_secs()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Render a commit message template, substituting `{package}`, `{version}` and `{sha256}`
+pub fn render_commit_message(
+    template: &str,
+    package: &str,
+    version: &str,
+    sha256: &str,
+) -> Result<String> {
+    let message = template
+        .replace("{package}", package)
+        .replace("{version}", version)
+        .replace("{sha256}", sha256)
+        .trim()
+        .to_string();
+
+    if message.is_empty() {
+        return Err(AppError::Archive(anyhow::anyhow!(
+            "Commit message template rendered an empty message"
+        )));
+    }
+
+    Ok(message)
+}
@@ -1,9 +1,83 @@
+use crate::{
+    checksum::{self, ChecksumKind, MultiHasher},
+    config::{ArchiveMode, Config, SourceFormat},
+    download_cache::{CachedDownload, DownloadCache},
+    error::{AppError, ArchiveError},
+    rate_limit::RateLimiter,
+    retry,
+    run_state::RunState,
+};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-use tracing::info;
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::Cursor,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, info, warn};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// How long a `check_archived` result is reused within a run
+const SNAPSHOT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Wayback Machine host used unless configured otherwise
+pub const DEFAULT_WAYBACK_HOST: &str = "web.archive.org";
+
+/// Delay between save retries when a 429 response carries no `Retry-After` header
+const RATE_LIMIT_BASE_DELAY: Duration = Duration::from_secs(10);
+
+/// Default delay between checks for a requested snapshot
+pub const DEFAULT_SNAPSHOT_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Default time to wait for a requested snapshot to appear
+pub const DEFAULT_SNAPSHOT_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Delay before the first download retry, doubled for each further one
+const DOWNLOAD_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between download retries
+const DOWNLOAD_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Without a `Content-Length`, log download progress every this many bytes
+const PROGRESS_LOG_INTERVAL: u64 = 1024 * 1024;
+
+tokio::task_local! {
+    /// End of the archive budget of the `archive_*` call running on this task
+    static DEADLINE: tokio::time::Instant;
+}
+
+/// Content types accepted for a downloaded ZIP archive
+const ZIP_CONTENT_TYPES: &[&str] = &[
+    "application/zip",
+    "application/x-zip",
+    "application/x-zip-compressed",
+    "application/octet-stream",
+];
+
+/// Content types accepted for a downloaded tarball, compressed or not
+const TAR_CONTENT_TYPES: &[&str] = &[
+    "application/x-tar",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-compressed-tar",
+    "application/x-gtar",
+    "application/octet-stream",
+];
+
+/// `ustar` magic of a tar header, at offset 257
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Magic bytes of a gzip member using deflate
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
 
 #[derive(Debug, Deserialize)]
 pub struct ArchiveResponse {
@@ -15,6 +89,49 @@ pub struct ArchiveSnapshot {
     pub available: bool,
     pub url: String,
     pub timestamp: String,
+    /// HTTP status Wayback recorded for the capture, when known
+    #[serde(default, deserialize_with = "deserialize_status")]
+    pub status: Option<u16>,
+}
+
+impl ArchiveSnapshot {
+    /// Whether the snapshot can be downloaded and versioned: Wayback reports it as
+    /// available, its timestamp parses and the capture is not suspect
+    pub fn is_usable(&self) -> bool {
+        self.available && self.parsed_timestamp().is_ok() && !self.is_suspect()
+    }
+
+    /// Whether the capture recorded an upstream server error (5xx), so its content is
+    /// likely an error page rather than the file
+    pub fn is_suspect(&self) -> bool {
+        self.status.is_some_and(is_suspect_status)
+    }
+
+    /// The snapshot's Wayback timestamp as a UTC date
+    pub fn parsed_timestamp(&self) -> Result<DateTime<Utc>> {
+        parse_wayback_timestamp(&self.timestamp)
+    }
+
+    /// How long before `now` the snapshot was taken (zero for timestamps in the future)
+    pub fn age(&self, now: DateTime<Utc>) -> Result<Duration> {
+        Ok((now - self.parsed_timestamp()?)
+            .to_std()
+            .unwrap_or(Duration::ZERO))
+    }
+}
+
+/// Where the snapshot in an [`ArchiveResult`] came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveSource {
+    /// Captured by a save request in this run. History entries written before the
+    /// source was recorded read as this.
+    #[default]
+    FreshlyArchived,
+    /// An existing snapshot, used because saving failed or was not requested
+    ExistingFallback,
+    /// The snapshot taken at an explicitly requested timestamp
+    Pinned,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,33 +141,561 @@ pub struct ArchiveResult {
     pub timestamp: DateTime<Utc>,
     pub sha256: String,
     pub version: String,
+    #[serde(default)]
+    pub source: ArchiveSource,
+    /// HTTP status Wayback recorded for the capture, when known
+    #[serde(default)]
+    pub capture_status: Option<u16>,
+    /// Digests computed alongside `sha256`, keyed by kind (including `sha256` itself)
+    #[serde(default)]
+    pub digests: HashMap<ChecksumKind, String>,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+    #[serde(skip)]
+    pub content_type: Option<String>,
+}
+
+/// State of a save request, as reported by the Wayback save job status API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveStatus {
+    /// The snapshot is still being captured
+    Pending,
+    /// The snapshot is available at `url`; `http_status` is the upstream status
+    /// Wayback recorded for the capture
+    Success {
+        url: String,
+        timestamp: String,
+        http_status: Option<u16>,
+    },
+    /// Wayback gave up on the capture
+    Failed { reason: String },
 }
 
+/// Response of the save endpoint when asked for JSON
+#[derive(Debug, Deserialize)]
+struct SaveJob {
+    job_id: Option<String>,
+}
+
+/// Body of `/save/status/{job_id}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum SaveJobStatus {
+    Pending,
+    Success {
+        timestamp: String,
+        original_url: String,
+        #[serde(default)]
+        http_status: Option<u16>,
+    },
+    Error {
+        #[serde(default)]
+        status_ext: Option<String>,
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginComparison {
+    Match,
+    Mismatch {
+        origin_sha256: String,
+        archived_sha256: String,
+    },
+}
+
+/// Stage of the archive workflow, reported to the sender given to
+/// [`WebArchiveClient::with_progress`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveProgress {
+    /// A save request is being sent to the Wayback Machine
+    SubmittingSave,
+    /// Polling for the requested snapshot, starting at attempt 1
+    WaitingForSnapshot { attempt: u32 },
+    /// `bytes` of the archive received so far, out of `total` when announced
+    Downloading { bytes: u64, total: Option<u64> },
+    /// The archive was downloaded and verified
+    Done,
+}
+
+pub struct Download {
+    /// The body, empty unless the caller asked to keep it
+    pub bytes: Vec<u8>,
+    /// Number of body bytes received
+    pub size: u64,
+    pub sha256: String,
+    /// Every configured digest, computed in the same pass as `sha256`
+    pub digests: HashMap<ChecksumKind, String>,
+    pub content_type: Option<String>,
+    /// Length announced in the `Content-Length` header
+    pub content_length: Option<u64>,
+}
+
+/// The archive step of the update process: snapshot the original URL and download it.
+///
+/// `App` only talks to the archive through this trait, so a fake returning a canned
+/// `ArchiveResult` can stand in for the Wayback Machine.
+#[async_trait]
+pub trait Archiver: Send + Sync {
+    /// Archive `url` (or reuse an existing snapshot) and download the archived file
+    async fn archive_and_download(&self, url: &str) -> Result<ArchiveResult>;
+
+    /// Archive `url` without falling back to existing snapshots
+    async fn archive_fresh(&self, url: &str) -> Result<ArchiveResult> {
+        self.archive_and_download(url).await
+    }
+
+    /// Download the latest existing snapshot of `url` without requesting a new one
+    async fn archive_existing(&self, url: &str) -> Result<ArchiveResult> {
+        self.archive_and_download(url).await
+    }
+
+    /// Archive `url` the way `mode` asks for
+    async fn archive_with_mode(&self, url: &str, mode: ArchiveMode) -> Result<ArchiveResult> {
+        match mode {
+            ArchiveMode::CreateThenFallback => self.archive_and_download(url).await,
+            ArchiveMode::CreateOnly => self.archive_fresh(url).await,
+            ArchiveMode::ExistingOnly => self.archive_existing(url).await,
+        }
+    }
+
+    /// Compare the live original with the archived copy's SHA256
+    async fn compare_with_origin(
+        &self,
+        original_url: &str,
+        archived_sha256: &str,
+    ) -> Result<OriginComparison> {
+        let _ = (original_url, archived_sha256);
+        anyhow::bail!("This archiver cannot compare against the original")
+    }
+}
+
+type SnapshotCache = HashMap<String, (Instant, Option<ArchiveSnapshot>)>;
+
 pub struct WebArchiveClient {
     client: reqwest::Client,
+    snapshot_cache: Mutex<SnapshotCache>,
+    rate_limit_budget: Duration,
+    host: String,
+    max_snapshot_age: Option<Duration>,
+    origin_headers: HeaderMap,
+    download_cache: Option<DownloadCache>,
+    run_state: Option<PathBuf>,
+    progress: Option<UnboundedSender<ArchiveProgress>>,
+    download_retries: u32,
+    snapshot_poll_interval: Duration,
+    snapshot_max_wait: Duration,
+    source_format: SourceFormat,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    archive_budget: Option<Duration>,
+    checksums: Vec<ChecksumKind>,
+    snapshot_timestamp: Option<String>,
 }
 
 impl WebArchiveClient {
     pub fn new() -> Self {
         Self {
             client: reqwest::Client::new(),
+            snapshot_cache: Mutex::new(HashMap::new()),
+            rate_limit_budget: Duration::ZERO,
+            host: DEFAULT_WAYBACK_HOST.to_string(),
+            max_snapshot_age: None,
+            origin_headers: HeaderMap::new(),
+            download_cache: None,
+            run_state: None,
+            progress: None,
+            download_retries: 0,
+            snapshot_poll_interval: DEFAULT_SNAPSHOT_POLL_INTERVAL,
+            snapshot_max_wait: DEFAULT_SNAPSHOT_MAX_WAIT,
+            source_format: SourceFormat::Zip,
+            rate_limiter: None,
+            archive_budget: None,
+            checksums: vec![ChecksumKind::Sha256],
+            snapshot_timestamp: None,
+        }
+    }
+
+    /// Client set up with the Wayback host, rate limit budget and TLS settings from
+    /// `config`, failing when its CA bundle or origin headers are invalid
+    pub fn from_config(config: &Config) -> crate::error::Result<Self> {
+        Ok(Self::new()
+            .with_client(config.http_client()?)
+            .with_host(&config.wayback_host)
+            .with_rate_limit_budget(config.rate_limit_budget)
+            .with_max_snapshot_age(config.max_snapshot_age)
+            .with_origin_headers(config.origin_header_map()?)
+            .with_download_retries(config.download_retries)
+            .with_snapshot_polling(config.snapshot_poll_interval, config.snapshot_max_wait)
+            .with_source_format(config.source_format)
+            .with_rate_limiter(config.wayback_rate_limiter.clone())
+            .with_archive_budget(config.archive_budget)
+            .with_checksums(&config.checksums)
+            .with_snapshot_timestamp(config.snapshot_timestamp.clone())
+            .with_run_state(Some(config.run_state_path()))
+            .with_download_cache(
+                config
+                    .download_cache
+                    .then(|| DownloadCache::new(config.download_cache_dir())),
+            ))
+    }
+
+    /// Compute these digests of every download in the same pass; SHA256 is always
+    /// computed
+    pub fn with_checksums(mut self, kinds: &[ChecksumKind]) -> Self {
+        self.checksums = kinds.to_vec();
+        if !self.checksums.contains(&ChecksumKind::Sha256) {
+            self.checksums.push(ChecksumKind::Sha256);
+        }
+        self
+    }
+
+    /// Accept the content types of `format` for downloaded snapshots
+    pub fn with_source_format(mut self, format: SourceFormat) -> Self {
+        self.source_format = format;
+        self
+    }
+
+    /// After a save request, check for the snapshot every `interval` (starting right
+    /// away) and give up once `max_wait` has passed
+    pub fn with_snapshot_polling(mut self, interval: Duration, max_wait: Duration) -> Self {
+        self.snapshot_poll_interval = interval;
+        self.snapshot_max_wait = max_wait;
+        self
+    }
+
+    /// Restart a download from scratch up to `retries` times after a transient failure
+    /// such as a connection reset. Client errors (4xx) are not retried.
+    pub fn with_download_retries(mut self, retries: u32) -> Self {
+        self.download_retries = retries;
+        self
+    }
+
+    /// Keep a copy of kept download bodies in `cache`, so a `304 Not Modified` or a
+    /// matching Wayback digest can reuse them
+    pub fn with_download_cache(mut self, cache: Option<DownloadCache>) -> Self {
+        self.download_cache = cache;
+        self
+    }
+
+    /// Record the validators and hash of each download in the [`RunState`] at `path`,
+    /// and make later downloads of the same URL conditional on them
+    pub fn with_run_state(mut self, path: Option<PathBuf>) -> Self {
+        self.run_state = path;
+        self
+    }
+
+    /// Report [`ArchiveProgress`] events to `sender` as the workflow advances.
+    /// Events are dropped once the receiver is gone.
+    pub fn with_progress(mut self, sender: UnboundedSender<ArchiveProgress>) -> Self {
+        self.progress = Some(sender);
+        self
+    }
+
+    fn report(&self, event: ArchiveProgress) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(event);
+        }
+    }
+
+    /// Send requests through `client`, e.g. one trusting a custom CA
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send `headers` (e.g. credentials or cookies) when downloading from the origin.
+    /// They are never sent to the Wayback host.
+    pub fn with_origin_headers(mut self, headers: HeaderMap) -> Self {
+        self.origin_headers = headers;
+        self
+    }
+
+    /// Treat existing snapshots older than `max_age` as unusable
+    pub fn with_max_snapshot_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_snapshot_age = max_age;
+        self
+    }
+
+    /// Talk to this Wayback Machine host (e.g. a mirror) instead of web.archive.org.
+    /// `https://` is used unless the host starts with a scheme such as `http://`.
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = host.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Total time to spend waiting out 429 responses from the save endpoint before
+    /// falling back to an existing snapshot
+    pub fn with_rate_limit_budget(mut self, budget: Duration) -> Self {
+        self.rate_limit_budget = budget;
+        self
+    }
+
+    /// Throttle requests to the Wayback host through `limiter`, which may be shared with
+    /// other clients
+    pub fn with_rate_limiter(mut self, limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Download the snapshot taken at `timestamp` (`YYYYMMDDhhmmss`) in every `archive_*`
+    /// call, instead of saving a new one or looking up the latest
+    pub fn with_snapshot_timestamp(mut self, timestamp: Option<String>) -> Self {
+        self.snapshot_timestamp = timestamp;
+        self
+    }
+
+    /// Give each `archive_*` call at most `budget` in total, covering save attempts,
+    /// snapshot polling, the fallback to an existing snapshot and the download
+    pub fn with_archive_budget(mut self, budget: Option<Duration>) -> Self {
+        self.archive_budget = budget;
+        self
+    }
+
+    /// Run `fut` under the archive budget, unless an enclosing `archive_*` call already
+    /// started the clock
+    async fn within_budget<T>(&self, url: &str, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(budget) = self.archive_budget else {
+            return fut.await;
+        };
+        if DEADLINE.try_with(|_| ()).is_ok() {
+            return fut.await;
+        }
+
+        let deadline = tokio::time::Instant::now() + budget;
+        match tokio::time::timeout_at(deadline, DEADLINE.scope(deadline, fut)).await {
+            Ok(result) => result,
+            Err(_) => Err(ArchiveError::DeadlineExceeded {
+                url: url.to_string(),
+                budget,
+            }
+            .into()),
+        }
+    }
+
+    /// Fail when waiting `delay` before the next request to `url` would overrun the
+    /// archive budget, instead of sleeping only to be cut off
+    fn check_deadline(&self, url: &str, delay: Duration) -> Result<()> {
+        let remaining = DEADLINE
+            .try_with(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()));
+        match (remaining, self.archive_budget) {
+            (Ok(remaining), Some(budget)) if remaining <= delay => {
+                warn!(
+                    "Archive budget of {}s exhausted, not waiting for {url}",
+                    budget.as_secs()
+                );
+                Err(ArchiveError::DeadlineExceeded {
+                    url: url.to_string(),
+                    budget,
+                }
+                .into())
+            }
+            _ => Ok(()),
         }
     }
 
+    /// Wait for the rate limiter before a request to `url`, then check the archive
+    /// budget; requests to other hosts (the original server or a mirror) are not throttled
+    async fn throttle(&self, url: &str) -> Result<()> {
+        if let Some(limiter) = &self.rate_limiter
+            && self.is_wayback(url)
+        {
+            limiter.acquire().await;
+        }
+        self.check_deadline(url, Duration::ZERO)
+    }
+
+    /// Whether `url` points at the Wayback host; unparsable URLs count as Wayback URLs
+    fn is_wayback(&self, url: &str) -> bool {
+        let wayback_host = url::Url::parse(&self.base_url())
+            .ok()
+            .and_then(|base| base.host_str().map(str::to_string));
+        url::Url::parse(url)
+            .ok()
+            .and_then(|url| {
+                url.host_str()
+                    .map(|host| Some(host) == wayback_host.as_deref())
+            })
+            .unwrap_or(true)
+    }
+
+    /// Scheme and host of the Wayback endpoints; `https://` unless the host names a scheme
+    fn base_url(&self) -> String {
+        if self.host.contains("://") {
+            self.host.clone()
+        } else {
+            format!("https://{}", self.host)
+        }
+    }
+
+    /// Wayback API endpoint reporting the snapshot of `url` closest to now
+    pub fn availability_url(&self, url: &str) -> Result<url::Url> {
+        Ok(url::Url::parse_with_params(
+            &format!("{}/wayback/available", self.base_url()),
+            [("url", url)],
+        )?)
+    }
+
+    /// CDX API endpoint listing every capture of `url`, oldest first
+    pub fn cdx_url(&self, url: &str) -> Result<url::Url> {
+        Ok(url::Url::parse_with_params(
+            &format!("{}/cdx/search/cdx", self.base_url()),
+            [("url", url), ("output", "json")],
+        )?)
+    }
+
+    /// Endpoint requesting a new snapshot of `url`
+    pub fn save_url(&self, url: &str) -> String {
+        format!("{}/save/{url}", self.base_url())
+    }
+
+    /// Endpoint reporting the state of a save job
+    pub fn save_status_url(&self, job_id: &str) -> String {
+        format!("{}/save/status/{job_id}", self.base_url())
+    }
+
+    /// Page redirecting to the latest snapshot of `url`, or to the one taken at `timestamp`
+    pub fn snapshot_url(&self, timestamp: Option<&str>, url: &str) -> String {
+        match timestamp {
+            Some(timestamp) => format!("{}/web/{timestamp}/{url}", self.base_url()),
+            None => format!("{}/web/{url}", self.base_url()),
+        }
+    }
+
+    /// The unmodified file captured at `timestamp`, without the Wayback toolbar or
+    /// rewritten links
+    pub fn raw_snapshot_url(&self, timestamp: &str, url: &str) -> String {
+        format!("{}/web/{timestamp}id_/{url}", self.base_url())
+    }
+
+    /// Query the Wayback availability API for the snapshot closest to now. A snapshot
+    /// older than the maximum snapshot age counts as no snapshot.
+    ///
+    /// Results are cached per URL for a short time, so repeated lookups within a run
+    /// don't issue duplicate requests.
     pub async fn check_archived(&self, url: &str) -> Result<Option<ArchiveSnapshot>> {
-        let api_url = format!("https://archive.org/wayback/available?url={url}");
+        let url = &normalize_url(url)?;
+        let snapshot = match self.cached_snapshot(url) {
+            Some(snapshot) => {
+                info!("Using cached archive lookup for {url}");
+                snapshot
+            }
+            None => self.refresh_archived(url).await?,
+        };
+
+        Ok(snapshot.filter(|snapshot| !self.is_too_old(snapshot)))
+    }
+
+    /// Whether `snapshot` is older than the maximum snapshot age, or its age is unknown
+    fn is_too_old(&self, snapshot: &ArchiveSnapshot) -> bool {
+        let Some(max_age) = self.max_snapshot_age else {
+            return false;
+        };
+        match snapshot.age(Utc::now()) {
+            Ok(age) if age > max_age => {
+                info!(
+                    "Existing archive {} is {} days old, older than the {} day limit",
+                    snapshot.url,
+                    age.as_secs() / 86400,
+                    max_age.as_secs() / 86400
+                );
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                info!("Ignoring existing archive {}: {e}", snapshot.url);
+                true
+            }
+        }
+    }
+
+    /// Query the Wayback availability API bypassing the cache, and cache the result
+    pub async fn refresh_archived(&self, url: &str) -> Result<Option<ArchiveSnapshot>> {
+        let url = &normalize_url(url)?;
+        let api_url = self.availability_url(url)?;
         info!("Checking existing archives at: {api_url}");
 
-        let response: ArchiveResponse = self.client.get(&api_url).send().await?.json().await?;
+        self.throttle(api_url.as_str()).await?;
+        let response: ArchiveResponse = self
+            .client
+            .get(api_url.as_str())
+            .send()
+            .await?
+            .json()
+            .await?;
 
         info!("Archive response: {response:?}");
-        Ok(response.archived_snapshots.get("closest").cloned())
+        let snapshot = response.archived_snapshots.get("closest").cloned();
+
+        self.snapshot_cache()
+            .insert(url.to_string(), (Instant::now(), snapshot.clone()));
+        Ok(snapshot)
+    }
+
+    /// List every capture of `url` from the CDX API, oldest first
+    pub async fn cdx(&self, url: &str) -> Result<Vec<CdxEntry>> {
+        let url = &normalize_url(url)?;
+        let api_url = self.cdx_url(url)?;
+        info!("Listing captures at: {api_url}");
+        self.query_cdx(api_url).await
+    }
+
+    /// The CDX entry of the capture of `url` taken at `timestamp`
+    pub async fn cdx_capture(&self, url: &str, timestamp: &str) -> Result<Option<CdxEntry>> {
+        let url = &normalize_url(url)?;
+        let mut api_url = self.cdx_url(url)?;
+        api_url
+            .query_pairs_mut()
+            .append_pair("from", timestamp)
+            .append_pair("to", timestamp);
+        debug!("Looking up capture {timestamp} at: {api_url}");
+
+        Ok(self
+            .query_cdx(api_url)
+            .await?
+            .into_iter()
+            .find(|entry| entry.timestamp == timestamp))
+    }
+
+    async fn query_cdx(&self, api_url: url::Url) -> Result<Vec<CdxEntry>> {
+        self.throttle(api_url.as_str()).await?;
+        let body = self
+            .client
+            .get(api_url.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        parse_cdx(&body)
     }
 
+    /// Forget the cached lookup for `url`, e.g. after requesting a new snapshot
+    pub fn invalidate_snapshot(&self, url: &str) {
+        let url = normalize_url(url).unwrap_or_else(|_| url.to_string());
+        self.snapshot_cache().remove(&url);
+    }
+
+    fn cached_snapshot(&self, url: &str) -> Option<Option<ArchiveSnapshot>> {
+        self.snapshot_cache()
+            .get(url)
+            .filter(|(cached_at, _)| cached_at.elapsed() < SNAPSHOT_CACHE_TTL)
+            .map(|(_, snapshot)| snapshot.clone())
+    }
+
+    fn snapshot_cache(&self) -> std::sync::MutexGuard<'_, SnapshotCache> {
+        self.snapshot_cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Follow the Wayback redirect for `url` to find its most recent snapshot
     pub async fn get_latest_archive(&self, url: &str) -> Result<Option<ArchiveSnapshot>> {
-        let archive_browse_url = format!("https://web.archive.org/web/{url}");
+        let url = normalize_url(url)?;
+        let archive_browse_url = self.snapshot_url(None, &url);
         info!("Accessing archive page: {archive_browse_url}");
 
+        self.throttle(&archive_browse_url).await?;
         let response = self.client.head(&archive_browse_url).send().await?;
 
         let final_url = response.url().to_string();
@@ -62,10 +707,12 @@ impl WebArchiveClient {
                 let timestamp = &after_web[..end];
                 if timestamp.len() >= 8 && timestamp.chars().all(|c| c.is_ascii_digit()) {
                     info!("Found archive timestamp: {timestamp}");
+                    // Wayback replays a capture with the status it recorded
                     return Ok(Some(ArchiveSnapshot {
                         available: true,
                         url: final_url.clone(),
                         timestamp: timestamp.to_string(),
+                        status: Some(response.status().as_u16()),
                     }));
                 }
             }
@@ -75,112 +722,607 @@ impl WebArchiveClient {
         Ok(None)
     }
 
-    pub async fn archive_url(&self, url: &str) -> Result<String> {
-        let save_url = format!("https://web.archive.org/save/{url}");
+    /// Submit `url` to Wayback's save endpoint and wait for the new snapshot.
+    ///
+    /// A 429 response is retried after its `Retry-After` delay (or an exponential
+    /// backoff when it has none) as long as the rate limit budget allows. Returns the
+    /// new snapshot, or an error if saving stayed rate limited, the snapshot did not
+    /// show up in time or the capture recorded an upstream server error.
+    pub async fn archive_url(&self, url: &str) -> Result<ArchiveSnapshot> {
+        let url = &normalize_url(url)?;
+        let save_url = self.save_url(url);
+
+        let mut waited = Duration::ZERO;
+        let mut attempt = 1;
+        let response = loop {
+            info!("Submitting archive request to: {save_url}");
+            self.report(ArchiveProgress::SubmittingSave);
+            self.throttle(&save_url).await?;
+            let response = self
+                .client
+                .get(&save_url)
+                .header(reqwest::header::ACCEPT, "application/json")
+                .send()
+                .await?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                break response;
+            }
 
-        info!("Submitting archive request to: {save_url}");
+            let delay = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| parse_retry_after(v, Utc::now()))
+                .unwrap_or_else(|| {
+                    retry::backoff_delay(attempt, RATE_LIMIT_BASE_DELAY, self.rate_limit_budget)
+                });
+            // A zero delay (no budget, or `Retry-After: 0`) would retry in a busy loop
+            if delay.is_zero() || waited + delay > self.rate_limit_budget {
+                break response;
+            }
 
-        let response = self.client.get(&save_url).send().await?;
+            self.check_deadline(&save_url, delay)?;
+            info!(
+                "Rate limited (429), retrying save in {}s...",
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            waited += delay;
+            attempt += 1;
+        };
+        self.invalidate_snapshot(url);
 
         info!("Archive request status: {}", response.status());
 
         if response.status().is_success() {
+            // With `Accept: application/json` the save endpoint answers with a job to poll;
+            // otherwise fall back to the (lagging) availability API
+            let job_id = response
+                .json::<SaveJob>()
+                .await
+                .ok()
+                .and_then(|job| job.job_id);
             info!("Archive request submitted successfully, waiting for completion...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
 
-            for attempt in 1..=5 {
+            // Dropping this future (e.g. on cancellation) interrupts the wait at once
+            let started = Instant::now();
+            let mut attempt = 1;
+            loop {
                 info!("Attempt {attempt} to get new archive...");
-                match self.check_archived(url).await {
-                    Ok(Some(snapshot)) => {
-                        if snapshot.available {
-                            info!("Found new archive: {}", snapshot.url);
-                            return Ok(snapshot.url);
+                self.report(ArchiveProgress::WaitingForSnapshot { attempt });
+                let status = match &job_id {
+                    Some(job_id) => self.job_status(job_id).await,
+                    None => self.availability_status(url).await,
+                };
+                match status {
+                    Ok(ArchiveStatus::Success {
+                        url,
+                        timestamp,
+                        http_status,
+                    }) => {
+                        info!("Found new archive from {timestamp}: {url}");
+                        return Ok(ArchiveSnapshot {
+                            available: true,
+                            url,
+                            timestamp,
+                            status: http_status,
+                        });
+                    }
+                    Ok(ArchiveStatus::Failed { reason }) => {
+                        return Err(ArchiveError::SaveFailed {
+                            url: url.clone(),
+                            reason,
                         }
+                        .into());
                     }
-                    Ok(None) => {
+                    Ok(ArchiveStatus::Pending) => {
                         info!("No archive found yet");
                     }
                     Err(e) => {
                         info!("Error checking archive: {e}");
                     }
                 }
-                if attempt < 5 {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                if started.elapsed() + self.snapshot_poll_interval > self.snapshot_max_wait {
+                    let waited = started.elapsed().as_secs();
+                    info!("No archive after waiting {waited}s, giving up");
+                    return Err(ArchiveError::SaveFailed {
+                        url: url.clone(),
+                        reason: format!("no snapshot appeared within {waited}s"),
+                    }
+                    .into());
                 }
+                self.check_deadline(url, self.snapshot_poll_interval)?;
+                tokio::time::sleep(self.snapshot_poll_interval).await;
+                attempt += 1;
             }
-        } else if response.status().as_u16() == 429 {
+        }
+
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
             info!("Rate limited (429). Will fallback to existing archive...");
-            anyhow::bail!("Rate limited - will use existing archive");
+            return Err(ArchiveError::RateLimited { url: url.clone() }.into());
         }
 
-        anyhow::bail!("Failed to archive URL: {}", url)
+        Err(ArchiveError::SaveFailed {
+            url: url.clone(),
+            reason: format!("the save request answered HTTP {}", response.status()),
+        }
+        .into())
     }
 
-    /// Download file from archive URL and calculate SHA256
-    pub async fn download_and_hash(&self, url: &str) -> Result<(Vec<u8>, String)> {
-        let response = self.client.get(url).send().await?.error_for_status()?;
+    /// Poll the status of a save job started by [`WebArchiveClient::archive_url`]
+    pub async fn job_status(&self, job_id: &str) -> Result<ArchiveStatus> {
+        let status_url = self.save_status_url(job_id);
+        self.throttle(&status_url).await?;
+        let body = self
+            .client
+            .get(&status_url)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
 
-        let bytes = response.bytes().await?;
-        let mut hasher = Sha256::new();
-        hasher.update(&bytes);
-        let hash = hasher.finalize();
-        let hash_string = format!("{hash:x}");
+        let status = parse_job_status(&body)?;
+        Ok(match status {
+            ArchiveStatus::Success {
+                url,
+                timestamp,
+                http_status,
+            } => ArchiveStatus::Success {
+                url: self.snapshot_url(Some(&timestamp), &url),
+                timestamp,
+                http_status,
+            },
+            status => status,
+        })
+    }
 
-        Ok((bytes.to_vec(), hash_string))
+    /// Status derived from the availability API, which never reports failures
+    async fn availability_status(&self, url: &str) -> Result<ArchiveStatus> {
+        // Always hit the API here, a cached result can't show the new snapshot
+        Ok(match self.refresh_archived(url).await? {
+            Some(snapshot) if snapshot.is_usable() => ArchiveStatus::Success {
+                url: snapshot.url,
+                timestamp: snapshot.timestamp,
+                http_status: snapshot.status,
+            },
+            _ => ArchiveStatus::Pending,
+        })
     }
 
-    /// Complete archive and download process - try to create new archive, fallback to existing one
-    pub async fn archive_and_download(&self, url: &str) -> Result<ArchiveResult> {
-        info!("Creating new archive for {url}...");
-
-        // Try to create new archive
-        match self.archive_url(url).await {
-            Ok(archive_url) => {
-                info!("Downloading from new archive: {archive_url}");
-                let (_, sha256) = self.download_and_hash(&archive_url).await?;
-
-                // Extract timestamp from archive URL as version number
-                let version = self.extract_version_from_archive_url(&archive_url);
-
-                return Ok(ArchiveResult {
-                    original_url: url.to_string(),
-                    archive_url,
-                    timestamp: Utc::now(),
-                    sha256,
-                    version,
-                });
+    /// Download file from archive URL and calculate its configured digests in a single
+    /// pass, keeping the body for validation
+    pub async fn download_and_hash(&self, url: &str) -> Result<Download> {
+        self.download_with_retries(url, true).await
+    }
+
+    /// Download `url` and calculate its SHA256 without keeping the body in memory
+    pub async fn hash_url(&self, url: &str) -> Result<Download> {
+        self.download_with_retries(url, false).await
+    }
+
+    /// [`Self::stream_download`], restarted with backoff after transient failures.
+    /// HTTP failures surface as [`ArchiveError::DownloadFailed`].
+    async fn download_with_retries(&self, url: &str, keep_bytes: bool) -> Result<Download> {
+        let mut attempt = 1;
+        let result = loop {
+            match self.stream_download(url, keep_bytes).await {
+                Err(e)
+                    if attempt <= self.download_retries
+                        && e.chain().any(crate::error::is_transient) =>
+                {
+                    let delay = retry::backoff_delay(
+                        attempt,
+                        DOWNLOAD_RETRY_BASE_DELAY,
+                        DOWNLOAD_RETRY_MAX_DELAY,
+                    );
+                    self.check_deadline(url, delay)?;
+                    warn!(
+                        "Download of {url} failed ({e:#}), retry {attempt}/{} in {}s",
+                        self.download_retries,
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => break result,
             }
-            Err(e) => {
-                info!("Failed to create new archive: {e}");
-                info!("Falling back to existing archive...");
+        };
+
+        result.map_err(|e| match e.downcast::<reqwest::Error>() {
+            Ok(source) => ArchiveError::DownloadFailed {
+                url: url.to_string(),
+                source,
             }
+            .into(),
+            Err(e) => e,
+        })
+    }
+
+    /// GET request for a download, carrying the origin headers unless `url` points at
+    /// the Wayback host
+    pub fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(url);
+        if self.is_wayback(url) || self.origin_headers.is_empty() {
+            request
+        } else {
+            request.headers(self.origin_headers.clone())
         }
+    }
 
-        // If creating new archive fails, directly access archive page to get latest version
-        if let Ok(Some(snapshot)) = self.get_latest_archive(url).await {
-            if snapshot.available {
-                info!("Using existing archive: {}", snapshot.url);
-                let (_, sha256) = self.download_and_hash(&snapshot.url).await?;
+    /// The download recorded in `entry`, or `None` when its body is needed but not cached.
+    /// Only the SHA256 is known when the body is not needed.
+    fn cached_download(&self, entry: &CachedDownload, keep_bytes: bool) -> Option<Download> {
+        let (bytes, digests) = if keep_bytes {
+            let bytes = self.download_cache.as_ref()?.body(entry)?;
+            let digests = checksum::digest_all(&self.checksums, &bytes);
+            (bytes, digests)
+        } else {
+            let digests = HashMap::from([(ChecksumKind::Sha256, entry.sha256.clone())]);
+            (Vec::new(), digests)
+        };
+        Some(Download {
+            bytes,
+            size: entry.size,
+            sha256: entry.sha256.clone(),
+            digests,
+            content_type: entry.content_type.clone(),
+            content_length: Some(entry.size),
+        })
+    }
 
-                // Extract version number from archive timestamp
-                let version = snapshot.timestamp.clone();
+    /// Stream the response body in chunks, hashing each one as it arrives
+    ///
+    /// With a run state, the request carries the validators of the previous download of
+    /// `url`, and a `304 Not Modified` reuses its hash instead. A download that keeps its
+    /// body only sends them when the body cache can supply it.
+    async fn stream_download(&self, url: &str, keep_bytes: bool) -> Result<Download> {
+        let cached = self
+            .run_state
+            .as_deref()
+            .filter(|_| !keep_bytes || self.download_cache.is_some())
+            .and_then(RunState::load)
+            .and_then(|mut state| state.downloads.remove(url))
+            .filter(|entry| entry.etag.is_some() || entry.last_modified.is_some());
+        let mut request = self.get(url);
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
 
-                return Ok(ArchiveResult {
-                    original_url: url.to_string(),
-                    archive_url: snapshot.url,
-                    timestamp: Utc::now(),
-                    sha256,
-                    version,
+        self.throttle(url).await?;
+        let mut response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED
+            && let Some(entry) = &cached
+        {
+            if let Some(download) = self.cached_download(entry, keep_bytes) {
+                info!("{url} is not modified, reusing the cached download");
+                self.report(ArchiveProgress::Downloading {
+                    bytes: download.size,
+                    total: download.content_length,
                 });
+                return Ok(download);
+            }
+            info!("Cached copy of {url} is missing, downloading it again");
+            self.throttle(url).await?;
+            response = self.get(url).send().await?;
+        }
+        let mut response = response.error_for_status()?;
+        let validator = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = validator(reqwest::header::ETAG);
+        let last_modified = validator(reqwest::header::LAST_MODIFIED);
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let content_length = response.content_length();
+
+        let mut hasher = MultiHasher::new(&self.checksums);
+        let mut bytes = Vec::new();
+        let mut size = 0u64;
+        let mut last_logged = 0u64;
+
+        while let Some(chunk) = response.chunk().await? {
+            hasher.update(&chunk);
+            if keep_bytes {
+                bytes.extend_from_slice(&chunk);
+            }
+            size += chunk.len() as u64;
+            self.report(ArchiveProgress::Downloading {
+                bytes: size,
+                total: content_length,
+            });
+
+            match content_length {
+                Some(total) if total > 0 => {
+                    let percent = size * 100 / total;
+                    if percent >= last_logged + 10 {
+                        info!("Downloaded {size}/{total} bytes ({percent}%)");
+                        last_logged = percent;
+                    }
+                }
+                _ => {
+                    if size >= last_logged + PROGRESS_LOG_INTERVAL {
+                        info!("Downloaded {size} bytes");
+                        last_logged = size;
+                    }
+                }
             }
         }
 
-        anyhow::bail!("No archive available for URL: {url}")
+        let digests = hasher.finalize();
+        let hash_string = digests[&ChecksumKind::Sha256].clone();
+
+        let entry = CachedDownload {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            sha256: hash_string.clone(),
+            size,
+            content_type: content_type.clone(),
+        };
+        // A kept body is cached even without validators, for the Wayback digest index
+        if keep_bytes && let Some(cache) = &self.download_cache {
+            cache.store(&entry, &bytes);
+        }
+        if let Some(path) = &self.run_state
+            && (entry.etag.is_some() || entry.last_modified.is_some() || cached.is_some())
+        {
+            let result = RunState::update(path, |state| {
+                if entry.etag.is_some() || entry.last_modified.is_some() {
+                    state.downloads.insert(url.to_string(), entry);
+                } else {
+                    state.downloads.remove(url);
+                }
+            });
+            if let Err(e) = result {
+                warn!("Could not record the validators of {url}: {e}");
+            }
+        }
+
+        Ok(Download {
+            bytes,
+            size,
+            sha256: hash_string,
+            digests,
+            content_type,
+            content_length,
+        })
+    }
+
+    /// Create a fresh archive and download it, without falling back to existing snapshots
+    pub async fn archive_fresh(&self, url: &str) -> Result<ArchiveResult> {
+        self.within_budget(url, async {
+            if let Some(timestamp) = &self.snapshot_timestamp {
+                return self.download_pinned(url, timestamp).await;
+            }
+
+            let snapshot = self.archive_url(url).await?;
+            let archive_url = snapshot.url;
+
+            info!("Downloading from new archive: {archive_url}");
+            let download = self.download_and_hash(&archive_url).await?;
+            verify_download(&download, self.source_format)
+                .map_err(|e| ArchiveError::invalid_content(&archive_url, e))?;
+
+            // Extract timestamp from archive URL as version number
+            let version = self.extract_version_from_archive_url(&archive_url);
+            let timestamp = parse_wayback_timestamp(&version)?;
+            self.report(ArchiveProgress::Done);
+
+            Ok(ArchiveResult {
+                original_url: url.to_string(),
+                archive_url,
+                timestamp,
+                sha256: download.sha256,
+                digests: download.digests,
+                version,
+                source: ArchiveSource::FreshlyArchived,
+                capture_status: snapshot.status,
+                content: download.bytes,
+                content_type: download.content_type,
+            })
+        })
+        .await
+    }
+
+    /// Complete archive and download process - try to create new archive, fallback to existing one
+    pub async fn archive_and_download(&self, url: &str) -> Result<ArchiveResult> {
+        self.within_budget(url, async {
+            if let Some(timestamp) = &self.snapshot_timestamp {
+                return self.download_pinned(url, timestamp).await;
+            }
+
+            info!("Creating new archive for {url}...");
+
+            // Try to create new archive
+            match self.archive_fresh(url).await {
+                Ok(result) => return Ok(result),
+                Err(e) => {
+                    info!("Failed to create new archive: {e}");
+                    // Out of budget, there is no time left for the fallback
+                    if self.check_deadline(url, Duration::ZERO).is_err() {
+                        return Err(e);
+                    }
+                    info!("Falling back to existing archive...");
+                }
+            }
+
+            match self.latest_usable_snapshot(url).await {
+                Some(snapshot) => self.download_snapshot(url, snapshot).await,
+                None => Err(ArchiveError::NoSnapshot {
+                    url: url.to_string(),
+                }
+                .into()),
+            }
+        })
+        .await
+    }
+
+    /// Download the latest existing snapshot without requesting a new one. Fails with
+    /// [`ArchiveError::NoSnapshot`] when it is missing or older than the maximum
+    /// snapshot age.
+    pub async fn archive_existing(&self, url: &str) -> Result<ArchiveResult> {
+        self.within_budget(url, async {
+            if let Some(timestamp) = &self.snapshot_timestamp {
+                return self.download_pinned(url, timestamp).await;
+            }
+
+            match self.latest_usable_snapshot(url).await {
+                Some(snapshot) => self.download_snapshot(url, snapshot).await,
+                None => Err(ArchiveError::NoSnapshot {
+                    url: url.to_string(),
+                }
+                .into()),
+            }
+        })
+        .await
+    }
+
+    /// The latest snapshot of `url`, if there is one and it is recent enough
+    async fn latest_usable_snapshot(&self, url: &str) -> Option<ArchiveSnapshot> {
+        // The availability API answer is cached, e.g. from polling for a failed save
+        match self.check_archived(url).await {
+            Ok(Some(snapshot)) if snapshot.is_usable() => return Some(snapshot),
+            Ok(_) => {}
+            Err(e) => debug!("Availability lookup for {url} failed: {e}"),
+        }
+
+        // The availability API lags behind, the archive page redirect doesn't
+        match self.get_latest_archive(url).await {
+            Ok(Some(snapshot)) if snapshot.is_usable() && !self.is_too_old(&snapshot) => {
+                Some(snapshot)
+            }
+            _ => None,
+        }
+    }
+
+    async fn download_snapshot(
+        &self,
+        url: &str,
+        snapshot: ArchiveSnapshot,
+    ) -> Result<ArchiveResult> {
+        info!("Using existing archive: {}", snapshot.url);
+        let download = match self.cached_by_wayback_digest(url, &snapshot).await {
+            Some(download) => download,
+            None => self.download_and_hash(&snapshot.url).await?,
+        };
+        verify_download(&download, self.source_format)
+            .map_err(|e| ArchiveError::invalid_content(&snapshot.url, e))?;
+
+        // Extract version number from archive timestamp
+        let timestamp = snapshot.parsed_timestamp()?;
+        let version = snapshot.timestamp;
+        self.report(ArchiveProgress::Done);
+
+        Ok(ArchiveResult {
+            original_url: url.to_string(),
+            archive_url: snapshot.url,
+            timestamp,
+            sha256: download.sha256,
+            digests: download.digests,
+            version,
+            source: ArchiveSource::ExistingFallback,
+            capture_status: snapshot.status,
+            content: download.bytes,
+            content_type: download.content_type,
+        })
+    }
+
+    /// Download the snapshot of `url` taken at `timestamp`, skipping save requests and
+    /// snapshot lookups. Its version is the requested timestamp.
+    async fn download_pinned(&self, url: &str, timestamp: &str) -> Result<ArchiveResult> {
+        parse_wayback_timestamp(timestamp)?;
+        info!("Using snapshot pinned at {timestamp}");
+
+        let snapshot = ArchiveSnapshot {
+            available: true,
+            url: self.raw_snapshot_url(timestamp, url),
+            timestamp: timestamp.to_string(),
+            status: None,
+        };
+        let result = self.download_snapshot(url, snapshot).await?;
+
+        Ok(ArchiveResult {
+            source: ArchiveSource::Pinned,
+            ..result
+        })
+    }
+
+    /// The cached body of `snapshot` when the CDX digest of its capture matches a file
+    /// downloaded before, so an unchanged snapshot isn't downloaded again. The CDX request
+    /// is only made with the opt-in download cache; a failed lookup falls back to
+    /// downloading.
+    async fn cached_by_wayback_digest(
+        &self,
+        url: &str,
+        snapshot: &ArchiveSnapshot,
+    ) -> Option<Download> {
+        let cache = self.download_cache.as_ref()?;
+        let capture = match self.cdx_capture(url, &snapshot.timestamp).await {
+            Ok(capture) => capture?,
+            Err(e) => {
+                warn!(
+                    "CDX lookup of {} failed, downloading it: {e:#}",
+                    snapshot.url
+                );
+                return None;
+            }
+        };
+        let entry = cache.lookup_wayback_digest(&capture.digest)?;
+        let download = self.cached_download(&entry, true)?;
+
+        info!(
+            digest = %capture.digest,
+            sha256 = %download.sha256,
+            "CDX digest of {} matches an earlier download, reusing it",
+            snapshot.url
+        );
+        self.report(ArchiveProgress::Downloading {
+            bytes: download.size,
+            total: download.content_length,
+        });
+        Some(download)
+    }
+
+    /// Download the original URL directly and compare its SHA256 with the archived copy
+    pub async fn compare_with_origin(
+        &self,
+        original_url: &str,
+        archived_sha256: &str,
+    ) -> Result<OriginComparison> {
+        info!(
+            headers = ?self.origin_headers,
+            "Downloading original file for comparison: {original_url}"
+        );
+        let origin = self.hash_url(original_url).await?;
+
+        if origin.sha256 == archived_sha256 {
+            Ok(OriginComparison::Match)
+        } else {
+            Ok(OriginComparison::Mismatch {
+                origin_sha256: origin.sha256,
+                archived_sha256: archived_sha256.to_string(),
+            })
+        }
     }
 
     /// Extract version number from archive URL (timestamp)
     fn extract_version_from_archive_url(&self, archive_url: &str) -> String {
-        // Extract timestamp from URL like https://web.archive.org/web/20231201000000/...
+        // Extract timestamp from URL like https://{host}/web/20231201000000/...
         if let Some(start) = archive_url.find("/web/") {
             let after_web = &archive_url[start + 5..];
             if let Some(end) = after_web.find('/') {
@@ -192,3 +1334,435 @@ impl WebArchiveClient {
         chrono::Utc::now().format("%Y%m%d%H%M%S").to_string()
     }
 }
+
+#[async_trait]
+impl Archiver for WebArchiveClient {
+    async fn archive_and_download(&self, url: &str) -> Result<ArchiveResult> {
+        WebArchiveClient::archive_and_download(self, url).await
+    }
+
+    async fn archive_fresh(&self, url: &str) -> Result<ArchiveResult> {
+        WebArchiveClient::archive_fresh(self, url).await
+    }
+
+    async fn archive_existing(&self, url: &str) -> Result<ArchiveResult> {
+        WebArchiveClient::archive_existing(self, url).await
+    }
+
+    async fn compare_with_origin(
+        &self,
+        original_url: &str,
+        archived_sha256: &str,
+    ) -> Result<OriginComparison> {
+        WebArchiveClient::compare_with_origin(self, original_url, archived_sha256).await
+    }
+}
+
+impl Default for WebArchiveClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The archiver `config` asks for: a single Wayback client, or a [`MultiArchiver`]
+/// racing one client per host in `providers`
+pub fn archiver_from_config(config: &Config) -> crate::error::Result<Box<dyn Archiver>> {
+    Ok(match config.providers.as_slice() {
+        [] => Box::new(WebArchiveClient::from_config(config)?),
+        [host] => Box::new(WebArchiveClient::from_config(config)?.with_host(host)),
+        hosts => Box::new(MultiArchiver::new(
+            hosts
+                .iter()
+                .map(|host| {
+                    let client: Arc<dyn Archiver> =
+                        Arc::new(WebArchiveClient::from_config(config)?.with_host(host));
+                    Ok((host.clone(), client))
+                })
+                .collect::<crate::error::Result<_>>()?,
+        )),
+    })
+}
+
+/// Archives through several providers at once and uses whichever succeeds first.
+///
+/// The remaining attempts keep running in the background so every provider still ends up
+/// with a snapshot where possible; their results are only logged.
+pub struct MultiArchiver {
+    providers: Vec<(String, Arc<dyn Archiver>)>,
+}
+
+impl MultiArchiver {
+    /// Race `providers`, each given a name for the logs
+    pub fn new(providers: Vec<(String, Arc<dyn Archiver>)>) -> Self {
+        Self { providers }
+    }
+
+    /// Start `attempt` on every provider and return the first success, or all errors
+    /// once every provider has failed
+    async fn first_success<F, Fut>(&self, attempt: F) -> Result<ArchiveResult>
+    where
+        F: Fn(Arc<dyn Archiver>) -> Fut,
+        Fut: Future<Output = Result<ArchiveResult>> + Send + 'static,
+    {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for (name, provider) in &self.providers {
+            let attempt = attempt(Arc::clone(provider));
+            let name = name.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let result = attempt.await;
+                if let Err(result) = tx.send((name, result)) {
+                    let (name, result) = result.0;
+                    match result {
+                        Ok(late) => info!(
+                            provider = %name,
+                            archive_url = %late.archive_url,
+                            "Archive provider finished after another one won"
+                        ),
+                        Err(e) => warn!(provider = %name, "Archive provider failed: {e:#}"),
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut errors = Vec::new();
+        while let Some((name, result)) = rx.recv().await {
+            match result {
+                Ok(result) => {
+                    info!(provider = %name, "Using the first successful archive provider");
+                    return Ok(result);
+                }
+                Err(e) => {
+                    warn!(provider = %name, "Archive provider failed: {e:#}");
+                    errors.push(format!("{name}: {e:#}"));
+                }
+            }
+        }
+
+        anyhow::bail!("All archive providers failed: {}", errors.join("; "))
+    }
+}
+
+#[async_trait]
+impl Archiver for MultiArchiver {
+    async fn archive_and_download(&self, url: &str) -> Result<ArchiveResult> {
+        self.archive_with_mode(url, ArchiveMode::CreateThenFallback)
+            .await
+    }
+
+    async fn archive_fresh(&self, url: &str) -> Result<ArchiveResult> {
+        self.archive_with_mode(url, ArchiveMode::CreateOnly).await
+    }
+
+    async fn archive_existing(&self, url: &str) -> Result<ArchiveResult> {
+        self.archive_with_mode(url, ArchiveMode::ExistingOnly).await
+    }
+
+    async fn archive_with_mode(&self, url: &str, mode: ArchiveMode) -> Result<ArchiveResult> {
+        let url = url.to_string();
+        self.first_success(|provider| {
+            let url = url.clone();
+            async move { provider.archive_with_mode(&url, mode).await }
+        })
+        .await
+    }
+
+    async fn compare_with_origin(
+        &self,
+        original_url: &str,
+        archived_sha256: &str,
+    ) -> Result<OriginComparison> {
+        match self.providers.first() {
+            Some((_, provider)) => {
+                provider
+                    .compare_with_origin(original_url, archived_sha256)
+                    .await
+            }
+            None => anyhow::bail!("No archive providers configured"),
+        }
+    }
+}
+
+/// Parse the JSON body of a Wayback save job status response.
+///
+/// For a successful job, `url` is the original URL that was captured.
+pub fn parse_job_status(body: &str) -> Result<ArchiveStatus> {
+    let status: SaveJobStatus = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("Unexpected save job status response: {e}"))?;
+
+    Ok(match status {
+        SaveJobStatus::Pending => ArchiveStatus::Pending,
+        SaveJobStatus::Success {
+            http_status: Some(status),
+            ..
+        } if is_suspect_status(status) => ArchiveStatus::Failed {
+            reason: format!("the capture recorded an upstream HTTP {status}"),
+        },
+        SaveJobStatus::Success {
+            timestamp,
+            original_url,
+            http_status,
+        } => ArchiveStatus::Success {
+            url: original_url,
+            timestamp,
+            http_status,
+        },
+        SaveJobStatus::Error {
+            status_ext,
+            message,
+        } => ArchiveStatus::Failed {
+            reason: message
+                .or(status_ext)
+                .unwrap_or_else(|| "unknown error".to_string()),
+        },
+    })
+}
+
+/// One capture listed by the Wayback CDX API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdxEntry {
+    pub timestamp: String,
+    pub original: String,
+    pub mimetype: String,
+    /// Upstream HTTP status of the capture; `None` for `-` (e.g. revisit records)
+    pub status: Option<u16>,
+    /// Base32 SHA1 of the captured body, as computed by Wayback
+    pub digest: String,
+    /// Size of the stored record; `None` when not reported
+    pub length: Option<u64>,
+}
+
+impl CdxEntry {
+    /// Whether the capture recorded an upstream server error (5xx)
+    pub fn is_suspect(&self) -> bool {
+        self.status.is_some_and(is_suspect_status)
+    }
+}
+
+/// Parse a CDX response with `output=json`: an array of string arrays whose first row
+/// names the columns. An empty array (no captures) yields no entries.
+pub fn parse_cdx(body: &str) -> Result<Vec<CdxEntry>> {
+    let rows: Vec<Vec<String>> =
+        serde_json::from_str(body).map_err(|e| anyhow::anyhow!("Unexpected CDX response: {e}"))?;
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok(Vec::new());
+    };
+
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| anyhow::anyhow!("CDX response has no {name} column"))
+    };
+    let timestamp = column("timestamp")?;
+    let original = column("original")?;
+    let mimetype = column("mimetype")?;
+    let status = column("statuscode")?;
+    let digest = column("digest")?;
+    let length = column("length")?;
+
+    rows.iter()
+        .map(|row| {
+            let field = |index: usize| {
+                row.get(index).cloned().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "CDX row has {} fields, expected {}",
+                        row.len(),
+                        header.len()
+                    )
+                })
+            };
+            Ok(CdxEntry {
+                timestamp: field(timestamp)?,
+                original: field(original)?,
+                mimetype: field(mimetype)?,
+                status: field(status)?.parse().ok(),
+                digest: field(digest)?,
+                length: field(length)?.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Whether a capture's recorded upstream status is a server error
+fn is_suspect_status(status: u16) -> bool {
+    (500..600).contains(&status)
+}
+
+/// The availability API reports a capture's status as a string (`"200"`), SPN2 as a number
+fn deserialize_status<'de, D>(deserializer: D) -> std::result::Result<Option<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Status {
+        Number(u16),
+        Text(String),
+    }
+
+    Ok(match Option::<Status>::deserialize(deserializer)? {
+        Some(Status::Number(status)) => Some(status),
+        Some(Status::Text(status)) => status.parse().ok(),
+        None => None,
+    })
+}
+
+/// Canonical form of a URL before it is embedded in Wayback requests.
+///
+/// The URL must be absolute http(s); the fragment is dropped, and the host, path and
+/// query are percent-encoded consistently (e.g. spaces become `%20`).
+pub fn normalize_url(url: &str) -> Result<String> {
+    let mut parsed =
+        url::Url::parse(url.trim()).map_err(|e| anyhow::anyhow!("Invalid URL '{url}': {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        anyhow::bail!("Unsupported URL scheme '{}' in {url}", parsed.scheme());
+    }
+    parsed.set_fragment(None);
+    Ok(parsed.into())
+}
+
+/// Parse a `Retry-After` header value, given either as delay seconds or as an HTTP-date.
+///
+/// Dates in the past yield a zero delay; unparsable values yield `None`.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Parse a 14-digit Wayback timestamp (`YYYYMMDDhhmmss`) into a UTC date
+pub fn parse_wayback_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    if timestamp.len() != 14 || !timestamp.chars().all(|c| c.is_ascii_digit()) {
+        anyhow::bail!(
+            "Invalid Wayback timestamp '{timestamp}': expected 14 digits (YYYYMMDDhhmmss)"
+        );
+    }
+
+    let naive = NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S")
+        .map_err(|e| anyhow::anyhow!("Invalid Wayback timestamp '{timestamp}': {e}"))?;
+
+    Ok(naive.and_utc())
+}
+
+/// Check that a download arrived complete, with a content type fitting `format` and not
+/// as an HTML page. This is cheaper than [`validate_source`] and names the likely cause.
+pub fn verify_download(download: &Download, format: SourceFormat) -> crate::error::Result<()> {
+    if let Some(expected) = download.content_length
+        && expected != download.size
+    {
+        return Err(AppError::TruncatedDownload {
+            expected,
+            received: download.size,
+        });
+    }
+
+    if let Some(content_type) = &download.content_type
+        && let Some(expected) = expected_content_types(format)
+    {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if !expected.contains(&mime.as_str()) {
+            return Err(AppError::UnexpectedContentType {
+                content_type: content_type.clone(),
+            });
+        }
+    }
+
+    if looks_like_html(&download.bytes) {
+        let head = String::from_utf8_lossy(&download.bytes[..download.bytes.len().min(64)]);
+        return Err(AppError::HtmlPage {
+            expected: format,
+            head: head.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+    }
+
+    Ok(())
+}
+
+/// Whether `bytes` start like an HTML document, e.g. a maintenance page served with
+/// status 200 where a binary was expected
+fn looks_like_html(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    ["<!doctype", "<html"].iter().any(|tag| {
+        head.get(..tag.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(tag))
+    })
+}
+
+/// Content types accepted for downloads in `format`, or `None` to accept any
+fn expected_content_types(format: SourceFormat) -> Option<&'static [&'static str]> {
+    match format {
+        SourceFormat::Zip => Some(ZIP_CONTENT_TYPES),
+        SourceFormat::Tar | SourceFormat::TarGz => Some(TAR_CONTENT_TYPES),
+        SourceFormat::Raw => None,
+    }
+}
+
+/// Check that downloaded bytes are a file in `format`: a ZIP archive whose central
+/// directory parses, a tar archive with a `ustar` header or a gzip stream. Raw files
+/// are not checked.
+pub fn validate_source(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    format: SourceFormat,
+) -> crate::error::Result<()> {
+    match format {
+        SourceFormat::Zip => validate_zip(bytes, content_type),
+        SourceFormat::Tar => {
+            let magic = bytes.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+            if magic == Some(TAR_MAGIC) {
+                Ok(())
+            } else {
+                Err(invalid_archive("tar archive", bytes, content_type))
+            }
+        }
+        // The tar header is compressed, so only the gzip framing can be checked cheaply
+        SourceFormat::TarGz if bytes.starts_with(GZIP_MAGIC) => Ok(()),
+        SourceFormat::TarGz => Err(invalid_archive(
+            "gzip-compressed tarball",
+            bytes,
+            content_type,
+        )),
+        SourceFormat::Raw => Ok(()),
+    }
+}
+
+/// Check that downloaded bytes are a ZIP archive whose central directory parses
+pub fn validate_zip(bytes: &[u8], content_type: Option<&str>) -> crate::error::Result<()> {
+    if bytes.starts_with(ZIP_MAGIC) && zip::ZipArchive::new(Cursor::new(bytes)).is_ok() {
+        return Ok(());
+    }
+
+    Err(invalid_archive("ZIP archive", bytes, content_type))
+}
+
+fn invalid_archive(expected: &'static str, bytes: &[u8], content_type: Option<&str>) -> AppError {
+    let head = bytes
+        .iter()
+        .take(16)
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    AppError::InvalidArchive {
+        expected,
+        content_type: content_type.unwrap_or("unknown").to_string(),
+        head,
+    }
+}
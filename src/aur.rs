@@ -1,103 +1,667 @@
+use crate::{archive::DEFAULT_WAYBACK_HOST, checksum::ChecksumKind};
 use anyhow::{Result, anyhow};
-use regex::Regex;
-use std::{fs, path::Path};
+use regex::{NoExpand, Regex};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// PKGBUILD written for a new package when no template file is configured.
+///
+/// `{pkgname}`, `{pkgver}`, `{sha256}`, `{url}` (the original URL), `{wayback_host}`,
+/// `{source_filename}` and the [`InstallTarget`] fields `{install_source}`, `{install_dir}`,
+/// `{install_dest}` and `{install_mode}` are substituted; shell expansions such as
+/// `${pkgver}` are left alone.
+pub const DEFAULT_PKGBUILD_TEMPLATE: &str = r#"# Maintainer: DeepChirp <DeepChirp@outlook.com>
+pkgname={pkgname}
+pkgver={pkgver}
+pkgrel=1
+pkgdesc='Contains the Key Database for the AACS Library (Daily Updates)'
+arch=('any')
+url='http://fvonline-db.bplaced.net/'
+depends=('libaacs')
+source=("{source_filename}::https://{wayback_host}/web/${pkgver}/{url}")
+sha256sums=('{sha256}')
+
+package() {
+    install -d "${pkgdir}{install_dir}" || return 1
+    install -Dm{install_mode} "${srcdir}/{install_source}" "${pkgdir}{install_dest}" || return 1
+}
+"#;
+
+/// Fields a rendered PKGBUILD template must assign
+const REQUIRED_PKGBUILD_FIELDS: &[&str] = &["pkgname", "pkgver", "pkgrel", "source", "sha256sums"];
+
+/// `pkgbase` and the package names built from a PKGBUILD; a split package has several
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageNames {
+    pub base: String,
+    pub names: Vec<String>,
+}
+
+impl PackageNames {
+    fn single(name: &str) -> Self {
+        Self {
+            base: name.to_string(),
+            names: vec![name.to_string()],
+        }
+    }
+}
+
+/// Which file of the source `package()` installs, where and with which mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallTarget {
+    /// Path of the file inside the extracted source
+    pub source: String,
+    /// Absolute install path
+    pub dest: String,
+    /// Octal file mode, e.g. `644`
+    pub mode: String,
+}
+
+impl Default for InstallTarget {
+    fn default() -> Self {
+        Self {
+            source: "keydb.cfg".to_string(),
+            dest: "/etc/xdg/aacs/KEYDB.cfg".to_string(),
+            mode: "644".to_string(),
+        }
+    }
+}
+
+impl InstallTarget {
+    /// Directory `dest` is installed into
+    pub fn dir(&self) -> &str {
+        match self.dest.rsplit_once('/') {
+            Some(("", _)) | None => "/",
+            Some((dir, _)) => dir,
+        }
+    }
+
+    /// Check that `dest` is absolute, `mode` is octal and neither path can break out of
+    /// the quoted `install` arguments
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.source.trim().is_empty() {
+            return Err("install source cannot be empty".to_string());
+        }
+        if !self.dest.starts_with('/') || self.dest.ends_with('/') {
+            return Err(format!(
+                "install destination {} must be an absolute file path",
+                self.dest
+            ));
+        }
+        if !(3..=4).contains(&self.mode.len())
+            || !self.mode.bytes().all(|b| (b'0'..=b'7').contains(&b))
+        {
+            return Err(format!(
+                "install mode {} is not an octal mode like 644",
+                self.mode
+            ));
+        }
+        if let Some(path) = [&self.source, &self.dest]
+            .into_iter()
+            .find(|path| path.contains(['"', '$', '`', '\\']) || path.contains(char::is_control))
+        {
+            return Err(format!("install path {path} contains shell metacharacters"));
+        }
+        Ok(())
+    }
+}
+
+/// The release fields written alongside pkgver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PkgRelease {
+    pub epoch: Option<u32>,
+    pub pkgrel: u32,
+}
 
 pub struct AurPackageManager {
     package_name: String,
-    original_url: String,
+    source_filename_template: String,
+    pkgbuild_template_path: Option<PathBuf>,
+    pkgrel: Option<u32>,
+    install_target: InstallTarget,
+    wayback_host: String,
 }
 
 impl AurPackageManager {
-    pub fn new(package_name: String, original_url: String) -> Self {
+    pub fn new(package_name: String, source_filename_template: String) -> Self {
         Self {
             package_name,
-            original_url,
+            source_filename_template,
+            pkgbuild_template_path: None,
+            pkgrel: None,
+            install_target: InstallTarget::default(),
+            wayback_host: DEFAULT_WAYBACK_HOST.to_string(),
         }
     }
 
+    /// Point the source of new PKGBUILDs at this Wayback host instead of web.archive.org.
+    /// A scheme is dropped, since the source is always fetched over HTTPS.
+    pub fn with_wayback_host(mut self, host: &str) -> Self {
+        self.wayback_host = host
+            .split_once("://")
+            .map_or(host, |(_, host)| host)
+            .trim_end_matches('/')
+            .to_string();
+        self
+    }
+
+    /// Install this file from `package()` of new PKGBUILDs instead of `keydb.cfg` as
+    /// `/etc/xdg/aacs/KEYDB.cfg`
+    pub fn with_install_target(mut self, target: InstallTarget) -> Self {
+        self.install_target = target;
+        self
+    }
+
+    /// Write this pkgrel verbatim instead of resetting it to 1 or bumping it
+    pub fn with_pkgrel(mut self, pkgrel: Option<u32>) -> Self {
+        self.pkgrel = pkgrel;
+        self
+    }
+
+    /// Render new PKGBUILDs from this template file instead of the built-in template
+    pub fn with_pkgbuild_template(mut self, path: Option<PathBuf>) -> Self {
+        self.pkgbuild_template_path = path;
+        self
+    }
+
+    /// Source filename with `$pkgver`/`${pkgver}` expanded, as written to .SRCINFO
+    pub fn source_filename(&self, version: &str) -> String {
+        self.source_filename_template
+            .replace("${pkgver}", version)
+            .replace("$pkgver", version)
+    }
+
+    /// Read `pkgver` from an existing PKGBUILD
     pub fn extract_current_version(&self, pkgbuild_path: &Path) -> Result<String> {
         let content = fs::read_to_string(pkgbuild_path)?;
 
-        let version_regex = Regex::new(r"pkgver=([^\s]+)")?;
+        let version_regex = Regex::new(r"(?m)^pkgver=([^\s]+)")?;
 
-        if let Some(captures) = version_regex.captures(&content) {
-            if let Some(version) = captures.get(1) {
-                return Ok(version.as_str().to_string());
-            }
+        if let Some(captures) = version_regex.captures(&content)
+            && let Some(version) = captures.get(1)
+        {
+            return Ok(version.as_str().to_string());
         }
 
         Err(anyhow!("Could not find pkgver in PKGBUILD"))
     }
 
-    pub fn extract_current_sha256(&self, pkgbuild_path: &Path) -> Result<String> {
+    /// Read the single entry of `sha256sums` from an existing PKGBUILD.
+    ///
+    /// Returns `None` for a `SKIP` placeholder, which has nothing to compare against, and
+    /// an error for anything that is not 64 lowercase hex characters.
+    pub fn extract_current_sha256(&self, pkgbuild_path: &Path) -> Result<Option<String>> {
         let content = fs::read_to_string(pkgbuild_path)?;
 
-        let sha256_regex = Regex::new(r"sha256sums=\('([^']+)'\)")?;
+        let sha256_regex = Regex::new(r"(?m)^sha256sums=\('([^']+)'\)")?;
 
-        if let Some(captures) = sha256_regex.captures(&content) {
-            if let Some(sha256) = captures.get(1) {
-                return Ok(sha256.as_str().to_string());
-            }
+        if let Some(captures) = sha256_regex.captures(&content)
+            && let Some(sha256) = captures.get(1)
+        {
+            return match sha256.as_str() {
+                "SKIP" => Ok(None),
+                sha256 if is_sha256(sha256) => Ok(Some(sha256.to_string())),
+                sha256 => Err(anyhow!("Malformed sha256sums entry in PKGBUILD: {sha256}")),
+            };
         }
 
         Err(anyhow!("Could not find sha256sums in PKGBUILD"))
     }
 
+    /// Read `pkgrel` from an existing PKGBUILD
+    pub fn extract_current_pkgrel(&self, pkgbuild_path: &Path) -> Result<u32> {
+        let content = fs::read_to_string(pkgbuild_path)?;
+
+        let pkgrel_regex = Regex::new(r"(?m)^pkgrel=([^\s]+)")?;
+
+        if let Some(captures) = pkgrel_regex.captures(&content)
+            && let Some(pkgrel) = captures.get(1)
+        {
+            return pkgrel
+                .as_str()
+                .parse()
+                .map_err(|_| anyhow!("Invalid pkgrel in PKGBUILD: {}", pkgrel.as_str()));
+        }
+
+        Err(anyhow!("Could not find pkgrel in PKGBUILD"))
+    }
+
+    /// Read `epoch` from an existing PKGBUILD, if it declares one
+    pub fn extract_current_epoch(&self, pkgbuild_path: &Path) -> Result<Option<u32>> {
+        let content = fs::read_to_string(pkgbuild_path)?;
+
+        let epoch_regex = Regex::new(r"(?m)^epoch=([^\s]+)")?;
+
+        match epoch_regex.captures(&content).and_then(|c| c.get(1)) {
+            Some(epoch) => epoch
+                .as_str()
+                .parse()
+                .map(Some)
+                .map_err(|_| anyhow!("Invalid epoch in PKGBUILD: {}", epoch.as_str())),
+            None => Ok(None),
+        }
+    }
+
+    /// Update pkgver, sha256sums and pkgrel in an existing PKGBUILD, and point the Wayback
+    /// URL in `source` at `original_url`.
+    ///
+    /// Only those assignments are touched; everything else in the file is kept as is,
+    /// and fields missing from the file are inserted rather than regenerating it.
+    ///
+    /// pkgrel is bumped when the version is unchanged and reset to 1 when it changes,
+    /// unless an explicit pkgrel was set with [`Self::with_pkgrel`].
+    /// When `bump_epoch` is set, epoch is incremented (or added as 1) so that an older
+    /// looking pkgver still supersedes the current package.
     pub fn update_pkgbuild(
         &self,
         pkgbuild_path: &Path,
+        original_url: &str,
+        old_version: &str,
         new_version: &str,
         new_sha256: &str,
-    ) -> Result<()> {
+        bump_epoch: bool,
+    ) -> Result<PkgRelease> {
+        let pkgrel = if let Some(pkgrel) = self.pkgrel {
+            pkgrel
+        } else if old_version == new_version {
+            self.extract_current_pkgrel(pkgbuild_path)
+                .map(|pkgrel| pkgrel + 1)
+                .unwrap_or(1)
+        } else {
+            1
+        };
+
+        let current_epoch = self.extract_current_epoch(pkgbuild_path)?;
+        let epoch = if bump_epoch {
+            Some(current_epoch.unwrap_or(0) + 1)
+        } else {
+            current_epoch
+        };
+
         let mut content = fs::read_to_string(pkgbuild_path)?;
 
-        let version_regex = Regex::new(r"pkgver=([^\s]+)")?;
-        content = version_regex
-            .replace(&content, format!("pkgver={new_version}"))
-            .to_string();
+        content = set_field(&content, "pkgver", new_version, &["pkgname", "pkgbase"])?;
+        content = set_field(&content, "pkgrel", &pkgrel.to_string(), &["pkgver"])?;
+        if let Some(epoch) = epoch {
+            content = set_field(&content, "epoch", &epoch.to_string(), &["pkgrel"])?;
+        }
+        content = set_source_url(&content, original_url)?;
+        content = set_field(
+            &content,
+            "sha256sums",
+            &format!("('{new_sha256}')"),
+            &["source", "pkgrel"],
+        )?;
 
-        let sha256_regex = Regex::new(r"sha256sums=\('([^']+)'\)")?;
-        content = sha256_regex
-            .replace(&content, format!("sha256sums=('{new_sha256}')"))
-            .to_string();
+        fs::write(pkgbuild_path, content)?;
+        Ok(PkgRelease { epoch, pkgrel })
+    }
 
-        let pkgrel_regex = Regex::new(r"pkgrel=([^\s]+)")?;
-        content = pkgrel_regex.replace(&content, "pkgrel=1").to_string();
+    /// Fill every integrity array besides `sha256sums` that the PKGBUILD declares
+    /// (`md5sums`, `b2sums`) with the matching entry of `digests`
+    pub fn update_checksums(
+        &self,
+        pkgbuild_path: &Path,
+        digests: &HashMap<ChecksumKind, String>,
+    ) -> Result<()> {
+        let mut content = fs::read_to_string(pkgbuild_path)?;
+
+        for kind in extra_checksum_kinds() {
+            if field_values(&content, kind.field())?.is_none() {
+                continue;
+            }
+            let Some(digest) = digests.get(&kind) else {
+                return Err(anyhow!(
+                    "PKGBUILD declares {} but no {} digest was computed, add {} to CHECKSUMS",
+                    kind.field(),
+                    kind.name(),
+                    kind.name()
+                ));
+            };
+            content = set_field(&content, kind.field(), &format!("('{digest}')"), &[])?;
+        }
 
         fs::write(pkgbuild_path, content)?;
         Ok(())
     }
 
-    /// Generate .SRCINFO file
+    /// Read `pkgbase` and the `pkgname` (or array of names, for a split package) from an
+    /// existing PKGBUILD. A missing PKGBUILD yields this manager's package name for both.
+    pub fn extract_package_names(&self, pkgbuild_path: &Path) -> Result<PackageNames> {
+        let content = match fs::read_to_string(pkgbuild_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(PackageNames::single(&self.package_name));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let names = field_values(&content, "pkgname")?
+            .filter(|names| !names.is_empty())
+            .unwrap_or_else(|| vec![self.package_name.clone()]);
+        let base = match field_values(&content, "pkgbase")? {
+            Some(base) if !base.is_empty() => base.join(" "),
+            _ => names[0].clone(),
+        };
+
+        Ok(PackageNames { base, names })
+    }
+
+    /// Generate .SRCINFO file, with one `pkgname` section per name of a split package and
+    /// the other integrity arrays the PKGBUILD declares after `sha256sums`
     pub fn generate_srcinfo(
         &self,
-        _pkgbuild_path: &Path,
+        pkgbuild_path: &Path,
         version: &str,
+        release: PkgRelease,
         sha256: &str,
         url: &str,
     ) -> Result<String> {
-        let source_line = format!("keydb_eng-{version}.zip::{url}");
-        let srcinfo = format!(
-            "pkgbase = {}\n\tpkgdesc = Contains the Key Database for the AACS Library (Daily Updates)\n\tpkgver = {}\n\tpkgrel = 1\n\turl = http://fvonline-db.bplaced.net/\n\tarch = any\n\tdepends = libaacs\n\tsource = {}\n\tsha256sums = {}\n\npkgname = {}\n",
-            self.package_name, version, source_line, sha256, self.package_name
+        let package_names = self.extract_package_names(pkgbuild_path)?;
+        let url = escape_source_url(url)?;
+        let source_line = format!("{}::{url}", self.source_filename(version));
+        let epoch_line = release
+            .epoch
+            .map(|epoch| format!("\tepoch = {epoch}\n"))
+            .unwrap_or_default();
+        let mut srcinfo = format!(
+            "pkgbase = {}\n\tpkgdesc = Contains the Key Database for the AACS Library (Daily Updates)\n\tpkgver = {}\n\tpkgrel = {}\n{}\turl = http://fvonline-db.bplaced.net/\n\tarch = any\n\tdepends = libaacs\n\tsource = {}\n\tsha256sums = {}\n",
+            package_names.base, version, release.pkgrel, epoch_line, source_line, sha256,
         );
+        let pkgbuild = fs::read_to_string(pkgbuild_path).unwrap_or_default();
+        for kind in extra_checksum_kinds() {
+            for digest in field_values(&pkgbuild, kind.field())?.unwrap_or_default() {
+                srcinfo.push_str(&format!("\t{} = {digest}\n", kind.field()));
+            }
+        }
+        for name in &package_names.names {
+            srcinfo.push_str(&format!("\npkgname = {name}\n"));
+        }
 
         Ok(srcinfo)
     }
 
-    /// Create initial PKGBUILD file (if it doesn't exist)
+    /// Create initial PKGBUILD file (if it doesn't exist), sourcing the archive of
+    /// `original_url`. pkgrel is 1 unless set with [`Self::with_pkgrel`].
     pub fn create_initial_pkgbuild(
         &self,
         pkgbuild_path: &Path,
+        original_url: &str,
         version: &str,
         sha256: &str,
-    ) -> Result<()> {
-        let pkgbuild_content = format!(
-            "# Maintainer: DeepChirp <DeepChirp@outlook.com>\npkgname={}\npkgver={}\npkgrel=1\npkgdesc='Contains the Key Database for the AACS Library (Daily Updates)'\narch=('any')\nurl='http://fvonline-db.bplaced.net/'\ndepends=('libaacs')\nsource=(\"keydb_eng-${{pkgver}}.zip::https://web.archive.org/web/${{pkgver}}/{}\")\nsha256sums=('{}')\n\npackage() {{\n    install -d \"${{pkgdir}}/etc/xdg/aacs\" || return 1\n    install -Dm644 \"${{srcdir}}/keydb.cfg\" \"${{pkgdir}}/etc/xdg/aacs/KEYDB.cfg\" || return 1\n}}\n",
-            self.package_name, version, self.original_url, sha256
-        );
+    ) -> Result<PkgRelease> {
+        let template = match &self.pkgbuild_template_path {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| anyhow!("Could not read PKGBUILD template {}: {e}", path.display()))?,
+            None => DEFAULT_PKGBUILD_TEMPLATE.to_string(),
+        };
+
+        let mut pkgbuild_content =
+            self.render_pkgbuild_template(&template, original_url, version, sha256)?;
+        if let Some(pkgrel) = self.pkgrel {
+            pkgbuild_content = set_field(&pkgbuild_content, "pkgrel", &pkgrel.to_string(), &[])?;
+        }
 
         fs::write(pkgbuild_path, pkgbuild_content)?;
-        Ok(())
+        Ok(PkgRelease {
+            epoch: None,
+            pkgrel: self.pkgrel.unwrap_or(1),
+        })
+    }
+
+    /// Substitute the placeholders of a PKGBUILD template and check the result assigns
+    /// every required field
+    pub fn render_pkgbuild_template(
+        &self,
+        template: &str,
+        original_url: &str,
+        version: &str,
+        sha256: &str,
+    ) -> Result<String> {
+        let original_url = &escape_source_url(original_url)?;
+        // A `$` in front marks a shell expansion like `${pkgver}`, not a placeholder
+        let placeholder = Regex::new(
+            r"(^|[^$])\{(pkgname|pkgver|sha256|url|wayback_host|source_filename|install_source|install_dir|install_dest|install_mode)\}",
+        )?;
+        let rendered = placeholder.replace_all(template, |caps: &regex::Captures| {
+            let value = match &caps[2] {
+                "pkgname" => self.package_name.as_str(),
+                "pkgver" => version,
+                "sha256" => sha256,
+                "url" => original_url,
+                "wayback_host" => self.wayback_host.as_str(),
+                "install_source" => self.install_target.source.as_str(),
+                "install_dir" => self.install_target.dir(),
+                "install_dest" => self.install_target.dest.as_str(),
+                "install_mode" => self.install_target.mode.as_str(),
+                _ => self.source_filename_template.as_str(),
+            };
+            format!("{}{value}", &caps[1])
+        });
+
+        let missing: Vec<&str> = REQUIRED_PKGBUILD_FIELDS
+            .iter()
+            .copied()
+            .filter(|field| {
+                !Regex::new(&format!(r"(?m)^{field}=")).is_ok_and(|regex| regex.is_match(&rendered))
+            })
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Rendered PKGBUILD template is missing required fields: {}",
+                missing.join(", ")
+            ));
+        }
+
+        Ok(rendered.into_owned())
+    }
+}
+
+/// Set a top-level `name=value` assignment in PKGBUILD content.
+///
+/// An existing assignment (including a multi-line array) is replaced in place. A missing
+/// one is inserted after the first assignment of one of the `after` fields, or after the
+/// leading comment block when none of them exist.
+fn set_field(content: &str, name: &str, value: &str, after: &[&str]) -> Result<String> {
+    let line = format!("{name}={value}");
+
+    let existing = Regex::new(&format!(r"(?m)^{name}=(\([^)]*\)|\S*)"))?;
+    if existing.is_match(content) {
+        return Ok(existing.replace(content, NoExpand(&line)).into_owned());
+    }
+
+    for anchor in after {
+        let anchor_regex = Regex::new(&format!(r"(?m)^{anchor}=(\([^)]*\)|\S*).*$"))?;
+        if let Some(m) = anchor_regex.find(content) {
+            let mut updated = content.to_string();
+            updated.insert_str(m.end(), &format!("\n{line}"));
+            return Ok(updated);
+        }
+    }
+
+    let header_len: usize = content
+        .split_inclusive('\n')
+        .take_while(|l| l.starts_with('#'))
+        .map(str::len)
+        .sum();
+    let mut updated = content.to_string();
+    updated.insert_str(header_len, &format!("{line}\n"));
+    Ok(updated)
+}
+
+/// Replace the original URL embedded after `/web/${pkgver}/` in the `source` assignment.
+///
+/// Fails when `source` has no such Wayback URL and doesn't mention `original_url` either,
+/// since the package would then download something other than what was archived.
+fn set_source_url(content: &str, original_url: &str) -> Result<String> {
+    let original_url = &escape_source_url(original_url)?;
+    let source = Regex::new(r"(?m)^source=(\([^)]*\)|\S*)")?;
+    let Some(assignment) = source.find(content) else {
+        return Err(anyhow!("Could not find source in PKGBUILD"));
+    };
+
+    let wayback_url = Regex::new(r#"(/web/\$\{?pkgver\}?/)[^"'\s)]+"#)?;
+    if wayback_url.is_match(assignment.as_str()) {
+        let updated = wayback_url.replace(assignment.as_str(), |caps: &regex::Captures| {
+            format!("{}{original_url}", &caps[1])
+        });
+        if updated != assignment.as_str() {
+            info!("Pointing PKGBUILD source at {original_url}");
+        }
+        let mut content = content.to_string();
+        content.replace_range(assignment.range(), &updated);
+        return Ok(content);
+    }
+
+    if assignment.as_str().contains(original_url) {
+        return Ok(content.to_string());
+    }
+    Err(anyhow!(
+        "PKGBUILD source neither uses a /web/${{pkgver}}/ Wayback URL nor references {original_url}"
+    ))
+}
+
+/// Checksum kinds written in addition to the always present `sha256sums`
+fn extra_checksum_kinds() -> impl Iterator<Item = ChecksumKind> {
+    ChecksumKind::ALL
+        .into_iter()
+        .filter(|kind| *kind != ChecksumKind::Sha256)
+}
+
+/// Values of a top-level `name=value` or `name=(...)` assignment in PKGBUILD content,
+/// with quotes stripped; `None` when the field isn't assigned
+fn field_values(content: &str, name: &str) -> Result<Option<Vec<String>>> {
+    let regex = Regex::new(&format!(r"(?m)^{name}=(\([^)]*\)|\S*)"))?;
+    Ok(regex.captures(content).map(|c| {
+        c[1].trim_matches(|c| matches!(c, '(' | ')'))
+            .split_whitespace()
+            .map(|value| value.trim_matches(|c| matches!(c, '\'' | '"')).to_string())
+            .collect()
+    }))
+}
+
+/// Make `url` safe to place in a PKGBUILD `source` array and a .SRCINFO `source =` line.
+///
+/// Whitespace, quotes, parentheses and the shell metacharacters `$`, `` ` `` and `\` are
+/// percent-encoded; existing `%XX` escapes are kept, so escaping twice changes nothing.
+/// Control characters can't be part of a URL and are rejected.
+pub fn escape_source_url(url: &str) -> Result<String> {
+    if let Some(c) = url.chars().find(|c| c.is_control()) {
+        return Err(anyhow!(
+            "URL contains a control character ({:?}), refusing to put it in source: {url:?}",
+            c
+        ));
     }
+
+    let mut escaped = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            ' ' | '"' | '\'' | '(' | ')' | '$' | '`' | '\\' => {
+                escaped.push_str(&format!("%{:02X}", c as u32));
+            }
+            c if c.is_whitespace() => {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    escaped.push_str(&format!("%{byte:02X}"));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+/// Check that `pkgver`, `pkgrel`, `epoch` and the integrity arrays in a .SRCINFO agree
+/// with the PKGBUILD it was generated for, since the AUR rejects pushes where the two are
+/// out of sync
+pub fn check_srcinfo_sync(pkgbuild: &str, srcinfo: &str) -> crate::error::Result<()> {
+    for field in [
+        "pkgver",
+        "pkgrel",
+        "epoch",
+        "sha256sums",
+        "md5sums",
+        "b2sums",
+    ] {
+        let in_pkgbuild = field_values(pkgbuild, field)?.map(|values| values.join(" "));
+        let in_srcinfo = Regex::new(&format!(r"(?m)^\s*{field} = (.*)$"))
+            .map_err(anyhow::Error::from)?
+            .captures_iter(srcinfo)
+            .map(|c| c[1].trim().to_string())
+            .collect::<Vec<_>>();
+        let in_srcinfo = (!in_srcinfo.is_empty()).then(|| in_srcinfo.join(" "));
+
+        if in_pkgbuild != in_srcinfo {
+            return Err(crate::error::AppError::SrcinfoMismatch {
+                field,
+                pkgbuild: in_pkgbuild.unwrap_or_default(),
+                srcinfo: in_srcinfo.unwrap_or_default(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `value` is a SHA256 digest: exactly 64 lowercase hex characters
+pub fn is_sha256(value: &str) -> bool {
+    value.len() == 64
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Compare two package versions the way pacman's `vercmp` does for plain pkgver strings.
+///
+/// Versions are split into alphanumeric segments; numeric segments compare numerically,
+/// alphabetic segments lexically, and a numeric segment is newer than an alphabetic one.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    let segments = |v: &str| -> Vec<String> {
+        let mut out = Vec::new();
+        let mut current = String::new();
+        for c in v.chars() {
+            let boundary = match current.chars().last() {
+                Some(last) => {
+                    !c.is_ascii_alphanumeric() || last.is_ascii_digit() != c.is_ascii_digit()
+                }
+                None => false,
+            };
+            if boundary && !current.is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+            if c.is_ascii_alphanumeric() {
+                current.push(c);
+            }
+        }
+        if !current.is_empty() {
+            out.push(current);
+        }
+        out
+    };
+
+    let (a, b) = (segments(a), segments(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let x_numeric = x.starts_with(|c: char| c.is_ascii_digit());
+        let y_numeric = y.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = match (x_numeric, y_numeric) {
+            (true, true) => {
+                let x = x.trim_start_matches('0');
+                let y = y.trim_start_matches('0');
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.len().cmp(&b.len())
 }
@@ -0,0 +1,37 @@
+use std::{future::Future, sync::Arc};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// Run `run` on every item in its own task, with at most `max_concurrency` of them in
+/// flight at once, and return the results in the order of `items`.
+///
+/// A `max_concurrency` of 0 is treated as 1.
+pub async fn run_bounded<I, T, F, Fut>(items: Vec<I>, max_concurrency: usize, run: F) -> Vec<T>
+where
+    I: Send + 'static,
+    T: Send + 'static,
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(max_concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        let permits = Arc::clone(&permits);
+        let task = run(item);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (index, task.await)
+        });
+    }
+
+    let mut results: Vec<(usize, T)> = Vec::with_capacity(tasks.len());
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
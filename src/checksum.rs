@@ -0,0 +1,96 @@
+use blake2::Blake2b512;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, digest::DynDigest};
+use std::collections::HashMap;
+
+/// Integrity array a PKGBUILD can declare for its sources
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumKind {
+    Md5,
+    Sha256,
+    /// BLAKE2b-512, as computed by `b2sum`
+    B2,
+}
+
+impl ChecksumKind {
+    pub const ALL: [ChecksumKind; 3] = [ChecksumKind::Md5, ChecksumKind::Sha256, ChecksumKind::B2];
+
+    /// Name of the PKGBUILD and .SRCINFO field, e.g. `b2sums`
+    pub fn field(self) -> &'static str {
+        match self {
+            ChecksumKind::Md5 => "md5sums",
+            ChecksumKind::Sha256 => "sha256sums",
+            ChecksumKind::B2 => "b2sums",
+        }
+    }
+
+    /// `md5`, `sha256` or `b2`, as accepted by [`Self::parse`]
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumKind::Md5 => "md5",
+            ChecksumKind::Sha256 => "sha256",
+            ChecksumKind::B2 => "b2",
+        }
+    }
+
+    /// Parse `md5`, `sha256` or `b2`, case-insensitively
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            ChecksumKind::Md5 => Box::new(Md5::new()),
+            ChecksumKind::Sha256 => Box::new(Sha256::new()),
+            ChecksumKind::B2 => Box::new(Blake2b512::new()),
+        }
+    }
+}
+
+/// Computes several digests in a single pass over streamed bytes
+pub struct MultiHasher {
+    hashers: Vec<(ChecksumKind, Box<dyn DynDigest + Send>)>,
+}
+
+impl MultiHasher {
+    /// Hasher for each of `kinds`, ignoring duplicates
+    pub fn new(kinds: &[ChecksumKind]) -> Self {
+        let mut kinds = kinds.to_vec();
+        kinds.sort();
+        kinds.dedup();
+        Self {
+            hashers: kinds
+                .into_iter()
+                .map(|kind| (kind, kind.hasher()))
+                .collect(),
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(chunk);
+        }
+    }
+
+    /// Lowercase hex digest of each kind
+    pub fn finalize(self) -> HashMap<ChecksumKind, String> {
+        self.hashers
+            .into_iter()
+            .map(|(kind, hasher)| (kind, hex(&hasher.finalize())))
+            .collect()
+    }
+}
+
+/// Digests of `kinds` over `bytes`
+pub fn digest_all(kinds: &[ChecksumKind], bytes: &[u8]) -> HashMap<ChecksumKind, String> {
+    let mut hasher = MultiHasher::new(kinds);
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,139 @@
+use aur_aacs_keydb::{Config, archive};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use tracing::Level;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Human,
+    /// One JSON object per event, for log pipelines
+    Json,
+}
+
+/// Keep the aacs-keydb-daily AUR package in sync with the latest KeyDB archive
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Regenerate and push the package even if it is already up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Write this pkgrel instead of resetting it to 1 or bumping it, e.g. to correct a
+    /// botched release
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub pkgrel: Option<u32>,
+
+    /// Archive and regenerate the package for real, but never clone, commit or push;
+    /// the files are written to a scratch dir
+    #[arg(long)]
+    pub no_git: bool,
+
+    /// Download the Wayback snapshot taken at this timestamp (YYYYMMDDhhmmss) instead of
+    /// archiving the original URL, e.g. to reproduce or restore a known-good version
+    #[arg(long, value_parser = parse_snapshot_timestamp)]
+    pub snapshot_timestamp: Option<String>,
+
+    /// Log output format
+    #[arg(long, value_enum, env = "LOG_FORMAT", default_value_t = LogFormat::Human)]
+    pub log_format: LogFormat,
+
+    /// More log output: -v for debug, -vv for trace
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Less log output: -q for warnings only, -qq for errors only
+    #[arg(short, long, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Log level (trace, debug, info, warn, error) instead of -v/-q; RUST_LOG wins if set
+    #[arg(long, conflicts_with_all = ["verbose", "quiet"])]
+    pub log_level: Option<Level>,
+
+    /// Exit code when nothing was updated (up to date, dry run or skipped)
+    #[arg(long, env = "EXIT_CODE_NOOP", default_value_t = 0)]
+    pub exit_code_noop: u8,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Render PKGBUILD and .SRCINFO and print them, without cloning or pushing anything
+    Generate {
+        /// Original URL to package instead of the configured one
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Use this SHA256 instead of the archived file's
+        #[arg(long)]
+        sha256: Option<String>,
+
+        /// Use this version instead of the archive timestamp
+        #[arg(long)]
+        version: Option<String>,
+    },
+
+    /// Report whether an update is available, without committing or pushing anything.
+    /// Exits with 0 when an update is available and 2 when up to date
+    Check,
+
+    /// Check prerequisites (SSH key, AUR access, original URL, tools) and report pass/fail
+    Doctor,
+
+    /// List the Wayback captures of the original URL from the CDX API, to see which
+    /// snapshots a run could pick
+    Snapshots,
+
+    /// Keep running and update on a schedule instead of once. SIGINT/SIGTERM let the
+    /// current run finish, then exit
+    Daemon {
+        /// Interval (e.g. 6h, 30m, 3600) or cron expression in UTC (e.g. "0 4 * * *")
+        #[arg(long, env = "DAEMON_SCHEDULE", default_value = "24h")]
+        schedule: String,
+    },
+}
+
+impl Cli {
+    /// Base log level selected by `--log-level` or `-v`/`-q`
+    pub fn level(&self) -> Level {
+        self.log_level
+            .unwrap_or_else(|| level_for(self.verbose, self.quiet))
+    }
+
+    /// Apply command line overrides on top of the environment configuration
+    pub fn apply(&self, config: &mut Config) {
+        if self.force {
+            config.force = true;
+        }
+        if self.no_git {
+            config.no_git = true;
+        }
+        if let Some(pkgrel) = self.pkgrel {
+            config.pkgrel = Some(pkgrel);
+        }
+        if let Some(timestamp) = &self.snapshot_timestamp {
+            config.snapshot_timestamp = Some(timestamp.clone());
+        }
+        if let Some(Command::Generate { url: Some(url), .. }) = &self.command {
+            config.original_url = url.clone();
+        }
+    }
+}
+
+fn parse_snapshot_timestamp(timestamp: &str) -> Result<String, String> {
+    archive::parse_wayback_timestamp(timestamp)
+        .map(|_| timestamp.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Map `-v`/`-q` occurrence counts to a log level, starting from INFO
+pub fn level_for(verbose: u8, quiet: u8) -> Level {
+    match (verbose, quiet) {
+        (0, 0) => Level::INFO,
+        (1, _) => Level::DEBUG,
+        (2.., _) => Level::TRACE,
+        (0, 1) => Level::WARN,
+        (0, 2..) => Level::ERROR,
+    }
+}
@@ -1,38 +1,611 @@
-use crate::error::{AppError, Result};
+use crate::{
+    aur::InstallTarget,
+    checksum::ChecksumKind,
+    error::{AppError, Result},
+    host_key::HostKeyPolicy,
+    rate_limit::RateLimiter,
+    redact,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use shellexpand::tilde;
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::warn;
+
+/// Name of the work directory managed by this tool inside the temp dir
+pub const MANAGED_WORK_DIR_NAME: &str = "aur-aacs-keydb-daily";
+
+/// Work directory managed by this tool, under `TMPDIR` (or the platform temp dir);
+/// anything below it may be deleted and recreated
+pub fn managed_work_dir() -> PathBuf {
+    std::env::temp_dir().join(MANAGED_WORK_DIR_NAME)
+}
+
+/// `.gitignore` added to a package repository that has none and gets none from
+/// `EXTRA_FILES`, keeping the downloaded source and makepkg's build directories out of it
+pub const DEFAULT_GITIGNORE: &str = "*.zip\n*.pkg.tar*\nsrc/\npkg/\n";
+
+/// What to do when the archived file differs from the live original
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OriginMismatchPolicy {
+    /// Request a fresh snapshot and use it instead
+    Rearchive,
+    /// Log a warning and keep the archived copy
+    Warn,
+}
+
+impl OriginMismatchPolicy {
+    /// Values accepted for `ORIGIN_MISMATCH`
+    pub const NAMES: &[&str] = &["warn", "rearchive"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "warn" => Some(OriginMismatchPolicy::Warn),
+            "rearchive" => Some(OriginMismatchPolicy::Rearchive),
+            _ => None,
+        }
+    }
+}
+
+/// Whether to request a new Wayback snapshot, reuse an existing one, or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveMode {
+    /// Request a new snapshot and fall back to the latest existing one if that fails
+    CreateThenFallback,
+    /// Only use the latest existing snapshot, failing when there is none within the
+    /// maximum snapshot age
+    ExistingOnly,
+    /// Only use a newly requested snapshot
+    CreateOnly,
+}
+
+impl ArchiveMode {
+    /// Values accepted for `ARCHIVE_MODE`
+    pub const NAMES: &[&str] = &["create-then-fallback", "existing-only", "create-only"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "create-then-fallback" => Some(ArchiveMode::CreateThenFallback),
+            "existing-only" => Some(ArchiveMode::ExistingOnly),
+            "create-only" => Some(ArchiveMode::CreateOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Format of the upstream file, deciding how downloads are validated and the extension
+/// of the default source filename
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceFormat {
+    #[default]
+    Zip,
+    /// An uncompressed tarball
+    Tar,
+    /// A gzip-compressed tarball
+    TarGz,
+    /// Any file; only the HTML error page check applies
+    Raw,
+}
+
+impl SourceFormat {
+    /// Values accepted for `SOURCE_FORMAT`
+    pub const NAMES: &[&str] = &["zip", "tar", "tar.gz", "tgz", "raw"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "zip" => Some(SourceFormat::Zip),
+            "tar" => Some(SourceFormat::Tar),
+            "tar.gz" | "tgz" => Some(SourceFormat::TarGz),
+            "raw" => Some(SourceFormat::Raw),
+            _ => None,
+        }
+    }
+
+    /// Extension of source filenames in this format, including the dot (empty for raw files)
+    pub fn extension(self) -> &'static str {
+        match self {
+            SourceFormat::Zip => ".zip",
+            SourceFormat::Tar => ".tar",
+            SourceFormat::TarGz => ".tar.gz",
+            SourceFormat::Raw => "",
+        }
+    }
+}
+
+impl std::fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SourceFormat::Zip => "ZIP archive",
+            SourceFormat::Tar => "tar archive",
+            SourceFormat::TarGz => "gzip-compressed tar archive",
+            SourceFormat::Raw => "file",
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub package_name: String,
     pub original_url: String,
+    pub mirror_urls: Vec<String>,
+    pub origin_headers: HashMap<String, String>,
+    pub source_format: SourceFormat,
+    /// Digests computed while downloading; SHA256 is always among them
+    pub checksums: Vec<ChecksumKind>,
+    pub source_filename_template: String,
+    pub pkgbuild_template_path: Option<String>,
+    pub install_source: String,
+    pub install_dest: String,
+    pub install_mode: String,
+    pub save_source_to: Option<PathBuf>,
+    pub output_summary_path: Option<PathBuf>,
+    pub download_cache: bool,
+    pub download_retries: u32,
+    pub extra_files: Vec<(PathBuf, String)>,
     pub work_dir: String,
+    pub lock_work_dir: bool,
+    pub cleanup_work_dir: bool,
+    pub cleanup_on_failure: bool,
     pub ssh_key_path: String,
+    pub git_https_token: Option<String>,
+    pub git_author_name: Option<String>,
+    pub git_author_email: Option<String>,
+    pub aur_ssh_user: String,
+    pub known_hosts_path: Option<PathBuf>,
+    pub aur_host_key_fingerprint: Option<String>,
+    pub strict_host_key_checking: bool,
+    pub aur_host: String,
+    pub aur_port: u16,
+    pub push_remote: String,
+    pub push_remote_url: Option<String>,
+    pub commit_message_template: String,
+    pub wayback_host: String,
+    pub providers: Vec<String>,
+    pub archive_mode: ArchiveMode,
+    pub max_snapshot_age: Option<Duration>,
+    /// Wayback timestamp to download instead of archiving, from `--snapshot-timestamp`
+    pub snapshot_timestamp: Option<String>,
+    pub min_archive_age: Option<Duration>,
+    pub max_archive_age: Option<Duration>,
+    /// Skip runs starting less than this after the last successful one
+    pub min_interval: Option<Duration>,
+    pub max_attempts: u32,
+    pub run_timeout: Duration,
+    pub archive_budget: Option<Duration>,
+    pub rate_limit_budget: Duration,
+    /// Shared by every client built from this config and its clones
+    pub wayback_rate_limiter: Option<Arc<RateLimiter>>,
+    pub snapshot_poll_interval: Duration,
+    pub snapshot_max_wait: Duration,
+    pub ca_bundle_path: Option<PathBuf>,
+    pub danger_accept_invalid_certs: bool,
+    pub force: bool,
+    /// pkgrel written verbatim instead of the reset/bump logic
+    pub pkgrel: Option<u32>,
+    pub dry_run: bool,
+    /// Archive for real but skip every git operation, writing the package to a scratch dir
+    pub no_git: bool,
+    pub run_namcap: bool,
+    pub generate_srcinfo: bool,
+    pub write_tracking_file: bool,
+    pub check_reachable: bool,
+    pub pushgateway_url: Option<String>,
+    pub manifest_url: Option<String>,
+    pub max_concurrency: usize,
+    pub history_path: Option<String>,
+    pub history_max_entries: usize,
+    pub allow_epoch_bump: bool,
+    pub skip_fallback_updates: bool,
+    pub verify_against_origin: bool,
+    pub signature_url: Option<String>,
+    pub gpg_pubkey_path: Option<PathBuf>,
+    pub origin_mismatch_policy: OriginMismatchPolicy,
 }
 
 impl Config {
+    /// Build the configuration from environment variables like [`Self::new`], failing when
+    /// a choice such as `ARCHIVE_MODE` is set to an unknown value
+    pub fn from_env() -> Result<Self> {
+        check_env_choice("SOURCE_FORMAT", SourceFormat::parse, SourceFormat::NAMES)?;
+        check_env_choice("ARCHIVE_MODE", ArchiveMode::parse, ArchiveMode::NAMES)?;
+        check_env_choice(
+            "ORIGIN_MISMATCH",
+            OriginMismatchPolicy::parse,
+            OriginMismatchPolicy::NAMES,
+        )?;
+        Ok(Self::new())
+    }
+
+    /// Build the configuration from environment variables, using defaults for unset values
+    /// (and for unknown choices, which [`Self::from_env`] rejects)
     pub fn new() -> Self {
+        let source_format = env_choice("SOURCE_FORMAT", SourceFormat::parse).unwrap_or_default();
         let ssh_key_path =
             tilde(&std::env::var("SSH_KEY_PATH").unwrap_or_else(|_| "~/.ssh/id_ed25519".into()))
                 .into_owned();
+        let origin_headers: HashMap<String, String> = std::env::var("ORIGIN_HEADERS")
+            .ok()
+            .and_then(|json| {
+                serde_json::from_str(&json)
+                    .inspect_err(|e| warn!("Ignoring ORIGIN_HEADERS, not a JSON object: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let git_https_token = std::env::var("GIT_HTTPS_TOKEN").ok();
 
         Self {
             package_name: "aacs-keydb-daily".to_string(),
             original_url: "http://fvonline-db.bplaced.net/export/keydb_eng.zip".to_string(),
-            work_dir: "/tmp/aur-aacs-keydb-daily".to_string(),
+            mirror_urls: std::env::var("MIRROR_URLS")
+                .map(|urls| {
+                    urls.split(',')
+                        .map(str::trim)
+                        .filter(|url| !url.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            origin_headers,
+            source_format,
+            checksums: std::env::var("CHECKSUMS")
+                .map(|kinds| parse_checksums(&kinds))
+                .unwrap_or_else(|_| vec![ChecksumKind::Sha256]),
+            source_filename_template: std::env::var("SOURCE_FILENAME_TEMPLATE")
+                .unwrap_or_else(|_| format!("keydb_eng-${{pkgver}}{}", source_format.extension())),
+            install_source: std::env::var("INSTALL_SOURCE")
+                .unwrap_or_else(|_| InstallTarget::default().source),
+            install_dest: std::env::var("INSTALL_DEST")
+                .unwrap_or_else(|_| InstallTarget::default().dest),
+            install_mode: std::env::var("INSTALL_MODE")
+                .unwrap_or_else(|_| InstallTarget::default().mode),
+            pkgbuild_template_path: std::env::var("PKGBUILD_TEMPLATE_PATH")
+                .ok()
+                .map(|path| tilde(&path).into_owned()),
+            save_source_to: std::env::var("SAVE_SOURCE_TO")
+                .ok()
+                .map(|dir| PathBuf::from(tilde(&dir).into_owned())),
+            output_summary_path: std::env::var("OUTPUT_SUMMARY_PATH")
+                .ok()
+                .map(|path| PathBuf::from(tilde(&path).into_owned())),
+            download_cache: env_flag_or("DOWNLOAD_CACHE", false),
+            download_retries: std::env::var("DOWNLOAD_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            extra_files: extra_files(std::env::var("EXTRA_FILES").ok().as_deref()),
+            work_dir: std::env::var("WORK_DIR")
+                .map(|dir| tilde(&dir).into_owned())
+                .unwrap_or_else(|_| managed_work_dir().display().to_string()),
+            lock_work_dir: env_flag_or("LOCK_WORK_DIR", true),
+            cleanup_work_dir: env_flag("CLEANUP_WORK_DIR"),
+            cleanup_on_failure: env_flag("CLEANUP_ON_FAILURE"),
             ssh_key_path,
+            git_https_token,
+            git_author_name: std::env::var("GIT_AUTHOR_NAME").ok(),
+            git_author_email: std::env::var("GIT_AUTHOR_EMAIL").ok(),
+            aur_ssh_user: std::env::var("AUR_SSH_USER").unwrap_or_else(|_| "aur".to_string()),
+            known_hosts_path: match std::env::var("KNOWN_HOSTS_PATH") {
+                Ok(path) => Some(PathBuf::from(tilde(&path).into_owned())),
+                Err(_) => HostKeyPolicy::default().known_hosts,
+            },
+            aur_host_key_fingerprint: std::env::var("AUR_HOST_KEY_FINGERPRINT").ok(),
+            strict_host_key_checking: env_flag_or("STRICT_HOST_KEY_CHECKING", true),
+            aur_host: std::env::var("AUR_HOST")
+                .unwrap_or_else(|_| crate::git::DEFAULT_AUR_HOST.to_string()),
+            aur_port: std::env::var("AUR_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(22),
+            push_remote: std::env::var("PUSH_REMOTE").unwrap_or_else(|_| "origin".to_string()),
+            push_remote_url: std::env::var("PUSH_REMOTE_URL").ok(),
+            commit_message_template: std::env::var("COMMIT_MESSAGE_TEMPLATE")
+                .unwrap_or_else(|_| "Update to {version}".to_string()),
+            wayback_host: std::env::var("WAYBACK_HOST")
+                .unwrap_or_else(|_| crate::archive::DEFAULT_WAYBACK_HOST.to_string()),
+            providers: std::env::var("ARCHIVE_PROVIDERS")
+                .map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|host| !host.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            archive_mode: env_choice("ARCHIVE_MODE", ArchiveMode::parse)
+                .unwrap_or(ArchiveMode::CreateThenFallback),
+            max_snapshot_age: env_secs("MAX_SNAPSHOT_AGE_SECS").filter(|age| !age.is_zero()),
+            snapshot_timestamp: None,
+            min_archive_age: env_secs("MIN_ARCHIVE_AGE_SECS"),
+            max_archive_age: env_secs("MAX_ARCHIVE_AGE_SECS"),
+            min_interval: env_secs("MIN_INTERVAL_SECS").filter(|interval| !interval.is_zero()),
+            max_attempts: std::env::var("MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            run_timeout: env_secs("RUN_TIMEOUT_SECS").unwrap_or(Duration::from_secs(600)),
+            archive_budget: env_secs("ARCHIVE_BUDGET_SECS"),
+            rate_limit_budget: Duration::from_secs(
+                std::env::var("RATE_LIMIT_BUDGET_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(120),
+            ),
+            wayback_rate_limiter: std::env::var("WAYBACK_REQUESTS_PER_MINUTE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&rpm: &u32| rpm > 0)
+                .map(|rpm| Arc::new(RateLimiter::per_minute(rpm))),
+            snapshot_poll_interval: env_secs("SNAPSHOT_POLL_INTERVAL_SECS")
+                .unwrap_or(crate::archive::DEFAULT_SNAPSHOT_POLL_INTERVAL),
+            snapshot_max_wait: env_secs("SNAPSHOT_MAX_WAIT_SECS")
+                .unwrap_or(crate::archive::DEFAULT_SNAPSHOT_MAX_WAIT),
+            ca_bundle_path: std::env::var("CA_BUNDLE_PATH")
+                .ok()
+                .map(|path| PathBuf::from(tilde(&path).into_owned())),
+            danger_accept_invalid_certs: env_flag("DANGER_ACCEPT_INVALID_CERTS"),
+            force: false,
+            pkgrel: None,
+            dry_run: env_flag("DRY_RUN"),
+            no_git: false,
+            run_namcap: env_flag("RUN_NAMCAP"),
+            generate_srcinfo: env_flag_or("GENERATE_SRCINFO", true),
+            write_tracking_file: env_flag("WRITE_TRACKING_FILE"),
+            check_reachable: env_flag("CHECK_REACHABLE"),
+            pushgateway_url: std::env::var("PUSHGATEWAY_URL").ok(),
+            manifest_url: std::env::var("MANIFEST_URL").ok(),
+            max_concurrency: std::env::var("MAX_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
+            history_path: std::env::var("HISTORY_PATH")
+                .ok()
+                .map(|path| tilde(&path).into_owned()),
+            history_max_entries: std::env::var("HISTORY_MAX_ENTRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            allow_epoch_bump: env_flag("ALLOW_EPOCH_BUMP"),
+            skip_fallback_updates: env_flag("SKIP_FALLBACK_UPDATES"),
+            verify_against_origin: env_flag("VERIFY_AGAINST_ORIGIN"),
+            signature_url: std::env::var("SIGNATURE_URL").ok(),
+            gpg_pubkey_path: std::env::var("GPG_PUBKEY_PATH")
+                .ok()
+                .map(|path| PathBuf::from(tilde(&path).into_owned())),
+            origin_mismatch_policy: env_choice("ORIGIN_MISMATCH", OriginMismatchPolicy::parse)
+                .unwrap_or(OriginMismatchPolicy::Warn),
         }
     }
 
+    /// `original_url` followed by the mirrors, in the order they are tried
+    pub fn original_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.original_url.as_str())
+            .chain(self.mirror_urls.iter().map(String::as_str))
+    }
+
+    /// Keep the HTTPS token and origin header values out of logs and error messages from
+    /// now on
+    pub fn register_secrets(&self) {
+        for secret in self.origin_headers.values().chain(&self.git_https_token) {
+            redact::register_secret(secret);
+        }
+    }
+
+    /// `origin_headers` as sent to the origin host, with values marked sensitive so they
+    /// are redacted wherever the map is logged
+    pub fn origin_header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.origin_headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|e| anyhow::anyhow!("Invalid origin header name {name}: {e}"))?;
+            let mut value = HeaderValue::try_from(value.as_str())
+                .map_err(|e| anyhow::anyhow!("Invalid value for origin header {name}: {e}"))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// HTTP client trusting `ca_bundle_path` in addition to the system roots, failing when
+    /// the bundle can't be read or holds no valid PEM certificate
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(path) = &self.ca_bundle_path {
+            let pem = std::fs::read(path)
+                .map_err(|e| anyhow::anyhow!("Could not read CA bundle {}: {e}", path.display()))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .ok()
+                .filter(|certificates| !certificates.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "CA bundle {} holds no valid PEM certificate",
+                        path.display()
+                    )
+                })?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if self.danger_accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Directory the package's AUR repository is cloned into: `{work_dir}/{package_name}`,
+    /// or the scratch dir `{work_dir}/{package_name}.no-git` with `no_git`
+    pub fn package_work_dir(&self) -> PathBuf {
+        if self.no_git {
+            Path::new(&self.work_dir).join(format!("{}.no-git", self.package_name))
+        } else {
+            Path::new(&self.work_dir).join(&self.package_name)
+        }
+    }
+
+    /// File installed by `package()` of new PKGBUILDs
+    pub fn install_target(&self) -> InstallTarget {
+        InstallTarget {
+            source: self.install_source.clone(),
+            dest: self.install_dest.clone(),
+            mode: self.install_mode.clone(),
+        }
+    }
+
+    /// How SSH host keys of the AUR (and push) remote are verified
+    pub fn host_key_policy(&self) -> HostKeyPolicy {
+        HostKeyPolicy {
+            known_hosts: self.known_hosts_path.clone(),
+            fingerprint: self.aur_host_key_fingerprint.clone(),
+            strict: self.strict_host_key_checking,
+        }
+    }
+
+    /// Advisory lock file guarding the package work dir
+    pub fn lock_path(&self) -> PathBuf {
+        Path::new(&self.work_dir).join(format!("{}.lock", self.package_name))
+    }
+
+    /// [`crate::run_state::RunState`] of the package
+    pub fn run_state_path(&self) -> PathBuf {
+        Path::new(&self.work_dir).join(format!("{}.state.json", self.package_name))
+    }
+
+    /// Last successfully fetched package manifest, used when `manifest_url` is unreachable
+    pub fn manifest_cache_path(&self) -> PathBuf {
+        Path::new(&self.work_dir).join("manifest.cache")
+    }
+
+    /// Copies of earlier downloads kept by the opt-in `DOWNLOAD_CACHE`
+    pub fn download_cache_dir(&self) -> PathBuf {
+        Path::new(&self.work_dir).join("download-cache")
+    }
+
+    /// Whether `work_dir` lives under the managed path and may be safely deleted
+    pub fn work_dir_is_managed(&self) -> bool {
+        Path::new(&self.work_dir).starts_with(managed_work_dir())
+    }
+
+    /// Check the configuration without touching the network or writing anything; see
+    /// [`Self::check_work_dir_writable`] for the work dir
     pub fn validate(&self) -> Result<()> {
-        if !Path::new(&self.ssh_key_path).exists() {
-            return Err(AppError::SshAuthFailed);
+        if !self.no_git && self.git_https_token.is_none() {
+            check_ssh_key(Path::new(&self.ssh_key_path))?;
         }
 
-        if !self.original_url.starts_with("http://") && !self.original_url.starts_with("https://") {
+        if self
+            .original_urls()
+            .any(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
             return Err(AppError::Archive(anyhow::anyhow!("Invalid URL format")));
         }
 
+        self.origin_header_map()?;
+        self.http_client()?;
+        if self.danger_accept_invalid_certs {
+            warn!("DANGER_ACCEPT_INVALID_CERTS is set, TLS certificates are not verified");
+        }
+
+        if !self.source_filename_template.contains("$pkgver")
+            && !self.source_filename_template.contains("${pkgver}")
+        {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "Source filename template must contain a ${{pkgver}} placeholder"
+            )));
+        }
+        if !self
+            .source_filename_template
+            .ends_with(self.source_format.extension())
+        {
+            warn!(
+                "Source filename template {} does not end with {}, the extension of the {:?} source format",
+                self.source_filename_template,
+                self.source_format.extension(),
+                self.source_format
+            );
+        }
+
+        if let (Some(min), Some(max)) = (self.min_archive_age, self.max_archive_age)
+            && min > max
+        {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "MIN_ARCHIVE_AGE_SECS must not be greater than MAX_ARCHIVE_AGE_SECS"
+            )));
+        }
+
+        if let Some(timestamp) = &self.snapshot_timestamp {
+            crate::archive::parse_wayback_timestamp(timestamp)?;
+        }
+
+        if self.commit_message_template.trim().is_empty() {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "Commit message template cannot be empty"
+            )));
+        }
+
+        if let Some(path) = &self.pkgbuild_template_path
+            && !Path::new(path).is_file()
+        {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "PKGBUILD template {path} does not exist"
+            )));
+        }
+
+        self.install_target()
+            .validate()
+            .map_err(|reason| AppError::Archive(anyhow::anyhow!(reason)))?;
+
+        if let Some((dest, _)) = self.extra_files.iter().find(|(dest, _)| {
+            dest.is_absolute()
+                || dest
+                    .components()
+                    .any(|c| matches!(c, std::path::Component::ParentDir))
+        }) {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "Extra file {} must stay inside the package repository",
+                dest.display()
+            )));
+        }
+
+        match (&self.signature_url, &self.gpg_pubkey_path) {
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(AppError::Archive(anyhow::anyhow!(
+                    "SIGNATURE_URL and GPG_PUBKEY_PATH must be set together"
+                )));
+            }
+            (Some(_), Some(path)) if !path.is_file() => {
+                return Err(AppError::Archive(anyhow::anyhow!(
+                    "GPG public key {} does not exist",
+                    path.display()
+                )));
+            }
+            _ => {}
+        }
+
+        if self.pkgrel == Some(0) {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "pkgrel must be a positive integer"
+            )));
+        }
+
+        if !self.no_git && self.push_remote != "origin" && self.push_remote_url.is_none() {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "PUSH_REMOTE_URL must be set when PUSH_REMOTE is not origin"
+            )));
+        }
+
+        if self.aur_host.trim().is_empty() {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "AUR host cannot be empty"
+            )));
+        }
+
         if self.package_name.is_empty() {
             return Err(AppError::Archive(anyhow::anyhow!(
                 "Package name cannot be empty"
@@ -41,4 +614,193 @@ impl Config {
 
         Ok(())
     }
+
+    /// Fail when `work_dir` cannot be created or written, e.g. because the temp dir is
+    /// read-only or full. Creates the work dir and removes a probe file written to it.
+    pub fn check_work_dir_writable(&self) -> Result<()> {
+        let probe = Path::new(&self.work_dir).join(format!(".write-test-{}", std::process::id()));
+        std::fs::create_dir_all(&self.work_dir)
+            .and_then(|()| std::fs::write(&probe, b"probe"))
+            .and_then(|()| std::fs::remove_file(&probe))
+            .map_err(|e| {
+                AppError::Archive(anyhow::anyhow!(
+                    "Work dir {} is not writable: {e}; set WORK_DIR to a writable directory",
+                    self.work_dir
+                ))
+            })
+    }
+
+    /// Confirm that `original_url` resolves and does not answer with a 4xx status
+    pub async fn validate_reachable(&self) -> Result<()> {
+        let response = self
+            .http_client()?
+            .head(&self.original_url)
+            .headers(self.origin_header_map()?)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            return Err(AppError::Unreachable {
+                url: self.original_url.clone(),
+                status: status.as_u16(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Environment variable `name` parsed with `parse`; `None` when it is unset or holds an
+/// unknown value, which [`Config::from_env`] rejects
+fn env_choice<T>(name: &str, parse: fn(&str) -> Option<T>) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| parse(value.trim()))
+}
+
+/// Fail when environment variable `name` is set to a value `parse` doesn't know
+fn check_env_choice<T>(name: &str, parse: fn(&str) -> Option<T>, accepted: &[&str]) -> Result<()> {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() && parse(value.trim()).is_none() => {
+            Err(AppError::Archive(anyhow::anyhow!(
+                "Unknown {name} '{value}', expected one of: {}",
+                accepted.join(", ")
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Files to write into the package repository, keyed by their path relative to it.
+///
+/// `spec` is a comma-separated list of `source` or `dest=source` entries; `dest`
+/// defaults to the source's file name. Unreadable sources are skipped with a warning.
+fn extra_files(spec: Option<&str>) -> Vec<(PathBuf, String)> {
+    let mut files = Vec::new();
+
+    for entry in spec.unwrap_or_default().split(',').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+
+        let (dest, source) = match entry.split_once('=') {
+            Some((dest, source)) => (PathBuf::from(dest), tilde(source).into_owned()),
+            None => {
+                let source = tilde(entry).into_owned();
+                match Path::new(&source).file_name() {
+                    Some(name) => (PathBuf::from(name), source),
+                    None => {
+                        warn!("Extra file {entry} has no file name, skipping");
+                        continue;
+                    }
+                }
+            }
+        };
+
+        match std::fs::read_to_string(&source) {
+            Ok(content) => {
+                files.retain(|(existing, _)| *existing != dest);
+                files.push((dest, content));
+            }
+            Err(e) => warn!("Could not read extra file {source}: {e}, skipping"),
+        }
+    }
+
+    files
+}
+
+/// Check that `path` is a readable file holding a PEM or OpenSSH private key, warning
+/// when other users can read it
+pub fn check_ssh_key(path: &Path) -> Result<()> {
+    let invalid = |reason: String| AppError::InvalidSshKey {
+        path: path.display().to_string(),
+        reason,
+    };
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(invalid(match std::fs::read_link(path) {
+                Ok(target) => format!(
+                    "is a broken symlink to {}, point SSH_KEY_PATH at an existing key",
+                    target.display()
+                ),
+                Err(_) => "does not exist, set SSH_KEY_PATH or GIT_HTTPS_TOKEN".to_string(),
+            }));
+        }
+        Err(e) => return Err(invalid(format!("cannot be inspected: {e}"))),
+    };
+    if metadata.is_dir() {
+        return Err(invalid(
+            "is a directory, SSH_KEY_PATH must name the private key file itself".to_string(),
+        ));
+    }
+
+    let content = std::fs::read(path).map_err(|e| invalid(format!("is not readable: {e}")))?;
+    let content = String::from_utf8_lossy(&content);
+    let first_line = content.lines().next().unwrap_or_default().trim();
+    if !(first_line.starts_with("-----BEGIN ") && first_line.ends_with("PRIVATE KEY-----")) {
+        let hint = if first_line.starts_with("ssh-") || first_line.starts_with("ecdsa-") {
+            ", it looks like a public key; use the file without .pub"
+        } else {
+            ""
+        };
+        return Err(invalid(format!(
+            "is not a private key (expected a -----BEGIN ... PRIVATE KEY----- header){hint}"
+        )));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o077 != 0 {
+            warn!(
+                "SSH key {} is accessible by other users, OpenSSH refuses such keys; run chmod 600 on it",
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Comma-separated checksum kinds, always including SHA256; unknown kinds are skipped
+/// with a warning
+fn parse_checksums(kinds: &str) -> Vec<ChecksumKind> {
+    let mut checksums = vec![ChecksumKind::Sha256];
+    for name in kinds.split(',').filter(|name| !name.trim().is_empty()) {
+        match ChecksumKind::parse(name) {
+            Some(kind) if !checksums.contains(&kind) => checksums.push(kind),
+            Some(_) => {}
+            None => warn!(
+                "Ignoring unknown checksum kind '{}' in CHECKSUMS",
+                name.trim()
+            ),
+        }
+    }
+    checksums
+}
+
+fn env_flag(name: &str) -> bool {
+    env_flag_or(name, false)
+}
+
+fn env_flag_or(name: &str, default: bool) -> bool {
+    std::env::var(name)
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(default)
+}
+
+fn env_secs(name: &str) -> Option<Duration> {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
 }
@@ -0,0 +1,46 @@
+use similar::TextDiff;
+use std::io::IsTerminal;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Render a unified diff between two versions of a file
+pub fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{name}"), &format!("b/{name}"))
+        .to_string()
+}
+
+/// Add ANSI colors to a unified diff
+pub fn colorize(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            if line.starts_with("+++") || line.starts_with("---") {
+                line.to_string()
+            } else if line.starts_with('+') {
+                format!("{GREEN}{line}{RESET}")
+            } else if line.starts_with('-') {
+                format!("{RED}{line}{RESET}")
+            } else if line.starts_with("@@") {
+                format!("{CYAN}{line}{RESET}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render a unified diff, colorized when stdout is a terminal
+pub fn render(name: &str, old: &str, new: &str) -> String {
+    let diff = unified_diff(name, old, new);
+    if std::io::stdout().is_terminal() {
+        colorize(&diff)
+    } else {
+        diff
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    config::{self, Config},
+    git::GitHelper,
+};
+use std::{env, fmt, path::Path};
+
+/// Outcome of a single prerequisite check
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// Whether a failure should make the whole report fail
+    pub critical: bool,
+    /// Detail on success, reason on failure
+    pub outcome: Result<String, String>,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, critical: bool, outcome: Result<String, String>) -> Self {
+        Self {
+            name,
+            critical,
+            outcome,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.outcome, self.critical) {
+            (Ok(detail), _) => write!(f, "[PASS] {}: {detail}", self.name),
+            (Err(reason), true) => write!(f, "[FAIL] {}: {reason}", self.name),
+            (Err(reason), false) => write!(f, "[WARN] {}: {reason}", self.name),
+        }
+    }
+}
+
+/// Run every prerequisite check for `config`; each one is reported on its own
+pub async fn run(config: &Config) -> Vec<CheckResult> {
+    vec![
+        check_ssh_key(config),
+        check_work_dir(config),
+        check_aur_remote(config),
+        check_original_url(config).await,
+        check_tool("makepkg"),
+        check_tool("namcap"),
+    ]
+}
+
+/// The configured SSH key is a readable private key (not needed with an HTTPS token)
+pub fn check_ssh_key(config: &Config) -> CheckResult {
+    let name = "SSH key";
+    if config.git_https_token.is_some() {
+        return CheckResult::new(name, false, Ok("not used, GIT_HTTPS_TOKEN is set".into()));
+    }
+
+    let outcome = config::check_ssh_key(Path::new(&config.ssh_key_path))
+        .map(|()| format!("{} is a readable private key", config.ssh_key_path))
+        .map_err(|e| e.to_string());
+    CheckResult::new(name, true, outcome)
+}
+
+/// The work dir can be created and written to
+pub fn check_work_dir(config: &Config) -> CheckResult {
+    let outcome = config
+        .check_work_dir_writable()
+        .map(|()| format!("{} is writable", config.work_dir))
+        .map_err(|e| e.to_string());
+    CheckResult::new("Work dir", true, outcome)
+}
+
+/// The AUR remote accepts a connection with the configured credentials
+pub fn check_aur_remote(config: &Config) -> CheckResult {
+    let git_helper = GitHelper::from_config(config);
+    let url = git_helper.repo_url(&config.package_name);
+
+    let outcome = git_helper
+        .check_connection(&config.package_name)
+        .map(|()| format!("connected to {url}"))
+        .map_err(|e| format!("{url}: {e}"));
+    CheckResult::new("AUR remote", true, outcome)
+}
+
+/// `original_url` resolves and does not answer with a 4xx status
+pub async fn check_original_url(config: &Config) -> CheckResult {
+    let outcome = config
+        .validate_reachable()
+        .await
+        .map(|()| format!("{} responds", config.original_url))
+        .map_err(|e| e.to_string());
+    CheckResult::new("Original URL", true, outcome)
+}
+
+/// An optional external tool is on PATH
+pub fn check_tool(tool: &'static str) -> CheckResult {
+    let found = env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(tool))
+            .find(|path| is_executable(path))
+    });
+
+    let outcome = match found {
+        Some(path) => Ok(format!("found at {}", path.display())),
+        None => Err("not found on PATH".to_string()),
+    };
+    CheckResult::new(tool, false, outcome)
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
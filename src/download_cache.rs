@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Validators and hash of an earlier download, for conditional requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedDownload {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub sha256: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
+/// Opt-in on-disk cache of download bodies keyed by URL.
+///
+/// Each kept body is stored as `{key}.body` and indexed by its Wayback digest in
+/// `{digest}.digest.json`, so a snapshot whose CDX digest matches can be served without
+/// downloading it. The validators themselves live in the [`crate::run_state::RunState`].
+/// Failures to write the cache are logged and otherwise ignored.
+pub struct DownloadCache {
+    dir: PathBuf,
+}
+
+impl DownloadCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The entry of an earlier download whose body has the Wayback (CDX) `digest`
+    pub fn lookup_wayback_digest(&self, digest: &str) -> Option<CachedDownload> {
+        let digest = normalize_wayback_digest(digest)?;
+        let content = fs::read_to_string(self.digest_path(&digest)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// The cached body of `entry`, if it was stored and still matches its hash
+    pub fn body(&self, entry: &CachedDownload) -> Option<Vec<u8>> {
+        let bytes = fs::read(self.body_path(&entry.url)).ok()?;
+        (format!("{:x}", Sha256::digest(&bytes)) == entry.sha256).then_some(bytes)
+    }
+
+    /// Remember the `body` of `entry` for the next request of its URL
+    pub fn store(&self, entry: &CachedDownload, body: &[u8]) {
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|()| write_atomic(&self.body_path(&entry.url), body))
+            .and_then(|()| {
+                let json = serde_json::to_vec_pretty(entry).map_err(std::io::Error::other)?;
+                write_atomic(&self.digest_path(&wayback_digest(body)), &json)
+            });
+        if let Err(e) = result {
+            warn!("Could not cache download of {}: {e}", entry.url);
+        }
+    }
+
+    fn body_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.body", key(url)))
+    }
+
+    fn digest_path(&self, digest: &str) -> PathBuf {
+        self.dir.join(format!("{digest}.digest.json"))
+    }
+}
+
+/// Digest the Wayback CDX API reports for a capture: the base32 SHA1 of its body
+pub fn wayback_digest(body: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    // 20 bytes are exactly 32 base32 characters, so there is no padding
+    let hash = <Sha1 as sha1::Digest>::digest(body);
+    let mut digest = String::with_capacity(32);
+    for chunk in hash.chunks(5) {
+        let bits = chunk
+            .iter()
+            .fold(0u64, |bits, &byte| bits << 8 | u64::from(byte));
+        for shift in (0..8).rev() {
+            digest.push(ALPHABET[(bits >> (shift * 5)) as usize & 31] as char);
+        }
+    }
+    digest
+}
+
+/// Strip the `sha1:` prefix some CDX servers add; `None` for anything that isn't a
+/// base32 SHA1, which could otherwise name a path outside the cache
+fn normalize_wayback_digest(digest: &str) -> Option<String> {
+    let digest = digest.trim();
+    let digest = digest
+        .strip_prefix("sha1:")
+        .unwrap_or(digest)
+        .to_ascii_uppercase();
+    (digest.len() == 32
+        && digest
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || (b'2'..=b'7').contains(&b)))
+    .then_some(digest)
+}
+
+fn key(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let partial = path.with_extension("part");
+    fs::write(&partial, content)?;
+    fs::rename(&partial, path)
+}
@@ -1,24 +1,258 @@
+use reqwest::StatusCode;
+use std::{io::ErrorKind, time::Duration};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum AppError {
-    #[error("Archive error: {0}")]
+    #[error("Archive error: {}", crate::redact::redact(&.0.to_string()))]
     Archive(#[from] anyhow::Error),
 
-    #[error("Git error: {0}")]
+    #[error("Archive error: {}", crate::redact::redact(&.0.to_string()))]
+    ArchiveFailed(ArchiveError),
+
+    #[error("Git error: {}", crate::redact::redact(&.0.to_string()))]
     Git(#[from] git2::Error),
 
-    #[error("IO error: {0}")]
+    #[error("IO error: {}", crate::redact::redact(&.0.to_string()))]
     Io(#[from] std::io::Error),
 
-    #[error("HTTP error: {0}")]
+    #[error("HTTP error: {}", crate::redact::redact(&.0.to_string()))]
     Http(#[from] reqwest::Error),
 
-    #[error("Package version not found in PKGBUILD")]
-    VersionNotFound,
+    #[error(
+        "Downloaded file is not a valid {expected} (content-type: {content_type}, first bytes: {head})"
+    )]
+    InvalidArchive {
+        expected: &'static str,
+        content_type: String,
+        head: String,
+    },
+
+    #[error("Downloaded file is an HTML page, not the expected {expected} (starts with: {head})")]
+    HtmlPage {
+        expected: crate::config::SourceFormat,
+        head: String,
+    },
+
+    #[error("Download truncated: expected {expected} bytes, received {received}")]
+    TruncatedDownload { expected: u64, received: u64 },
+
+    #[error("Unexpected content type for archive download: {content_type}")]
+    UnexpectedContentType { content_type: String },
+
+    #[error("namcap reported errors in PKGBUILD\nstdout:\n{stdout}\nstderr:\n{stderr}")]
+    Namcap { stdout: String, stderr: String },
+
+    #[error(
+        ".SRCINFO is out of sync with PKGBUILD: {field} is '{srcinfo}' but PKGBUILD has '{pkgbuild}'"
+    )]
+    SrcinfoMismatch {
+        field: &'static str,
+        pkgbuild: String,
+        srcinfo: String,
+    },
+
+    #[error("Upstream signature verification failed: {reason}")]
+    SignatureInvalid { reason: String },
+
+    #[error("{} is not reachable (HTTP {status})", crate::redact::redact(url))]
+    Unreachable { url: String, status: u16 },
+
+    #[error("Another run holds the work dir lock {path}; remove it if no other run is active")]
+    Locked { path: String },
+
+    #[error(
+        "Manifest has {} problem(s):\n  - {}",
+        problems.len(),
+        problems.join("\n  - ")
+    )]
+    InvalidManifest { problems: Vec<String> },
+
+    #[error(
+        "Package {name} does not exist on the remote; it may have been renamed, merged or deleted on the AUR, or the package name is wrong"
+    )]
+    PackageNotFound { name: String },
 
     #[error("SSH authentication failed")]
     SshAuthFailed,
+
+    #[error("SSH key {path} {reason}")]
+    InvalidSshKey { path: String, reason: String },
+
+    #[error(
+        "Host key of {host} ({fingerprint}) does not match the expected key, refusing to connect"
+    )]
+    HostKeyMismatch { host: String, fingerprint: String },
+
+    #[error(
+        "Host key of {host} ({fingerprint}) is not in known_hosts; add it or set AUR_HOST_KEY_FINGERPRINT"
+    )]
+    UnknownHostKey { host: String, fingerprint: String },
+
+    #[error("Cancelled by signal")]
+    Cancelled,
+
+    #[error("Run timed out after {}s", .0.as_secs())]
+    Timeout(std::time::Duration),
+}
+
+impl AppError {
+    /// Convert an archive-layer error, keeping the typed `AppError`s and `ArchiveError`s
+    /// raised inside it
+    pub fn from_archive(e: anyhow::Error) -> Self {
+        match e.downcast::<AppError>() {
+            Ok(e) => e,
+            Err(e) => match e.downcast::<ArchiveError>() {
+                Ok(e) => e.into(),
+                Err(e) => AppError::Archive(e),
+            },
+        }
+    }
+
+    /// Whether the failure is transient (timeouts, connection resets, HTTP 429/5xx)
+    /// and the run may succeed if retried. Configuration, authentication and
+    /// parse errors are permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::Archive(e) => e.chain().any(is_transient),
+            AppError::ArchiveFailed(e) => e.is_retryable(),
+            AppError::Git(e) => is_transient_git(e),
+            AppError::Io(e) => is_transient_io(e),
+            AppError::Http(e) => is_transient_http(e),
+            AppError::TruncatedDownload { .. } => true,
+            AppError::InvalidArchive { .. }
+            | AppError::HtmlPage { .. }
+            | AppError::UnexpectedContentType { .. }
+            | AppError::Namcap { .. }
+            | AppError::SrcinfoMismatch { .. }
+            | AppError::SignatureInvalid { .. }
+            | AppError::Unreachable { .. }
+            | AppError::Locked { .. }
+            | AppError::SshAuthFailed
+            | AppError::InvalidSshKey { .. }
+            | AppError::PackageNotFound { .. }
+            | AppError::InvalidManifest { .. }
+            | AppError::HostKeyMismatch { .. }
+            | AppError::UnknownHostKey { .. }
+            | AppError::Cancelled
+            | AppError::Timeout(_) => false,
+        }
+    }
+}
+
+/// Whether `e` is a transient network, IO or git failure worth retrying
+pub(crate) fn is_transient(e: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        is_transient_http(e)
+    } else if let Some(e) = e.downcast_ref::<std::io::Error>() {
+        is_transient_io(e)
+    } else if let Some(e) = e.downcast_ref::<git2::Error>() {
+        is_transient_git(e)
+    } else {
+        false
+    }
+}
+
+/// Why the archive layer failed to produce a snapshot
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error(
+        "Wayback is rate limiting save requests for {}",
+        crate::redact::redact(url)
+    )]
+    RateLimited { url: String },
+
+    #[error(
+        "Wayback failed to archive {}: {}",
+        crate::redact::redact(url),
+        crate::redact::redact(reason)
+    )]
+    SaveFailed { url: String, reason: String },
+
+    #[error("No archive available for {}", crate::redact::redact(url))]
+    NoSnapshot { url: String },
+
+    #[error("Download of {} failed: {}", crate::redact::redact(url), crate::redact::redact(&source.to_string()))]
+    DownloadFailed {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    #[error(
+        "Archived content at {} is invalid: {source}",
+        crate::redact::redact(url)
+    )]
+    InvalidContent {
+        url: String,
+        #[source]
+        source: Box<AppError>,
+    },
+
+    #[error("Archiving ran out of its {}s budget before requesting {}", budget.as_secs(), crate::redact::redact(url))]
+    DeadlineExceeded { url: String, budget: Duration },
+}
+
+impl ArchiveError {
+    pub fn invalid_content(url: &str, source: AppError) -> Self {
+        ArchiveError::InvalidContent {
+            url: url.to_string(),
+            source: Box::new(source),
+        }
+    }
+
+    /// Rate limiting and transient download failures may clear up on a later attempt;
+    /// Wayback refusing or lacking a snapshot will not
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ArchiveError::RateLimited { .. } => true,
+            ArchiveError::DownloadFailed { source, .. } => is_transient_http(source),
+            ArchiveError::InvalidContent { source, .. } => source.is_retryable(),
+            ArchiveError::SaveFailed { .. }
+            | ArchiveError::NoSnapshot { .. }
+            | ArchiveError::DeadlineExceeded { .. } => false,
+        }
+    }
+}
+
+/// Content errors convert to the `AppError` describing the content, an exhausted budget
+/// to [`AppError::Timeout`] and everything else to [`AppError::ArchiveFailed`]
+impl From<ArchiveError> for AppError {
+    fn from(e: ArchiveError) -> Self {
+        match e {
+            ArchiveError::InvalidContent { source, .. } => *source,
+            ArchiveError::DeadlineExceeded { budget, .. } => AppError::Timeout(budget),
+            e => AppError::ArchiveFailed(e),
+        }
+    }
+}
+
+fn is_transient_http(e: &reqwest::Error) -> bool {
+    if e.is_timeout() || e.is_connect() {
+        return true;
+    }
+
+    match e.status() {
+        Some(status) => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        None => e.is_request() || e.is_body(),
+    }
+}
+
+fn is_transient_io(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+fn is_transient_git(e: &git2::Error) -> bool {
+    matches!(e.class(), git2::ErrorClass::Net | git2::ErrorClass::Http)
+        && e.code() != git2::ErrorCode::Auth
 }
 
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -0,0 +1,96 @@
+use crate::{
+    archive::{self, WebArchiveClient},
+    aur::AurPackageManager,
+    config::Config,
+    error::Result,
+};
+use std::{collections::HashMap, fs, path::PathBuf};
+use tracing::info;
+
+/// Overrides that replace the archive step, so rendering can run offline
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOverrides {
+    pub sha256: Option<String>,
+    pub version: Option<String>,
+}
+
+/// The rendered package files
+#[derive(Debug, Clone)]
+pub struct GeneratedFiles {
+    pub pkgbuild: String,
+    pub srcinfo: String,
+}
+
+/// Render PKGBUILD and .SRCINFO for `config` in a scratch directory, without any git access.
+///
+/// The archive step only runs when `overrides` does not provide both the version and the
+/// SHA256; values that are provided always take precedence over the archived ones.
+pub async fn generate(config: &Config, overrides: GenerateOverrides) -> Result<GeneratedFiles> {
+    let (version, sha256, digests, archive_url) = match (overrides.version, overrides.sha256) {
+        (Some(version), Some(sha256)) => {
+            info!("Using version and SHA256 overrides, skipping archive step");
+            let archive_url = WebArchiveClient::from_config(config)?
+                .snapshot_url(Some(&version), &config.original_url);
+            (version, sha256, HashMap::new(), archive_url)
+        }
+        (version, sha256) => {
+            info!(
+                "Archiving {} to render package files...",
+                config.original_url
+            );
+            let result = archive::archiver_from_config(config)?
+                .archive_with_mode(&config.original_url, config.archive_mode)
+                .await?;
+            archive::validate_source(
+                &result.content,
+                result.content_type.as_deref(),
+                config.source_format,
+            )?;
+            // The other digests describe the archived file, not an overridden SHA256
+            let digests = if sha256.is_some() {
+                HashMap::new()
+            } else {
+                result.digests
+            };
+            (
+                version.unwrap_or(result.version),
+                sha256.unwrap_or(result.sha256),
+                digests,
+                result.archive_url,
+            )
+        }
+    };
+
+    let scratch_dir =
+        std::env::temp_dir().join(format!("aur-aacs-keydb-generate-{}", std::process::id()));
+    fs::create_dir_all(&scratch_dir)?;
+    let pkgbuild_path = scratch_dir.join("PKGBUILD");
+
+    let aur_manager = AurPackageManager::new(
+        config.package_name.clone(),
+        config.source_filename_template.clone(),
+    )
+    .with_pkgbuild_template(config.pkgbuild_template_path.clone().map(PathBuf::from))
+    .with_pkgrel(config.pkgrel)
+    .with_install_target(config.install_target())
+    .with_wayback_host(&config.wayback_host);
+    let rendered = aur_manager
+        .create_initial_pkgbuild(&pkgbuild_path, &config.original_url, &version, &sha256)
+        .and_then(|release| {
+            aur_manager.update_checksums(&pkgbuild_path, &digests)?;
+            let srcinfo = aur_manager.generate_srcinfo(
+                &pkgbuild_path,
+                &version,
+                release,
+                &sha256,
+                &archive_url,
+            )?;
+            Ok(GeneratedFiles {
+                pkgbuild: fs::read_to_string(&pkgbuild_path)?,
+                srcinfo,
+            })
+        });
+
+    fs::remove_dir_all(&scratch_dir)?;
+    Ok(rendered?)
+}
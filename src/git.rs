@@ -1,99 +1,658 @@
+use crate::{config::Config, error::AppError, host_key::HostKeyPolicy};
 use anyhow::Result;
-use git2::{build::RepoBuilder, Cred, FetchOptions, RemoteCallbacks, Repository};
-use std::path::Path;
-use tracing::info;
+use git2::{
+    CertificateCheckStatus, Cred, FetchOptions, RemoteCallbacks, Repository, ResetType, Signature,
+    build::{CheckoutBuilder, RepoBuilder},
+};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// Minimum time between two progress log lines of a clone, fetch or checkout
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether `e` reports that the remote has no repository at the requested path.
+///
+/// The AUR serves an empty repository for a package name that was never pushed, which
+/// clones fine and gets its initial commit on the first run; this only matches remotes
+/// refusing the path outright, e.g. a package that was deleted or merged into another.
+pub fn is_missing_repository(e: &git2::Error) -> bool {
+    let message = e.message().to_ascii_lowercase();
+    (e.code() == git2::ErrorCode::NotFound
+        && !matches!(
+            e.class(),
+            git2::ErrorClass::Config | git2::ErrorClass::Reference | git2::ErrorClass::Object
+        ))
+        || message.contains("repository not found")
+        || message.contains("does not appear to be a git repository")
+        || message.contains("invalid repository name")
+        // The local transport used by `file://` remote bases
+        || message.starts_with("failed to resolve path")
+}
+
+/// Report a clone or fetch failing because the remote repository doesn't exist as
+/// [`AppError::PackageNotFound`]
+fn missing_package_error(e: anyhow::Error, package_name: &str) -> anyhow::Error {
+    match e.downcast_ref::<git2::Error>() {
+        Some(git) if is_missing_repository(git) => AppError::PackageNotFound {
+            name: package_name.to_string(),
+        }
+        .into(),
+        _ => e,
+    }
+}
+
+/// Rate limits progress logging; the first and the final update always pass
+struct ProgressThrottle {
+    last: Option<Instant>,
+}
+
+impl ProgressThrottle {
+    fn new() -> Self {
+        Self { last: None }
+    }
+
+    fn ready(&mut self, done: bool) -> bool {
+        let now = Instant::now();
+        let ready = done
+            || self
+                .last
+                .is_none_or(|last| now.duration_since(last) >= PROGRESS_LOG_INTERVAL);
+        if ready {
+            self.last = Some(now);
+        }
+        ready
+    }
+}
+
+/// Forced checkout logging its progress at DEBUG
+fn checkout_builder() -> CheckoutBuilder<'static> {
+    let mut throttle = ProgressThrottle::new();
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().progress(move |_, completed, total| {
+        if throttle.ready(completed == total) {
+            debug!("Checkout: {completed}/{total} files");
+        }
+    });
+    checkout
+}
+
+/// How to authenticate against the AUR remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    SshKey,
+    HttpsToken,
+}
+
+/// Pick the authentication method for a remote URL.
+///
+/// A token is only used for `https://` remotes; everything else uses the SSH key.
+pub fn auth_method(url: &str, has_token: bool) -> AuthMethod {
+    if has_token && url.starts_with("https://") {
+        AuthMethod::HttpsToken
+    } else {
+        AuthMethod::SshKey
+    }
+}
+
+/// Git operations the update process needs on the package repository.
+///
+/// Repositories are addressed by their work tree path, so implementations are free to
+/// keep them anywhere (or nowhere, for an in-memory fake).
+pub trait RepoManager: Send + Sync {
+    /// Make `path` a clean checkout of the package's AUR repository
+    fn prepare(&self, path: &Path, package_name: &str, reclone_if_broken: bool) -> Result<()>;
+
+    /// Commit all changes in `path` with `message` and push them to the remote.
+    ///
+    /// Returns `false` without committing when the work tree matches HEAD.
+    fn commit_and_push(&self, path: &Path, message: &str) -> Result<bool>;
+}
 
 pub struct GitHelper {
     ssh_key_path: String,
+    https_token: Option<String>,
+    author_name: Option<String>,
+    author_email: Option<String>,
+    aur_ssh_user: String,
+    aur_host: String,
+    aur_port: u16,
+    remote_base: Option<String>,
+    push_remote: String,
+    push_remote_url: Option<String>,
+    host_key_policy: HostKeyPolicy,
+    /// Set by the certificate check when it rejects a host key, so the caller gets the
+    /// typed error rather than libgit2's generic callback failure
+    host_key_rejection: Arc<Mutex<Option<AppError>>>,
 }
 
+/// AUR host used unless configured otherwise
+pub const DEFAULT_AUR_HOST: &str = "aur.archlinux.org";
+
 impl GitHelper {
-    pub fn new(ssh_key_path: String) -> Self {
-        Self { ssh_key_path }
+    pub fn new(
+        ssh_key_path: String,
+        https_token: Option<String>,
+        author_name: Option<String>,
+        author_email: Option<String>,
+    ) -> Self {
+        Self {
+            ssh_key_path,
+            https_token,
+            author_name,
+            author_email,
+            aur_ssh_user: "aur".to_string(),
+            aur_host: DEFAULT_AUR_HOST.to_string(),
+            aur_port: 22,
+            remote_base: None,
+            push_remote: "origin".to_string(),
+            push_remote_url: None,
+            host_key_policy: HostKeyPolicy::default(),
+            host_key_rejection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Helper set up with the credentials, commit author and AUR remote from `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.ssh_key_path.clone(),
+            config.git_https_token.clone(),
+            config.git_author_name.clone(),
+            config.git_author_email.clone(),
+        )
+        .with_aur_remote(&config.aur_ssh_user, &config.aur_host, config.aur_port)
+        .with_push_remote(&config.push_remote, config.push_remote_url.clone())
+        .with_host_key_policy(config.host_key_policy())
+    }
+
+    /// Verify SSH host keys of remotes according to `policy`
+    pub fn with_host_key_policy(mut self, policy: HostKeyPolicy) -> Self {
+        self.host_key_policy = policy;
+        self
+    }
+
+    /// Replace `e` with the host key rejection that caused it, if any
+    fn host_key_error(&self, e: anyhow::Error) -> anyhow::Error {
+        match self
+            .host_key_rejection
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            Some(rejection) => rejection.into(),
+            None => e,
+        }
     }
 
-    pub fn prepare_aur_repo(&self, path: &Path, package_name: &str) -> Result<Repository> {
+    /// Push to the remote `name` (e.g. a personal fork) instead of `origin`, while still
+    /// syncing from `origin`, adding it with `url` or updating its URL when given. Changes
+    /// pushed anywhere but `origin` are logged as a patch for manual submission to the AUR.
+    pub fn with_push_remote(mut self, name: &str, url: Option<String>) -> Self {
+        self.push_remote = name.to_string();
+        self.push_remote_url = url;
+        self
+    }
+
+    /// Push to this AUR instance (e.g. a staging server) instead of aur.archlinux.org
+    pub fn with_aur_remote(mut self, ssh_user: &str, host: &str, port: u16) -> Self {
+        self.aur_ssh_user = ssh_user.to_string();
+        self.aur_host = host.to_string();
+        self.aur_port = port;
+        self
+    }
+
+    /// Use `{base}/{package_name}.git` as the remote instead of the AUR, e.g. a
+    /// `file://` directory of bare repositories
+    pub fn with_remote_base(mut self, base: impl Into<String>) -> Self {
+        self.remote_base = Some(base.into());
+        self
+    }
+
+    /// AUR remote URL: HTTPS when a token is configured, SSH otherwise
+    pub fn repo_url(&self, package_name: &str) -> String {
+        if let Some(base) = &self.remote_base {
+            format!("{}/{package_name}.git", base.trim_end_matches('/'))
+        } else if self.https_token.is_some() {
+            format!("https://{}/{package_name}.git", self.aur_host)
+        } else if self.aur_port == 22 {
+            format!(
+                "ssh://{}@{}/{package_name}.git",
+                self.aur_ssh_user, self.aur_host
+            )
+        } else {
+            format!(
+                "ssh://{}@{}:{}/{package_name}.git",
+                self.aur_ssh_user, self.aur_host, self.aur_port
+            )
+        }
+    }
+
+    /// Connect to the package's AUR remote with the configured credentials and
+    /// disconnect again, without fetching anything
+    pub fn check_connection(&self, package_name: &str) -> Result<()> {
+        let mut remote = git2::Remote::create_detached(self.repo_url(package_name))?;
+        remote
+            .connect_auth(git2::Direction::Fetch, Some(self.remote_callbacks()), None)
+            .map_err(|e| self.host_key_error(e.into()))?;
+        remote.disconnect()?;
+        Ok(())
+    }
+
+    fn remote_callbacks(&self) -> RemoteCallbacks<'static> {
         let mut cb = RemoteCallbacks::new();
         let ssh_key_path = self.ssh_key_path.clone();
+        let https_token = self.https_token.clone();
+
+        let policy = self.host_key_policy.clone();
+        let rejection = Arc::clone(&self.host_key_rejection);
+        let (aur_host, aur_port) = (self.aur_host.clone(), self.aur_port);
+        cb.certificate_check(move |cert, host| {
+            // TLS certificates of HTTPS remotes are left to libgit2's own verification
+            let Some(key) = cert.as_hostkey().and_then(|hostkey| hostkey.hostkey()) else {
+                return Ok(CertificateCheckStatus::CertificatePassthrough);
+            };
+            // Only the AUR port is configurable; other remotes (a fork) use the default
+            let port = if host == aur_host { aur_port } else { 22 };
+            match policy.verify(host, port, key) {
+                Ok(()) => Ok(CertificateCheckStatus::CertificateOk),
+                Err(e) => {
+                    let message = e.to_string();
+                    *rejection.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                    Err(git2::Error::from_str(&message))
+                }
+            }
+        });
 
-        cb.credentials(move |_, user, _| {
-            Cred::ssh_key(user.unwrap(), None, Path::new(&ssh_key_path), None)
+        cb.credentials(move |url, user, _| {
+            match (auth_method(url, https_token.is_some()), &https_token) {
+                (AuthMethod::HttpsToken, Some(token)) => {
+                    Cred::userpass_plaintext(user.unwrap_or("aur"), token)
+                }
+                _ => Cred::ssh_key(user.unwrap(), None, Path::new(&ssh_key_path), None),
+            }
+        });
+
+        cb
+    }
+
+    /// Fetch options authenticating like [`Self::remote_callbacks`] and logging transfer
+    /// progress at DEBUG, so a slow network shows up as a stalled byte count
+    fn fetch_options(&self) -> FetchOptions<'static> {
+        let mut callbacks = self.remote_callbacks();
+        let mut throttle = ProgressThrottle::new();
+        callbacks.transfer_progress(move |progress| {
+            let total = progress.total_objects();
+            let received = progress.received_objects();
+            if throttle.ready(total > 0 && progress.indexed_objects() == total) {
+                let percent = (received * 100).checked_div(total).unwrap_or(0);
+                debug!(
+                    "Fetch: {percent}% ({received}/{total} objects, {} bytes received, {}/{total} indexed)",
+                    progress.received_bytes(),
+                    progress.indexed_objects()
+                );
+            }
+            true
         });
 
         let mut fo = FetchOptions::new();
-        fo.remote_callbacks(cb);
+        fo.remote_callbacks(callbacks);
+        fo
+    }
+
+    /// Clone or update the AUR repository at `path`.
+    ///
+    /// When `reclone_if_broken` is set, an existing directory that is not a usable
+    /// repository (e.g. left behind by an interrupted clone) is deleted and cloned again.
+    pub fn prepare_aur_repo(
+        &self,
+        path: &Path,
+        package_name: &str,
+        reclone_if_broken: bool,
+    ) -> Result<Repository> {
+        let mut fo = self.fetch_options();
 
-        let repo_url = format!("ssh://aur@aur.archlinux.org/{package_name}.git");
+        let repo_url = self.repo_url(package_name);
 
         if path.exists() {
-            info!("Repository exists, updating...");
-            let repo = Repository::open(path)?;
-            {
-                let mut origin = repo.find_remote("origin")?;
-                origin.fetch(&["refs/heads/*:refs/heads/*"], Some(&mut fo), None)?;
-            }
-            {
-                let fetch_head = repo.find_reference("FETCH_HEAD")?;
-                let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
-                let analysis = repo.merge_analysis(&[&fetch_commit])?;
-                if analysis.0.is_fast_forward() {
-                    let mut master = repo.find_reference("refs/heads/master")?;
-                    master.set_target(fetch_commit.id(), "fast-forward")?;
-                    repo.set_head("refs/heads/master")?;
-                    repo.checkout_head(None)?;
-                    info!("Repository updated successfully");
-                } else {
-                    info!("Repository is up to date");
+            match Self::open_existing(path) {
+                Ok(repo) => {
+                    info!("Repository exists, updating...");
+                    Self::update_existing(&repo, "origin", &mut fo)
+                        .map_err(|e| missing_package_error(e, package_name))?;
+                    self.ensure_push_remote(&repo)?;
+                    self.check_push_remote(&repo);
+                    return Ok(repo);
+                }
+                Err(reason) if reclone_if_broken => {
+                    warn!(
+                        "{} is not a usable repository ({reason}), deleting it and re-cloning",
+                        path.display()
+                    );
+                    std::fs::remove_dir_all(path)?;
                 }
+                Err(reason) => {
+                    anyhow::bail!(
+                        "{} is not a usable repository ({reason}) and is outside the managed work dir, refusing to delete it",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        info!("Cloning repository...");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let repo = RepoBuilder::new()
+            .fetch_options(fo)
+            .with_checkout(checkout_builder())
+            .clone(&repo_url, path)
+            .map_err(|e| missing_package_error(e.into(), package_name))?;
+
+        info!("Repository cloned successfully");
+        self.ensure_push_remote(&repo)?;
+        self.check_push_remote(&repo);
+        Ok(repo)
+    }
+
+    /// Warn when the push remote's master lacks commits of the AUR master, which the
+    /// local master was just synced to. The push remote is never synced from.
+    fn check_push_remote(&self, repo: &Repository) {
+        if self.push_remote == "origin" {
+            return;
+        }
+
+        let tracking = format!("refs/remotes/{}/master", self.push_remote);
+        let refspec = format!("+refs/heads/master:{tracking}");
+        let fetched = repo.find_remote(&self.push_remote).and_then(|mut remote| {
+            remote.fetch(&[&refspec], Some(&mut self.fetch_options()), None)
+        });
+        if let Err(e) = fetched {
+            warn!(remote = %self.push_remote, "Could not fetch the push remote: {}", e.message());
+            return;
+        }
+
+        let (Ok(local), Ok(pushed)) = (
+            repo.refname_to_id("refs/heads/master"),
+            repo.refname_to_id(&tracking),
+        ) else {
+            return;
+        };
+        if let Ok((ahead, behind)) = repo.graph_ahead_behind(local, pushed)
+            && ahead > 0
+        {
+            warn!(
+                remote = %self.push_remote,
+                "{}/master is {ahead} commit(s) behind the AUR{}, pushing to it may be rejected",
+                self.push_remote,
+                if behind > 0 { format!(" and {behind} commit(s) ahead") } else { String::new() }
+            );
+        }
+    }
+
+    /// Add the push remote to `repo`, or point it at the configured URL
+    fn ensure_push_remote(&self, repo: &Repository) -> Result<()> {
+        if self.push_remote == "origin" {
+            return Ok(());
+        }
+
+        match (repo.find_remote(&self.push_remote), &self.push_remote_url) {
+            (Ok(remote), Some(url)) if remote.url() != Some(url) => {
+                repo.remote_set_url(&self.push_remote, url)?;
+            }
+            (Ok(_), _) => {}
+            (Err(_), Some(url)) => {
+                info!(remote = %self.push_remote, "Adding push remote {url}");
+                repo.remote(&self.push_remote, url)?;
             }
-            Ok(repo)
+            (Err(e), None) => {
+                anyhow::bail!("Push remote {} is not configured: {e}", self.push_remote)
+            }
+        }
+        Ok(())
+    }
+
+    fn update_existing(repo: &Repository, remote: &str, fo: &mut FetchOptions) -> Result<()> {
+        repo.find_remote(remote)?
+            .fetch(&["master"], Some(fo), None)?;
+
+        // A package that was never pushed has no master on the remote, in which case the
+        // fetch succeeds but leaves FETCH_HEAD missing or empty
+        let fetched = std::fs::read_to_string(repo.path().join("FETCH_HEAD"))
+            .is_ok_and(|content| !content.trim().is_empty());
+        if !fetched {
+            info!("Remote has no commits yet, the first push will create master");
+            return Self::discard_local_changes(repo);
+        }
+
+        let fetch_head = repo.find_reference("FETCH_HEAD")?;
+        let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
+
+        if repo.find_reference("refs/heads/master").is_err() {
+            info!("Local repository has no master yet, checking out {remote}/master...");
+            repo.reference(
+                "refs/heads/master",
+                fetch_commit.id(),
+                true,
+                "initial checkout",
+            )?;
+            repo.set_head("refs/heads/master")?;
+            repo.checkout_head(Some(&mut checkout_builder()))?;
+            return Ok(());
+        }
+
+        let analysis = repo.merge_analysis(&[&fetch_commit])?;
+        let local_commit = repo.find_reference("refs/heads/master")?.peel_to_commit()?;
+        let (ahead, behind) = repo.graph_ahead_behind(local_commit.id(), fetch_commit.id())?;
+
+        if analysis.0.is_up_to_date() {
+            info!("Repository is up to date");
+        } else if analysis.0.is_fast_forward() {
+            info!("Local master is {behind} commit(s) behind {remote}, fast-forwarding...");
+            let mut master = repo.find_reference("refs/heads/master")?;
+            master.set_target(fetch_commit.id(), "fast-forward")?;
+            repo.set_head("refs/heads/master")?;
+            info!("Repository updated successfully");
         } else {
-            info!("Repository does not exist, cloning...");
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
+            warn!(
+                "Local master has diverged from {remote} ({ahead} commit(s) ahead, {behind} commit(s) behind)"
+            );
+            warn!("Discarding local commits and resetting to {remote}/master...");
+            repo.set_head("refs/heads/master")?;
+            let target = repo.find_object(fetch_commit.id(), None)?;
+            repo.reset(&target, ResetType::Hard, None)?;
+            info!("Repository reset to {}", fetch_commit.id());
+        }
+
+        Self::discard_local_changes(repo)
+    }
+
+    /// Check out HEAD over the work tree, dropping changes and untracked files left by a
+    /// cancelled or failed run. With an unborn HEAD every file is left over and removed.
+    fn discard_local_changes(repo: &Repository) -> Result<()> {
+        let mut checkout = checkout_builder();
+        checkout.remove_untracked(true);
+        match repo.head() {
+            Ok(_) => repo.checkout_head(Some(&mut checkout))?,
+            Err(_) => {
+                let empty = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+                repo.checkout_tree(empty.as_object(), Some(&mut checkout))?;
             }
+        }
+        Ok(())
+    }
 
-            let repo = RepoBuilder::new()
-                .fetch_options(fo)
-                .clone(&repo_url, path)?;
+    /// Build the commit signature from the configured author, falling back to git config
+    fn signature(&self, repo: &Repository) -> Result<Signature<'static>> {
+        let fallback = repo.signature().ok();
+        let name = self
+            .author_name
+            .clone()
+            .or_else(|| fallback.as_ref().and_then(|s| s.name().map(str::to_string)));
+        let email = self.author_email.clone().or_else(|| {
+            fallback
+                .as_ref()
+                .and_then(|s| s.email().map(str::to_string))
+        });
 
-            info!("Repository cloned successfully");
-            Ok(repo)
+        match (name, email) {
+            (Some(name), Some(email)) => Ok(Signature::now(&name, &email)?),
+            _ => anyhow::bail!(
+                "No commit author available: set GIT_AUTHOR_NAME and GIT_AUTHOR_EMAIL, \
+                 or configure user.name and user.email in git"
+            ),
         }
     }
 
-    pub fn commit_and_push(&self, repo: &Repository, message: &str) -> Result<()> {
+    fn open_existing(path: &Path) -> std::result::Result<Repository, String> {
+        if !path.join(".git").exists() {
+            return Err("missing .git directory".to_string());
+        }
+
+        let repo = Repository::open(path).map_err(|e| e.message().to_string())?;
+        if repo.find_remote("origin").is_err() {
+            return Err("missing origin remote".to_string());
+        }
+
+        Ok(repo)
+    }
+
+    /// Stage everything in the work tree, commit it on HEAD and push master to the push
+    /// remote (`origin` unless configured otherwise).
+    ///
+    /// Returns `false` without committing or pushing when the staged tree is identical
+    /// to HEAD, so regenerating byte-identical files never creates an empty commit.
+    pub fn commit_and_push(&self, repo: &Repository, message: &str) -> Result<bool> {
         let mut index = repo.index()?;
         index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
         index.write()?;
 
+        // An empty repository (a package that was never pushed) has an unborn HEAD; its
+        // first commit has no parent and creates master
+        let head = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let head_tree = head.as_ref().map(|head| head.tree()).transpose()?;
+        let staged = repo.diff_tree_to_index(head_tree.as_ref(), Some(&index), None)?;
+        if staged.deltas().len() == 0 {
+            info!("No changes to commit");
+            return Ok(false);
+        }
+
         let tree_id = index.write_tree()?;
         let tree = repo.find_tree(tree_id)?;
-        let signature = repo.signature()?;
-        let head = repo.head()?.peel_to_commit()?;
+        let signature = self.signature(repo)?;
+        let parents: Vec<&git2::Commit> = head.iter().collect();
 
+        if head.is_none() {
+            info!("Creating the initial commit on master");
+        }
         repo.commit(
-            Some("HEAD"),
+            Some("refs/heads/master"),
             &signature,
             &signature,
             message,
             &tree,
-            &[&head],
+            &parents,
         )?;
+        repo.set_head("refs/heads/master")?;
 
-        let mut cb = RemoteCallbacks::new();
-        let ssh_key_path = self.ssh_key_path.clone();
+        if let Err(e) = self.push_master(repo) {
+            warn!("Push failed: {e}");
+            Self::rollback_commit(repo, head.as_ref())?;
+            return Err(e);
+        }
+
+        if self.push_remote != "origin" {
+            let patch = Self::patch(repo, head_tree.as_ref(), &tree)?;
+            info!(
+                remote = %self.push_remote,
+                "Pushed to {}, submit this change to the AUR manually:\n{patch}",
+                self.push_remote
+            );
+        }
+
+        Ok(true)
+    }
+
+    /// Unified diff from `old` (an empty tree when `None`) to `new`
+    fn patch(repo: &Repository, old: Option<&git2::Tree>, new: &git2::Tree) -> Result<String> {
+        let diff = repo.diff_tree_to_tree(old, Some(new), None)?;
+        let mut patch = String::new();
+        diff.print(git2::DiffFormat::Patch, |_, _, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin());
+            }
+            patch.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+        Ok(patch)
+    }
 
-        cb.credentials(move |_, user, _| {
-            Cred::ssh_key(user.unwrap(), None, Path::new(&ssh_key_path), None)
+    /// Push master to the push remote, failing when it rejects the update
+    fn push_master(&self, repo: &Repository) -> Result<()> {
+        let rejection = Arc::new(Mutex::new(None));
+        let mut callbacks = self.remote_callbacks();
+        callbacks.push_update_reference({
+            let rejection = Arc::clone(&rejection);
+            move |refname, status| {
+                if let Some(status) = status {
+                    *rejection.lock().unwrap_or_else(|e| e.into_inner()) =
+                        Some(format!("{refname}: {status}"));
+                }
+                Ok(())
+            }
         });
 
         let mut push_options = git2::PushOptions::new();
-        push_options.remote_callbacks(cb);
+        push_options.remote_callbacks(callbacks);
 
-        let mut origin = repo.find_remote("origin")?;
-        origin.push(&["refs/heads/master:refs/heads/master"], Some(&mut push_options))?;
+        let mut remote = repo.find_remote(&self.push_remote)?;
+        remote.push(
+            &["refs/heads/master:refs/heads/master"],
+            Some(&mut push_options),
+        )?;
 
+        if let Some(reason) = rejection.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            anyhow::bail!("Remote rejected the push: {reason}");
+        }
         Ok(())
     }
+
+    /// Move master, the index and the work tree back to `previous` (or make master unborn
+    /// again with an empty work tree) after a failed push, so the next run doesn't start
+    /// from a local commit the remote never received
+    fn rollback_commit(repo: &Repository, previous: Option<&git2::Commit>) -> Result<()> {
+        match previous {
+            Some(previous) => {
+                repo.reset(previous.as_object(), ResetType::Hard, None)?;
+                info!("Rolled master back to {}", previous.id());
+            }
+            None => {
+                repo.find_reference("refs/heads/master")?.delete()?;
+                Self::discard_local_changes(repo)?;
+                info!("Removed the unpushed initial commit");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RepoManager for GitHelper {
+    fn prepare(&self, path: &Path, package_name: &str, reclone_if_broken: bool) -> Result<()> {
+        self.prepare_aur_repo(path, package_name, reclone_if_broken)
+            .map_err(|e| self.host_key_error(e))?;
+        Ok(())
+    }
+
+    fn commit_and_push(&self, path: &Path, message: &str) -> Result<bool> {
+        let repo = Repository::open(path)?;
+        GitHelper::commit_and_push(self, &repo, message).map_err(|e| self.host_key_error(e))
+    }
 }
@@ -0,0 +1,130 @@
+use crate::{archive::ArchiveResult, error::Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+use tracing::warn;
+
+/// One successful update, as stored in the history file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub package: String,
+    pub run_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub archive: ArchiveResult,
+    /// Missing in entries written before timings were recorded
+    #[serde(default)]
+    pub timings: PhaseTimings,
+}
+
+/// Time spent in each step of a run, stored as fractional seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    /// Archiving and downloading the source
+    #[serde(with = "secs_f64")]
+    pub archive: Duration,
+    /// Cloning or updating the AUR repository
+    #[serde(with = "secs_f64")]
+    pub clone: Duration,
+    /// Comparing with the AUR package and regenerating its files
+    #[serde(with = "secs_f64")]
+    pub diff: Duration,
+    /// Committing and pushing
+    #[serde(with = "secs_f64")]
+    pub push: Duration,
+}
+
+mod secs_f64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Append-only JSONL record of successful updates, trimmed to `max_entries`
+pub struct History {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>, max_entries: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_entries,
+        }
+    }
+
+    /// Append `entry`, dropping the oldest entries once there are more than `max_entries`
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let line = serde_json::to_string(entry).map_err(io::Error::from)?;
+        let mut lines = self.read_lines()?;
+
+        if lines.len() < self.max_entries {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            writeln!(file, "{line}")?;
+            return Ok(());
+        }
+
+        lines.push(line);
+        let keep = lines.len().saturating_sub(self.max_entries);
+        let mut content = lines[keep..].join("\n");
+        content.push('\n');
+
+        // Rewrite through a temporary file so an interrupted trim can't lose the history
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// The `n` most recent entries, oldest first. Unreadable lines are skipped.
+    pub fn load_recent(&self, n: usize) -> Result<Vec<HistoryEntry>> {
+        let lines = self.read_lines()?;
+        let start = lines.len().saturating_sub(n);
+
+        Ok(lines[start..]
+            .iter()
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!(
+                        "Skipping malformed history entry in {}: {e}",
+                        self.path.display()
+                    );
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn read_lines(&self) -> Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
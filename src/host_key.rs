@@ -0,0 +1,166 @@
+use crate::error::AppError;
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+};
+use hmac::{Hmac, KeyInit, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// How the SSH host key of a git remote is verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKeyPolicy {
+    /// OpenSSH `known_hosts` file listing the accepted keys
+    pub known_hosts: Option<PathBuf>,
+    /// Expected `SHA256:...` fingerprint as printed by `ssh-keygen -lf`; when set, the
+    /// known_hosts file is not consulted
+    pub fingerprint: Option<String>,
+    /// Reject hosts without a known key instead of accepting them with a warning
+    pub strict: bool,
+}
+
+impl Default for HostKeyPolicy {
+    /// `~/.ssh/known_hosts`, strictly checked
+    fn default() -> Self {
+        Self {
+            known_hosts: dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts")),
+            fingerprint: None,
+            strict: true,
+        }
+    }
+}
+
+impl HostKeyPolicy {
+    /// Check the raw host `key` presented by `host:port`
+    pub fn verify(&self, host: &str, port: u16, key: &[u8]) -> Result<(), AppError> {
+        let actual = fingerprint(key);
+
+        if let Some(expected) = &self.fingerprint {
+            return if normalize_fingerprint(expected) == actual {
+                Ok(())
+            } else {
+                Err(AppError::HostKeyMismatch {
+                    host: host.to_string(),
+                    fingerprint: actual,
+                })
+            };
+        }
+
+        let known_hosts = match &self.known_hosts {
+            Some(path) => std::fs::read_to_string(path).unwrap_or_else(|e| {
+                warn!("Could not read known_hosts {}: {e}", path.display());
+                String::new()
+            }),
+            None => String::new(),
+        };
+        let known = known_keys(&known_hosts, host, port);
+
+        if known.is_empty() {
+            if self.strict {
+                return Err(AppError::UnknownHostKey {
+                    host: host.to_string(),
+                    fingerprint: actual,
+                });
+            }
+            warn!("Accepting unknown host key {actual} of {host}, strict host key checking is off");
+            return Ok(());
+        }
+
+        if known.iter().any(|known| known == key) {
+            Ok(())
+        } else {
+            Err(AppError::HostKeyMismatch {
+                host: host.to_string(),
+                fingerprint: actual,
+            })
+        }
+    }
+}
+
+/// `SHA256:` fingerprint of a raw host key, in the format of `ssh-keygen -lf`
+pub fn fingerprint(key: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(key)))
+}
+
+/// Accept fingerprints with or without the `SHA256:` prefix and base64 padding
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = fingerprint.trim();
+    let hash = fingerprint.strip_prefix("SHA256:").unwrap_or(fingerprint);
+    format!("SHA256:{}", hash.trim_end_matches('='))
+}
+
+/// Raw keys listed for `host:port` in known_hosts `content`.
+///
+/// Plain, bracketed (`[host]:port`), wildcard and hashed (`|1|salt|hash`) host patterns are
+/// understood; a matching negated pattern excludes the line. `@cert-authority` and
+/// `@revoked` lines are skipped.
+pub fn known_keys(content: &str, host: &str, port: u16) -> Vec<Vec<u8>> {
+    let name = if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{host}]:{port}")
+    };
+
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('@'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let patterns = fields.next()?;
+            let _key_type = fields.next()?;
+            let key = STANDARD.decode(fields.next()?).ok()?;
+            hosts_match(patterns, &name).then_some(key)
+        })
+        .collect()
+}
+
+fn hosts_match(patterns: &str, name: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split(',') {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if pattern_matches(negated, name) {
+                return false;
+            }
+        } else if pattern_matches(pattern, name) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    if let Some(hashed) = pattern.strip_prefix("|1|") {
+        let Some((salt, hash)) = hashed.split_once('|') else {
+            return false;
+        };
+        let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+            return false;
+        };
+        let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+            return false;
+        };
+        mac.update(name.as_bytes());
+        return mac.verify_slice(&hash).is_ok();
+    }
+
+    glob_matches(
+        pattern.to_ascii_lowercase().as_bytes(),
+        name.to_ascii_lowercase().as_bytes(),
+    )
+}
+
+/// `*` and `?` wildcard matching as in ssh_config patterns
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name_rest))) => glob_matches(rest, name_rest),
+        (Some((p, rest)), Some((n, name_rest))) if p == n => glob_matches(rest, name_rest),
+        _ => false,
+    }
+}
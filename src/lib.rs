@@ -0,0 +1,38 @@
+//! Automation for maintaining the `aacs-keydb-daily` AUR package.
+//!
+//! The binary is a thin wrapper around [`App`]; the individual building blocks
+//! ([`WebArchiveClient`], [`AurPackageManager`], [`GitHelper`]) can also be used
+//! on their own to embed the update logic in another tool.
+
+pub mod app;
+pub mod archive;
+pub mod aur;
+pub mod batch;
+pub mod checksum;
+pub mod config;
+pub mod diff;
+pub mod doctor;
+pub mod download_cache;
+pub mod error;
+pub mod generate;
+pub mod git;
+pub mod history;
+pub mod host_key;
+pub mod lock;
+pub mod manifest;
+pub mod metrics;
+pub mod rate_limit;
+pub mod redact;
+pub mod retry;
+pub mod run_state;
+pub mod schedule;
+pub mod shutdown;
+pub mod signature;
+pub mod summary;
+
+pub use app::{App, RunOutcome, UpdateCheck};
+pub use archive::{ArchiveProgress, ArchiveResult, Archiver, MultiArchiver, WebArchiveClient};
+pub use aur::AurPackageManager;
+pub use config::Config;
+pub use error::{AppError, ArchiveError, Result};
+pub use git::{GitHelper, RepoManager};
@@ -0,0 +1,110 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// Advisory lock file preventing concurrent runs from using the same work dir.
+///
+/// The file records the holder's PID and start time. A lock whose holder is no longer
+/// running (e.g. after a crash) is reclaimed with a warning. The lock is released when the
+/// guard is dropped, on success and on error alike.
+pub struct WorkDirLock {
+    path: PathBuf,
+}
+
+/// Holder recorded in a lock file: `{pid} {started_at}`
+struct LockOwner {
+    pid: u32,
+    started_at: DateTime<Utc>,
+}
+
+impl LockOwner {
+    fn parse(content: &str) -> Option<Self> {
+        let (pid, started_at) = content.trim().split_once(' ')?;
+        Some(Self {
+            pid: pid.parse().ok()?,
+            started_at: DateTime::parse_from_rfc3339(started_at).ok()?.to_utc(),
+        })
+    }
+
+    /// Whether the recorded process still runs. A lock naming this process's own PID was
+    /// left by an earlier run whose PID got reused (e.g. PID 1 in a container), so it
+    /// doesn't count. Without `/proc` the holder is assumed to be alive.
+    fn is_alive(&self) -> bool {
+        if self.pid == std::process::id() {
+            return false;
+        }
+        if !Path::new("/proc/self").exists() {
+            return true;
+        }
+        Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+}
+
+impl WorkDirLock {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = match Self::create(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|content| LockOwner::parse(&content));
+                match owner {
+                    Some(owner) if !owner.is_alive() => {
+                        warn!(
+                            "Reclaiming stale work dir lock {} held by PID {} since {}, which is no longer running",
+                            path.display(),
+                            owner.pid,
+                            owner.started_at
+                        );
+                        fs::remove_file(path)?;
+                        Self::create(path).map_err(|e| match e.kind() {
+                            io::ErrorKind::AlreadyExists => AppError::Locked {
+                                path: path.display().to_string(),
+                            },
+                            _ => e.into(),
+                        })?
+                    }
+                    _ => {
+                        return Err(AppError::Locked {
+                            path: path.display().to_string(),
+                        });
+                    }
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        writeln!(file, "{} {}", std::process::id(), Utc::now().to_rfc3339())?;
+
+        info!("Acquired work dir lock {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Release the lock now instead of when the guard goes out of scope
+    pub fn release(self) {
+        drop(self);
+    }
+
+    fn create(path: &Path) -> io::Result<fs::File> {
+        OpenOptions::new().write(true).create_new(true).open(path)
+    }
+}
+
+impl Drop for WorkDirLock {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.path) {
+            Ok(()) => info!("Released work dir lock {}", self.path.display()),
+            Err(e) => warn!("Failed to remove lock {}: {e}", self.path.display()),
+        }
+    }
+}
@@ -1,27 +1,307 @@
-mod app;
-mod archive;
-mod aur;
-mod config;
-mod error;
-mod git;
-
-use app::App;
-use config::Config;
-use error::Result;
-use tracing::Level;
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+mod cli;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::from_default_env().add_directive(Level::INFO.into()))
-        .init();
+use aur_aacs_keydb::{
+    App, AppError, Config, Result, RunOutcome, WebArchiveClient,
+    app::{check_exit_code, exit_code},
+    archive::CdxEntry,
+    batch, doctor,
+    generate::{GenerateOverrides, generate},
+    manifest,
+    redact::RedactingMakeWriter,
+    retry,
+    schedule::Schedule,
+    shutdown,
+};
+use chrono::Utc;
+use clap::Parser;
+use cli::{Cli, Command, LogFormat};
+use std::{process::ExitCode, time::Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use tracing_subscriber::{EnvFilter, fmt, fmt::format::FmtSpan, prelude::*};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
 
+#[tokio::main]
+async fn main() -> ExitCode {
     dotenv::dotenv().ok();
 
-    let config = Config::new();
+    let cli = Cli::parse();
+
+    // Closing a span logs its duration (`time.busy`/`time.idle`), which times each step.
+    // An explicit RUST_LOG takes precedence over the command line level
+    let filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(_) => EnvFilter::from_default_env(),
+        Err(_) => EnvFilter::default().add_directive(cli.level().into()),
+    };
+    match cli.log_format {
+        LogFormat::Human => tracing_subscriber::registry()
+            .with(
+                fmt::layer()
+                    .with_writer(RedactingMakeWriter::new(std::io::stdout))
+                    .with_span_events(FmtSpan::CLOSE),
+            )
+            .with(filter)
+            .init(),
+        LogFormat::Json => tracing_subscriber::registry()
+            .with(
+                fmt::layer()
+                    .json()
+                    .with_writer(RedactingMakeWriter::new(std::io::stdout))
+                    .with_span_events(FmtSpan::CLOSE),
+            )
+            .with(filter)
+            .init(),
+    }
+
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the selected command, returning the exit code for a successful run
+async fn run(cli: Cli) -> Result<ExitCode> {
+    let mut config = Config::from_env()?;
+    cli.apply(&mut config);
+    config.register_secrets();
+
+    if let Some(Command::Generate {
+        sha256, version, ..
+    }) = cli.command
+    {
+        let files = generate(&config, GenerateOverrides { sha256, version }).await?;
+        println!("# PKGBUILD\n{}", files.pkgbuild);
+        println!("# .SRCINFO\n{}", files.srcinfo);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Doctor) = cli.command {
+        let results = doctor::run(&config).await;
+        for result in &results {
+            println!("{result}");
+        }
+        let failed = results.iter().filter(|r| r.critical && !r.passed()).count();
+        if failed > 0 {
+            return Err(AppError::Archive(anyhow::anyhow!(
+                "{failed} critical check(s) failed"
+            )));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Snapshots) = cli.command {
+        let entries = WebArchiveClient::from_config(&config)?
+            .cdx(&config.original_url)
+            .await
+            .map_err(AppError::from_archive)?;
+        println!("{}", snapshot_table(&entries));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Daemon { schedule }) = &cli.command {
+        let schedule = Schedule::parse(schedule)?;
+        return run_daemon(&config, &schedule, cli.exit_code_noop).await;
+    }
+
+    let configs = package_configs(&config).await?;
+
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            shutdown::wait_for_signal().await;
+            warn!("Received shutdown signal, cancelling...");
+            cancel.cancel();
+        }
+    });
+
+    if let Some(Command::Check) = cli.command {
+        let mut checks = Vec::new();
+        for config in configs {
+            let package = config.package_name.clone();
+            let run_timeout = config.run_timeout;
+            let app = App::new(config)?;
+            let check = shutdown::timeout(run_timeout, &cancel, app.check(&cancel)).await?;
+            println!("{package}: {check}");
+            checks.push(check);
+        }
+        return Ok(ExitCode::from(check_exit_code(&checks)));
+    }
+
+    update_packages(configs, &cancel, cli.exit_code_noop).await
+}
+
+/// The configuration of each package: one per manifest entry, or `config` itself
+async fn package_configs(config: &Config) -> Result<Vec<Config>> {
+    let Some(manifest_url) = &config.manifest_url else {
+        return Ok(vec![config.clone()]);
+    };
+
+    let specs = manifest::load(
+        &config.http_client()?,
+        manifest_url,
+        &config.manifest_cache_path(),
+    )
+    .await?;
+    info!("Manifest lists {} package(s)", specs.len());
+    Ok(specs.iter().map(|spec| spec.apply(config)).collect())
+}
+
+/// Update every package in `configs`, returning the exit code for the combined outcome
+async fn update_packages(
+    configs: Vec<Config>,
+    cancel: &CancellationToken,
+    noop_code: u8,
+) -> Result<ExitCode> {
+    let max_concurrency = configs.first().map_or(1, |config| config.max_concurrency);
+    let packages: Vec<String> = configs.iter().map(|c| c.package_name.clone()).collect();
+    let results = batch::run_bounded(configs, max_concurrency, |config| {
+        let cancel = cancel.clone();
+        async move {
+            // Packages still waiting for a slot when the run is cancelled don't start
+            if cancel.is_cancelled() {
+                return Err(AppError::Cancelled);
+            }
+            run_with_retries(config, &cancel).await
+        }
+    })
+    .await;
+
+    let mut outcomes = Vec::new();
+    let mut first_error = None;
+    for (package, result) in packages.iter().zip(results) {
+        match result {
+            Ok(outcome) => {
+                match &outcome {
+                    RunOutcome::Updated { old, new } => {
+                        info!(package = %package, old = ?old, new = %new, "Package updated");
+                    }
+                    RunOutcome::UpToDate => info!(package = %package, "Package is up to date"),
+                    RunOutcome::DryRun { changes } => {
+                        info!(package = %package, "Dry run finished, changes follow");
+                        println!("{changes}");
+                    }
+                    RunOutcome::Skipped { reason } => {
+                        info!(package = %package, "Package update skipped: {reason}");
+                    }
+                }
+                outcomes.push(outcome);
+            }
+            Err(e) => {
+                error!(package = %package, "Update failed: {e}");
+                if matches!(e, AppError::Cancelled) || first_error.is_none() {
+                    first_error = Some(e);
+                }
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(ExitCode::from(exit_code(&outcomes, noop_code))),
+    }
+}
+
+/// Update the packages whenever `schedule` is due until SIGINT or SIGTERM arrives.
+///
+/// A signal during a run lets it finish before exiting; one during the wait between runs
+/// exits right away. A failed run is logged and the next one happens on schedule.
+async fn run_daemon(config: &Config, schedule: &Schedule, noop_code: u8) -> Result<ExitCode> {
+    let stop = CancellationToken::new();
+    tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            shutdown::wait_for_signal().await;
+            warn!("Received shutdown signal, exiting once the current run (if any) finishes...");
+            stop.cancel();
+        }
+    });
+
+    let mut last_run = None;
+    loop {
+        let now = Utc::now();
+        let Some(next) = schedule.next_run(now, last_run) else {
+            warn!("Schedule has no further runs, exiting");
+            return Ok(ExitCode::SUCCESS);
+        };
+        let delay = (next - now).to_std().unwrap_or_default();
+        info!(next_run = %next, "Next run in {}s", delay.as_secs());
+        if shutdown::sleep(delay, &stop).await.is_err() {
+            info!("Daemon stopped");
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        last_run = Some(Utc::now());
+        // The run gets its own token, so a shutdown signal doesn't cancel it midway
+        let result = match package_configs(config).await {
+            Ok(configs) => update_packages(configs, &CancellationToken::new(), noop_code).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("Scheduled run failed, trying again at the next scheduled time: {e}");
+        }
+
+        if stop.is_cancelled() {
+            info!("Daemon stopped");
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+}
+
+/// Run the update for one package, retrying transient failures with backoff
+async fn run_with_retries(config: Config, cancel: &CancellationToken) -> Result<RunOutcome> {
+    let max_attempts = config.max_attempts.max(1);
+    let run_timeout = config.run_timeout;
     let app = App::new(config)?;
 
-    app.run().await
+    let mut attempt = 1;
+    loop {
+        let attempt_cancel = cancel.child_token();
+        match shutdown::timeout(run_timeout, &attempt_cancel, app.run(&attempt_cancel)).await {
+            Ok(outcome) => return Ok(outcome),
+            Err(e) if e.is_retryable() && attempt < max_attempts => {
+                let delay = retry::backoff_delay(attempt, RETRY_BASE_DELAY, RETRY_MAX_DELAY);
+                warn!("Attempt {attempt}/{max_attempts} failed with a transient error: {e}");
+                warn!("Retrying in {}s...", delay.as_secs());
+                shutdown::sleep(delay, cancel).await?;
+                attempt += 1;
+            }
+            Err(e) => {
+                error!("Attempt {attempt}/{max_attempts} failed: {e}");
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// CDX captures as an aligned table, with suspect (5xx) captures flagged
+fn snapshot_table(entries: &[CdxEntry]) -> String {
+    if entries.is_empty() {
+        return "No snapshots found".to_string();
+    }
+
+    let mut table = format!(
+        "{:<14}  {:>6}  {:<32}  {:>10}\n",
+        "TIMESTAMP", "STATUS", "DIGEST", "LENGTH"
+    );
+    for entry in entries {
+        let status = entry
+            .status
+            .map_or("-".to_string(), |status| status.to_string());
+        let length = entry
+            .length
+            .map_or("-".to_string(), |length| length.to_string());
+        let note = if entry.is_suspect() { "  suspect" } else { "" };
+        table.push_str(&format!(
+            "{:<14}  {:>6}  {:<32}  {:>10}{note}\n",
+            entry.timestamp, status, entry.digest, length
+        ));
+    }
+    table.push_str(&format!("{} snapshot(s)", entries.len()));
+    table
 }
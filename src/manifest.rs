@@ -0,0 +1,187 @@
+use crate::{
+    config::Config,
+    error::{AppError, Result},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fs, path::Path};
+use tracing::{info, warn};
+
+/// One package entry of a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageSpec {
+    pub name: String,
+    pub original_url: String,
+    /// Overrides `SOURCE_FILENAME_TEMPLATE` for this package
+    #[serde(default)]
+    pub source_filename_template: Option<String>,
+}
+
+impl PackageSpec {
+    /// Check that the name is non-empty, the URL is an absolute http(s) URL and the
+    /// template contains `${pkgver}`, returning every problem found
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("name is empty".to_string());
+        }
+
+        match url::Url::parse(&self.original_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(url) => problems.push(format!(
+                "original_url has unsupported scheme '{}'",
+                url.scheme()
+            )),
+            Err(e) => problems.push(format!(
+                "original_url '{}' is invalid: {e}",
+                self.original_url
+            )),
+        }
+
+        if let Some(template) = &self.source_filename_template
+            && !template.contains("${pkgver}")
+            && !template.contains("$pkgver")
+        {
+            problems.push(format!(
+                "source_filename_template '{template}' has no ${{pkgver}} placeholder"
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// `base` with this package's name, URL and template applied
+    pub fn apply(&self, base: &Config) -> Config {
+        let mut config = base.clone();
+        config.package_name = self.name.clone();
+        config.original_url = self.original_url.clone();
+        if let Some(template) = &self.source_filename_template {
+            config.source_filename_template = template.clone();
+        }
+        config
+    }
+}
+
+/// Parse a manifest document.
+///
+/// JSON may be either a list of package specs or an object with a `packages` list;
+/// TOML uses `[[packages]]` tables. Entries are checked one by one, so a manifest with
+/// unknown keys, wrong types or invalid values fails with every problem listed.
+pub fn parse(content: &str) -> Result<Vec<PackageSpec>> {
+    let document = match serde_json::from_str::<Value>(content) {
+        Ok(document) => document,
+        Err(json_error) => match toml::from_str::<toml::Table>(content) {
+            Ok(table) => serde_json::to_value(table).map_err(anyhow::Error::from)?,
+            Err(toml_error) => {
+                return Err(AppError::Archive(anyhow::anyhow!(
+                    "Manifest is neither valid JSON ({json_error}) nor valid TOML ({toml_error})"
+                )));
+            }
+        },
+    };
+
+    let mut problems = Vec::new();
+    let entries = match document {
+        Value::Array(entries) => entries,
+        Value::Object(mut fields) => {
+            let entries = match fields.remove("packages") {
+                Some(Value::Array(entries)) => entries,
+                Some(_) => {
+                    problems.push("packages is not a list".to_string());
+                    Vec::new()
+                }
+                None => {
+                    problems.push("packages is missing".to_string());
+                    Vec::new()
+                }
+            };
+            problems.extend(fields.keys().map(|key| format!("unknown key '{key}'")));
+            entries
+        }
+        _ => {
+            problems.push("expected a list of packages or a packages table".to_string());
+            Vec::new()
+        }
+    };
+
+    let mut specs: Vec<PackageSpec> = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let spec = match serde_json::from_value::<PackageSpec>(entry) {
+            Ok(spec) => spec,
+            Err(e) => {
+                problems.push(format!("packages[{index}]: {e}"));
+                continue;
+            }
+        };
+        if let Err(spec_problems) = spec.validate() {
+            problems.extend(
+                spec_problems
+                    .into_iter()
+                    .map(|problem| format!("packages[{index}] ({}): {problem}", spec.name)),
+            );
+        }
+        if specs.iter().any(|other| other.name == spec.name) {
+            problems.push(format!(
+                "packages[{index}]: duplicate package name '{}'",
+                spec.name
+            ));
+        }
+        specs.push(spec);
+    }
+
+    if problems.is_empty() {
+        Ok(specs)
+    } else {
+        Err(AppError::InvalidManifest { problems })
+    }
+}
+
+/// Fetch the manifest from `manifest_url` and parse it.
+///
+/// A successfully fetched manifest is written to `cache_path`; if the fetch fails, the
+/// cached copy is used instead when there is one.
+pub async fn load(
+    client: &reqwest::Client,
+    manifest_url: &str,
+    cache_path: &Path,
+) -> Result<Vec<PackageSpec>> {
+    info!("Fetching package manifest from {manifest_url}");
+
+    match fetch(client, manifest_url).await {
+        Ok(content) => {
+            let specs = parse(&content)?;
+            if let Some(parent) = cache_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if let Err(e) = fs::write(cache_path, &content) {
+                warn!("Could not cache manifest at {}: {e}", cache_path.display());
+            }
+            Ok(specs)
+        }
+        Err(e) => {
+            let Ok(content) = fs::read_to_string(cache_path) else {
+                return Err(e);
+            };
+            warn!(
+                "Could not fetch manifest ({e}), using cached copy {}",
+                cache_path.display()
+            );
+            parse(&content)
+        }
+    }
+}
+
+async fn fetch(client: &reqwest::Client, manifest_url: &str) -> Result<String> {
+    Ok(client
+        .get(manifest_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use prometheus::{Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+use std::{collections::HashMap, time::Duration};
+use tracing::info;
+
+const JOB_NAME: &str = "aur_aacs_keydb";
+
+/// Run metrics pushed to a Prometheus Pushgateway at the end of each run
+pub struct Metrics {
+    registry: Registry,
+    pushgateway_url: String,
+    package_name: String,
+    archives_attempted: IntCounter,
+    archives_succeeded: IntCounter,
+    archives_failed: IntCounter,
+    archive_latency: Histogram,
+    update_checks: IntCounterVec,
+    last_success: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(pushgateway_url: String, package_name: String) -> Result<Self> {
+        let registry = Registry::new();
+
+        let archives_attempted = IntCounter::new(
+            "aur_archives_attempted_total",
+            "Archive attempts on web.archive.org",
+        )?;
+        let archives_succeeded = IntCounter::new(
+            "aur_archives_succeeded_total",
+            "Archive attempts that produced a usable snapshot",
+        )?;
+        let archives_failed =
+            IntCounter::new("aur_archives_failed_total", "Archive attempts that failed")?;
+        let archive_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "aur_archive_duration_seconds",
+                "Time spent archiving and downloading",
+            )
+            .buckets(vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]),
+        )?;
+        let update_checks = IntCounterVec::new(
+            Opts::new("aur_update_checks_total", "Update checks by result"),
+            &["result"],
+        )?;
+        let last_success = IntGauge::new(
+            "aur_last_successful_update_timestamp_seconds",
+            "Unix time of the last successful push",
+        )?;
+
+        registry.register(Box::new(archives_attempted.clone()))?;
+        registry.register(Box::new(archives_succeeded.clone()))?;
+        registry.register(Box::new(archives_failed.clone()))?;
+        registry.register(Box::new(archive_latency.clone()))?;
+        registry.register(Box::new(update_checks.clone()))?;
+        registry.register(Box::new(last_success.clone()))?;
+
+        Ok(Self {
+            registry,
+            pushgateway_url,
+            package_name,
+            archives_attempted,
+            archives_succeeded,
+            archives_failed,
+            archive_latency,
+            update_checks,
+            last_success,
+        })
+    }
+
+    pub fn record_archive(&self, succeeded: bool, elapsed: Duration) {
+        self.archives_attempted.inc();
+        if succeeded {
+            self.archives_succeeded.inc();
+        } else {
+            self.archives_failed.inc();
+        }
+        self.archive_latency.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_update_check(&self, needed: bool) {
+        let result = if needed { "needed" } else { "up_to_date" };
+        self.update_checks.with_label_values(&[result]).inc();
+    }
+
+    pub fn record_success(&self) {
+        self.last_success.set(chrono::Utc::now().timestamp());
+    }
+
+    /// Push all metrics to the Pushgateway, grouped by package name
+    pub async fn push(&self) -> Result<()> {
+        let families = self.registry.gather();
+        let url = self.pushgateway_url.clone();
+        let grouping = HashMap::from([("package".to_string(), self.package_name.clone())]);
+
+        info!("Pushing metrics to {url}");
+        // The prometheus push client is blocking
+        tokio::task::spawn_blocking(move || {
+            prometheus::push_metrics(JOB_NAME, grouping, &url, families, None)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+/// Token bucket spacing requests evenly at a fixed rate, with room for a burst of one.
+///
+/// Shared through an `Arc` so every client built from the same [`crate::Config`] draws
+/// from one budget, however many packages run concurrently.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    /// When the next request may be sent
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Limiter allowing `requests_per_minute` requests per minute (at least one)
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests_per_minute.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Time between two requests
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Wait until a request may be sent, reserving its slot
+    pub async fn acquire(&self) {
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let at = next.map_or(now, |next| next.max(now));
+            *next = Some(at + self.interval);
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+}
@@ -0,0 +1,9 @@
+use std::time::Duration;
+
+/// Delay before retrying after the given (1-based) failed attempt.
+///
+/// Doubles `base` for every attempt and caps the result at `max`.
+pub fn backoff_delay(attempt: u32, base: Duration, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    base.saturating_mul(factor).min(max)
+}
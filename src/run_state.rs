@@ -0,0 +1,70 @@
+use crate::{download_cache::CachedDownload, error::Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, io, path::Path, time::Duration};
+use tracing::warn;
+
+/// State kept between runs of a package, next to its work dir lock
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunState {
+    /// When the last run that wasn't a dry run finished without error
+    #[serde(default)]
+    pub last_success: Option<DateTime<Utc>>,
+    /// Validators and hash of the last download of each URL, for conditional requests
+    #[serde(default)]
+    pub downloads: HashMap<String, CachedDownload>,
+}
+
+impl RunState {
+    /// The state stored at `path`; `None` when there is none yet or it can't be read
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed to read run state {}: {e}", path.display());
+                return None;
+            }
+        };
+        serde_json::from_str(&content)
+            .inspect_err(|e| warn!("Ignoring malformed run state {}: {e}", path.display()))
+            .ok()
+    }
+
+    /// Apply `change` to the state stored at `path` (or an empty one) and write it back
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut state = Self::load(path).unwrap_or_default();
+        change(&mut state);
+        state.save(path)
+    }
+
+    /// Write the state to `path` through a temporary file, so a crash can't leave it
+    /// half-written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(
+            &tmp_path,
+            serde_json::to_string(self).map_err(io::Error::from)?,
+        )?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Why a run at `now` should be skipped, if the last success lies less than
+    /// `min_interval` before it
+    pub fn too_recent(&self, now: DateTime<Utc>, min_interval: Duration) -> Option<String> {
+        let last_success = self.last_success?;
+        let elapsed = now.signed_duration_since(last_success);
+        let min_interval = chrono::Duration::from_std(min_interval).ok()?;
+        (elapsed < min_interval).then(|| {
+            format!(
+                "last successful run was at {}, less than {}s ago",
+                last_success.to_rfc3339(),
+                min_interval.num_seconds()
+            )
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Utc};
+use std::{str::FromStr, time::Duration};
+
+/// When the daemon runs the update
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Right away, then this long after the start of each run
+    Every(Duration),
+    /// At the times matched by a cron expression, in UTC
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Parse an interval (`3600`, `90s`, `30m`, `6h`, `1d`) or a cron expression. Cron
+    /// expressions take 5 fields (`0 4 * * *`) or 6–7 with leading seconds and trailing
+    /// year.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        if let Some(interval) = parse_interval(spec) {
+            if interval.is_zero() {
+                return Err(AppError::Archive(anyhow::anyhow!(
+                    "Schedule interval must be greater than zero"
+                )));
+            }
+            return Ok(Schedule::Every(interval));
+        }
+
+        let expression = match spec.split_whitespace().count() {
+            5 => format!("0 {spec}"),
+            _ => spec.to_string(),
+        };
+        cron::Schedule::from_str(&expression)
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|e| {
+                AppError::Archive(anyhow::anyhow!(
+                    "Invalid schedule '{spec}', expected an interval like 6h or a cron expression: {e}"
+                ))
+            })
+    }
+
+    /// When the next run is due, given the start of the previous one; `None` when a cron
+    /// expression matches no later time
+    pub fn next_run(
+        &self,
+        now: DateTime<Utc>,
+        last_run: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => {
+                let Some(last_run) = last_run else {
+                    return Some(now);
+                };
+                // A run longer than the interval is followed by the next one right away
+                let next = last_run + chrono::Duration::from_std(*interval).ok()?;
+                Some(next.max(now))
+            }
+            Schedule::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+}
+
+/// Seconds, optionally with an `s`, `m`, `h` or `d` unit
+fn parse_interval(spec: &str) -> Option<Duration> {
+    let (number, unit) = match spec.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => spec.split_at(index),
+        None => (spec, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
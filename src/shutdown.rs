@@ -0,0 +1,59 @@
+use crate::error::{AppError, Result};
+use std::{future::Future, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+/// Wait until the process receives SIGINT (Ctrl-C) or SIGTERM
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Sleep for `duration`, returning `AppError::Cancelled` early if `cancel` fires first
+pub async fn sleep(duration: Duration, cancel: &CancellationToken) -> Result<()> {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => Ok(()),
+        _ = cancel.cancelled() => Err(AppError::Cancelled),
+    }
+}
+
+/// How long a timed out run may take to wind down after being cancelled
+const TIMEOUT_GRACE: Duration = Duration::from_secs(10);
+
+/// Drive `run` for at most `duration`.
+///
+/// On expiry `cancel` is fired and `run` gets a short grace period to wind down (release
+/// locks, log partial work) before it is dropped; the result is then `AppError::Timeout`.
+pub async fn timeout<T>(
+    duration: Duration,
+    cancel: &CancellationToken,
+    run: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::pin!(run);
+    tokio::select! {
+        result = &mut run => result,
+        _ = tokio::time::sleep(duration) => {
+            cancel.cancel();
+            let _ = tokio::time::timeout(TIMEOUT_GRACE, run).await;
+            Err(AppError::Timeout(duration))
+        }
+    }
+}
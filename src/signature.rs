@@ -0,0 +1,93 @@
+use crate::error::{AppError, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Verify the detached OpenPGP `signature` of `payload` with `gpg`, trusting only the
+/// key(s) in `pubkey_path`.
+///
+/// The key is imported into a throwaway keyring, so the user's own keyring is neither
+/// used nor modified.
+pub fn verify(payload: &[u8], signature: &[u8], pubkey_path: &Path) -> Result<()> {
+    let home = TempHome::create()?;
+    let payload_path = home.0.join("payload");
+    let signature_path = home.0.join("payload.sig");
+    fs::write(&payload_path, payload)?;
+    fs::write(&signature_path, signature)?;
+
+    let import = gpg(&home.0)
+        .arg("--import")
+        .arg(pubkey_path)
+        .output()
+        .map_err(|e| invalid(format!("could not run gpg: {e}")))?;
+    if !import.status.success() {
+        return Err(invalid(format!(
+            "could not import {}: {}",
+            pubkey_path.display(),
+            String::from_utf8_lossy(&import.stderr).trim()
+        )));
+    }
+
+    let verify = gpg(&home.0)
+        .args(["--status-fd", "1", "--verify"])
+        .arg(&signature_path)
+        .arg(&payload_path)
+        .output()
+        .map_err(|e| invalid(format!("could not run gpg: {e}")))?;
+    let status = String::from_utf8_lossy(&verify.stdout);
+    let signer = status
+        .lines()
+        .find_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .and_then(|fields| fields.split_whitespace().next());
+
+    match signer {
+        Some(fingerprint) if verify.status.success() => {
+            info!(fingerprint, "Upstream signature verified");
+            Ok(())
+        }
+        _ => Err(invalid(
+            String::from_utf8_lossy(&verify.stderr).trim().to_string(),
+        )),
+    }
+}
+
+fn gpg(home: &Path) -> Command {
+    let mut command = Command::new("gpg");
+    command.arg("--batch").arg("--homedir").arg(home);
+    command
+}
+
+fn invalid(reason: String) -> AppError {
+    AppError::SignatureInvalid { reason }
+}
+
+/// GnuPG home directory removed again when dropped
+struct TempHome(PathBuf);
+
+impl TempHome {
+    fn create() -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path =
+            std::env::temp_dir().join(format!("aur-aacs-keydb-gpg-{}-{nanos}", std::process::id()));
+        fs::create_dir(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempHome {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
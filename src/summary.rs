@@ -0,0 +1,57 @@
+use crate::{app::RunOutcome, error::Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Machine-readable result of one run, written to `OUTPUT_SUMMARY_PATH` for downstream
+/// automation. Fields the run never reached stay `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub package: String,
+    pub finished_at: DateTime<Utc>,
+    /// `None` when the run failed
+    pub outcome: Option<RunOutcome>,
+    pub error: Option<String>,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub archive_url: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl RunSummary {
+    pub fn new(package: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            finished_at: Utc::now(),
+            outcome: None,
+            error: None,
+            old_version: None,
+            new_version: None,
+            archive_url: None,
+            sha256: None,
+        }
+    }
+
+    /// Record the final result of the run and when it finished
+    pub fn finish(&mut self, result: &Result<RunOutcome>) {
+        self.finished_at = Utc::now();
+        match result {
+            Ok(outcome) => self.outcome = Some(outcome.clone()),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Write the summary as pretty JSON, replacing `path` atomically
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1080 @@
+//! Tests for `App` with a fake archiver and an in-memory repository, so no network
+//! access or AUR remote is involved.
+
+mod common;
+
+use async_trait::async_trait;
+use aur_aacs_keydb::{
+    App, AppError, ArchiveResult, Archiver, Config, GitHelper, RunOutcome, UpdateCheck,
+    app::{
+        TRACKING_FILE_NAME, TrackingFile, check_archive_age, check_exit_code, exit_code,
+        render_commit_message,
+    },
+    archive::ArchiveSource,
+    config::ArchiveMode,
+    git::RepoManager,
+    history::History,
+    run_state::RunState,
+    summary::RunSummary,
+};
+use chrono::{TimeZone, Utc};
+use git2::Repository;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+
+const ORIGINAL_URL: &str = "http://example.com/keydb_eng.zip";
+const PACKAGE: &str = "aacs-keydb-daily";
+const VERSION: &str = "20240101000000";
+
+/// An archiver returning a canned result instead of talking to the Wayback Machine
+struct FakeArchiver {
+    version: String,
+    content: Vec<u8>,
+}
+
+impl FakeArchiver {
+    fn new() -> Self {
+        Self::serving(common::keydb_zip())
+    }
+
+    fn serving(content: impl Into<Vec<u8>>) -> Self {
+        Self {
+            version: VERSION.to_string(),
+            content: content.into(),
+        }
+    }
+
+    fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl Archiver for FakeArchiver {
+    async fn archive_and_download(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        Ok(ArchiveResult {
+            original_url: url.to_string(),
+            archive_url: format!("https://web.archive.org/web/{}/{url}", self.version),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            sha256: hex(&Sha256::digest(&self.content)),
+            version: self.version.clone(),
+            source: ArchiveSource::FreshlyArchived,
+            capture_status: None,
+            digests: HashMap::new(),
+            content: self.content.clone(),
+            content_type: Some("application/zip".to_string()),
+        })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Records commit messages in memory instead of pushing
+#[derive(Clone, Default)]
+struct RecordingRepo {
+    commits: Arc<Mutex<Vec<String>>>,
+}
+
+impl RepoManager for RecordingRepo {
+    fn prepare(&self, path: &Path, _: &str, _: bool) -> anyhow::Result<()> {
+        std::fs::create_dir_all(path)?;
+        Ok(())
+    }
+
+    fn commit_and_push(&self, _: &Path, message: &str) -> anyhow::Result<bool> {
+        self.commits.lock().unwrap().push(message.to_string());
+        Ok(true)
+    }
+}
+
+impl RecordingRepo {
+    fn commits(&self) -> Vec<String> {
+        self.commits.lock().unwrap().clone()
+    }
+}
+
+/// A work dir with a recording repository, shared by consecutive runs, and a local bare
+/// repository standing in for the AUR remote
+struct Fixture {
+    dir: TempDir,
+    repo: RecordingRepo,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        Repository::init_bare(dir.path().join("remotes").join(format!("{PACKAGE}.git"))).unwrap();
+        std::fs::write(dir.path().join("id_ed25519"), common::PRIVATE_KEY).unwrap();
+        Self {
+            dir,
+            repo: RecordingRepo::default(),
+        }
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            package_name: PACKAGE.to_string(),
+            original_url: ORIGINAL_URL.to_string(),
+            git_author_name: Some("Test".to_string()),
+            git_author_email: Some("test@example.com".to_string()),
+            source_filename_template: "keydb_eng-${pkgver}.zip".to_string(),
+            work_dir: self.dir.path().display().to_string(),
+            git_https_token: Some("token".to_string()),
+            force: false,
+            dry_run: false,
+            run_namcap: false,
+            check_reachable: false,
+            pushgateway_url: None,
+            min_archive_age: None,
+            max_archive_age: None,
+            save_source_to: None,
+            allow_epoch_bump: false,
+            verify_against_origin: false,
+            ..Config::new()
+        }
+    }
+
+    async fn run(
+        &self,
+        config: Config,
+        archiver: impl Archiver + 'static,
+    ) -> aur_aacs_keydb::Result<RunOutcome> {
+        App::with_backends(config, Box::new(archiver), Box::new(self.repo.clone()))?
+            .run(&CancellationToken::new())
+            .await
+    }
+
+    /// App pushing to the bare remote instead of recording commits
+    fn app(&self, config: Config) -> App {
+        self.app_with(config, FakeArchiver::new())
+    }
+
+    fn app_with(&self, config: Config, archiver: FakeArchiver) -> App {
+        let git_helper = GitHelper::new(
+            String::new(),
+            None,
+            config.git_author_name.clone(),
+            config.git_author_email.clone(),
+        )
+        .with_remote_base(format!(
+            "file://{}",
+            self.dir.path().join("remotes").display()
+        ));
+        App::with_backends(config, Box::new(archiver), Box::new(git_helper)).unwrap()
+    }
+
+    /// Content of `path` in the tree of the remote's master, if it has one
+    fn committed(&self, path: &Path) -> Option<String> {
+        let remote = Repository::open_bare(
+            self.dir
+                .path()
+                .join("remotes")
+                .join(format!("{PACKAGE}.git")),
+        )
+        .unwrap();
+        let tree = remote
+            .find_reference("refs/heads/master")
+            .ok()?
+            .peel_to_tree()
+            .unwrap();
+        let entry = tree.get_path(path).ok()?;
+        let blob = remote.find_blob(entry.id()).unwrap();
+        Some(String::from_utf8(blob.content().to_vec()).unwrap())
+    }
+
+    fn pkgbuild_path(&self) -> std::path::PathBuf {
+        self.config().package_work_dir().join("PKGBUILD")
+    }
+
+    fn pkgbuild(&self) -> String {
+        std::fs::read_to_string(self.pkgbuild_path()).unwrap()
+    }
+
+    fn srcinfo(&self) -> String {
+        std::fs::read_to_string(self.config().package_work_dir().join(".SRCINFO")).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn fresh_archive_defaults_to_archive_and_download() {
+    let result = FakeArchiver::new()
+        .archive_fresh(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    assert_eq!(result.version, VERSION);
+    assert_eq!(
+        result.archive_url,
+        "https://web.archive.org/web/20240101000000/http://example.com/keydb_eng.zip"
+    );
+}
+
+#[tokio::test]
+async fn origin_comparison_is_unsupported_by_default() {
+    let result = FakeArchiver::new()
+        .compare_with_origin(ORIGINAL_URL, &"0".repeat(64))
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn config_is_validated_with_fake_backends() {
+    let fixture = Fixture::new();
+    let config = Config {
+        package_name: String::new(),
+        ..fixture.config()
+    };
+
+    assert!(
+        App::with_backends(
+            config,
+            Box::new(FakeArchiver::new()),
+            Box::new(fixture.repo.clone())
+        )
+        .is_err()
+    );
+}
+
+#[tokio::test]
+async fn update_is_committed_with_default_message() {
+    let fixture = Fixture::new();
+
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.repo.commits(), vec![format!("Update to {VERSION}")]);
+    assert!(fixture.pkgbuild().contains(&format!("pkgver={VERSION}\n")));
+    assert!(
+        fixture
+            .srcinfo()
+            .contains(&format!("\tpkgver = {VERSION}\n"))
+    );
+}
+
+#[tokio::test]
+async fn up_to_date_package_is_not_committed() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 1);
+}
+
+#[tokio::test]
+async fn changed_file_with_same_version_bumps_pkgrel() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let mut archiver = FakeArchiver::new();
+    archiver.content.extend_from_slice(b"trailing");
+
+    fixture.run(fixture.config(), archiver).await.unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 2);
+    assert!(fixture.pkgbuild().contains("pkgrel=2\n"));
+    assert!(fixture.srcinfo().contains("\tpkgrel = 2\n"));
+}
+
+#[tokio::test]
+async fn older_version_is_not_committed() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    let mut archiver = FakeArchiver::new().with_version("20231231000000");
+    archiver.content.extend_from_slice(b"trailing");
+
+    fixture.run(fixture.config(), archiver).await.unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 1);
+    assert!(fixture.pkgbuild().contains(&format!("pkgver={VERSION}\n")));
+}
+
+#[tokio::test]
+async fn older_version_bumps_epoch_when_allowed() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let config = Config {
+        allow_epoch_bump: true,
+        ..fixture.config()
+    };
+    let mut archiver = FakeArchiver::new().with_version("20231231000000");
+    archiver.content.extend_from_slice(b"trailing");
+
+    fixture.run(config, archiver).await.unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 2);
+    assert!(fixture.pkgbuild().contains("epoch=1\n"));
+    assert!(fixture.srcinfo().contains("\tepoch = 1\n"));
+}
+
+#[tokio::test]
+async fn pkgbuild_without_pkgver_is_regenerated() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let pkgbuild = fixture
+        .pkgbuild()
+        .lines()
+        .filter(|line| !line.starts_with("pkgver="))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    std::fs::write(fixture.pkgbuild_path(), pkgbuild).unwrap();
+
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 2);
+    assert!(fixture.pkgbuild().contains(&format!("pkgver={VERSION}\n")));
+}
+
+#[tokio::test]
+async fn skip_checksum_forces_an_update() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let sha256 = hex(&Sha256::digest(common::keydb_zip()));
+    std::fs::write(
+        fixture.pkgbuild_path(),
+        fixture.pkgbuild().replace(&sha256, "SKIP"),
+    )
+    .unwrap();
+
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.repo.commits().len(), 2);
+    assert!(
+        fixture
+            .pkgbuild()
+            .contains(&format!("sha256sums=('{sha256}')"))
+    );
+}
+
+#[tokio::test]
+async fn force_commits_an_up_to_date_package() {
+    let fixture = Fixture::new();
+    fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let config = Config {
+        force: true,
+        ..fixture.config()
+    };
+
+    fixture.run(config, FakeArchiver::new()).await.unwrap();
+
+    assert_eq!(
+        fixture.repo.commits().last().unwrap(),
+        &format!("Update to {VERSION} (forced)")
+    );
+    assert!(fixture.pkgbuild().contains("pkgrel=2\n"));
+    assert!(fixture.srcinfo().contains("\tpkgrel = 2\n"));
+}
+
+#[tokio::test]
+async fn dry_run_leaves_work_dir_untouched() {
+    let fixture = Fixture::new();
+    let config = Config {
+        dry_run: true,
+        ..fixture.config()
+    };
+
+    let outcome = fixture.run(config, FakeArchiver::new()).await.unwrap();
+
+    assert!(
+        matches!(&outcome, RunOutcome::DryRun { changes } if changes.contains("+pkgver=")),
+        "{outcome:?}"
+    );
+    assert!(fixture.repo.commits().is_empty());
+    assert!(!fixture.pkgbuild_path().exists());
+}
+
+/// An archiver whose snapshot never becomes available
+struct StalledArchiver;
+
+#[async_trait]
+impl Archiver for StalledArchiver {
+    async fn archive_and_download(&self, _: &str) -> anyhow::Result<ArchiveResult> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn cancelled_run_does_not_commit() {
+    let fixture = Fixture::new();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let result = App::with_backends(
+        fixture.config(),
+        Box::new(StalledArchiver),
+        Box::new(fixture.repo.clone()),
+    )
+    .unwrap()
+    .run(&cancel)
+    .await;
+
+    assert!(matches!(result, Err(AppError::Cancelled)), "{result:?}");
+    assert!(fixture.repo.commits().is_empty());
+    assert!(!fixture.config().lock_path().exists());
+}
+
+#[test]
+fn commit_message_template_placeholders_are_substituted() {
+    let message = render_commit_message(
+        "upgpkg: {package} {version}\n\nsha256: {sha256}\n",
+        PACKAGE,
+        VERSION,
+        "abc123",
+    )
+    .unwrap();
+
+    assert_eq!(
+        message,
+        format!("upgpkg: aacs-keydb-daily {VERSION}\n\nsha256: abc123")
+    );
+}
+
+#[test]
+fn blank_commit_message_is_rejected() {
+    assert!(render_commit_message("  \n", PACKAGE, VERSION, "abc123").is_err());
+}
+
+#[tokio::test]
+async fn custom_commit_message_template_is_used() {
+    let fixture = Fixture::new();
+    let config = Config {
+        commit_message_template: "upgpkg: {package} {version}".to_string(),
+        ..fixture.config()
+    };
+
+    fixture.run(config, FakeArchiver::new()).await.unwrap();
+
+    assert_eq!(
+        fixture.repo.commits(),
+        vec![format!("upgpkg: {PACKAGE} {VERSION}")]
+    );
+}
+
+#[tokio::test]
+async fn archived_source_is_saved_with_matching_hash() {
+    let fixture = Fixture::new();
+    let save_dir = TempDir::new().unwrap();
+    let config = Config {
+        save_source_to: Some(save_dir.path().join("sources")),
+        ..fixture.config()
+    };
+
+    fixture.run(config, FakeArchiver::new()).await.unwrap();
+
+    let saved = save_dir
+        .path()
+        .join("sources")
+        .join(format!("keydb_eng-{VERSION}.zip"));
+    let content = std::fs::read(&saved).unwrap();
+    assert_eq!(
+        Sha256::digest(&content),
+        Sha256::digest(common::keydb_zip())
+    );
+    assert_eq!(
+        std::fs::read_dir(save_dir.path().join("sources"))
+            .unwrap()
+            .count(),
+        1
+    );
+}
+
+/// [`FakeArchiver`] failing for one unreachable URL
+struct UnreachableArchiver {
+    unreachable: String,
+}
+
+#[async_trait]
+impl Archiver for UnreachableArchiver {
+    async fn archive_and_download(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        if url == self.unreachable {
+            anyhow::bail!("{url} is unreachable");
+        }
+        FakeArchiver::new().archive_and_download(url).await
+    }
+}
+
+#[tokio::test]
+async fn mirror_is_archived_when_original_url_fails() {
+    let fixture = Fixture::new();
+    let mirror = "https://mirror.example.org/keydb_eng.zip";
+    let config = Config {
+        mirror_urls: vec![mirror.to_string()],
+        ..fixture.config()
+    };
+    let archiver = UnreachableArchiver {
+        unreachable: config.original_url.clone(),
+    };
+
+    let outcome = fixture.run(config, archiver).await.unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Updated { .. }), "{outcome:?}");
+    let pkgbuild = fixture.pkgbuild();
+    assert!(
+        pkgbuild.contains(&format!("/web/${{pkgver}}/{mirror}\")")),
+        "{pkgbuild}"
+    );
+    let srcinfo = fixture.srcinfo();
+    assert!(
+        srcinfo.contains(&format!("/web/{VERSION}/{mirror}\n")),
+        "{srcinfo}"
+    );
+}
+
+#[tokio::test]
+async fn extra_files_are_committed() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config
+        .extra_files
+        .push((PathBuf::from("LICENSE"), "Public domain\n".to_string()));
+
+    let outcome = fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Updated { .. }));
+    assert_eq!(
+        fixture.committed(Path::new("LICENSE")).as_deref(),
+        Some("Public domain\n")
+    );
+    let gitignore = fixture.committed(Path::new(".gitignore")).unwrap();
+    for pattern in ["*.zip", "src/", "pkg/"] {
+        assert!(gitignore.lines().any(|line| line == pattern));
+    }
+}
+
+#[tokio::test]
+async fn existing_gitignore_is_kept() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config
+        .extra_files
+        .push((PathBuf::from(".gitignore"), "*.zip\n".to_string()));
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    let mut archiver = FakeArchiver::new().with_version("20240201000000");
+    archiver.content = common::keydb_zip_with(b"; KEYDB 2\n");
+    let outcome = fixture
+        .app_with(fixture.config(), archiver)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Updated { .. }), "{outcome:?}");
+    assert_eq!(
+        fixture.committed(Path::new(".gitignore")).as_deref(),
+        Some("*.zip\n")
+    );
+}
+
+#[tokio::test]
+async fn srcinfo_is_not_written_when_disabled() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.generate_srcinfo = false;
+    let work_path = config.package_work_dir();
+
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(!work_path.join(".SRCINFO").exists());
+    assert!(fixture.committed(Path::new(".SRCINFO")).is_none());
+    assert!(fixture.committed(Path::new("PKGBUILD")).is_some());
+}
+
+fn read_summary(path: &Path) -> RunSummary {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn summary_is_written_after_update() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    let path = fixture.dir.path().join("summary.json");
+    config.output_summary_path = Some(path.clone());
+
+    let outcome = fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    let summary = read_summary(&path);
+    assert_eq!(summary.package, PACKAGE);
+    assert_eq!(summary.outcome, Some(outcome));
+    assert_eq!(summary.error, None);
+    assert_eq!(summary.old_version, None);
+    assert_eq!(summary.new_version.as_deref(), Some(VERSION));
+    assert_eq!(
+        summary.sha256,
+        Some(hex(&Sha256::digest(common::keydb_zip())))
+    );
+    assert!(summary.archive_url.unwrap().contains(VERSION));
+}
+
+#[tokio::test]
+async fn summary_is_written_after_failure() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    let path = fixture.dir.path().join("{package}.json");
+    config.output_summary_path = Some(path);
+
+    let error = fixture
+        .app_with(config, FakeArchiver::serving("<html>not a zip</html>"))
+        .run(&CancellationToken::new())
+        .await
+        .unwrap_err();
+
+    let summary = read_summary(&fixture.dir.path().join(format!("{PACKAGE}.json")));
+    assert_eq!(summary.outcome, None);
+    assert_eq!(summary.error, Some(error.to_string()));
+    assert_eq!(summary.new_version, None);
+}
+
+#[tokio::test]
+async fn check_reports_available_update_without_pushing() {
+    let fixture = Fixture::new();
+    let app = fixture.app(fixture.config());
+
+    let check = app.check(&CancellationToken::new()).await.unwrap();
+
+    assert_eq!(
+        check,
+        UpdateCheck {
+            update_available: true,
+            current_version: None,
+            candidate_version: VERSION.to_string(),
+        }
+    );
+    assert_eq!(
+        check.to_string(),
+        format!("update available: none -> {VERSION}")
+    );
+    assert_eq!(check_exit_code(&[check]), 0);
+    assert_eq!(fixture.committed(Path::new("PKGBUILD")), None);
+}
+
+#[tokio::test]
+async fn check_reports_up_to_date_after_update() {
+    let fixture = Fixture::new();
+    let app = fixture.app(fixture.config());
+    app.run(&CancellationToken::new()).await.unwrap();
+
+    let check = app.check(&CancellationToken::new()).await.unwrap();
+
+    assert!(!check.update_available);
+    assert_eq!(check.current_version.as_deref(), Some(VERSION));
+    assert_eq!(check.to_string(), format!("up to date: {VERSION}"));
+    assert_eq!(check_exit_code(&[check]), 2);
+}
+
+/// A work dir under the managed temp root, where cleanup is allowed
+fn managed_work_dir() -> TempDir {
+    let root = aur_aacs_keydb::config::managed_work_dir();
+    std::fs::create_dir_all(&root).unwrap();
+    TempDir::new_in(root).unwrap()
+}
+
+#[tokio::test]
+async fn work_dir_is_removed_after_successful_run() {
+    let fixture = Fixture::new();
+    let work_dir = managed_work_dir();
+    let mut config = fixture.config();
+    config.work_dir = work_dir.path().display().to_string();
+    config.cleanup_work_dir = true;
+    let package_dir = config.package_work_dir();
+
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(!package_dir.exists());
+}
+
+#[tokio::test]
+async fn work_dir_is_kept_after_failed_run() {
+    let fixture = Fixture::new();
+    let work_dir = managed_work_dir();
+    let mut config = fixture.config();
+    config.work_dir = work_dir.path().display().to_string();
+    config.cleanup_work_dir = true;
+    // Left behind by an earlier run; the archive is rejected before the repo is prepared
+    let package_dir = config.package_work_dir();
+    std::fs::create_dir_all(&package_dir).unwrap();
+
+    let result = fixture
+        .app_with(config, FakeArchiver::serving("not a zip"))
+        .run(&CancellationToken::new())
+        .await;
+
+    assert!(result.is_err());
+    assert!(package_dir.exists());
+}
+
+#[tokio::test]
+async fn unmanaged_work_dir_is_never_removed() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.cleanup_work_dir = true;
+    let package_dir = config.package_work_dir();
+
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(package_dir.exists());
+}
+
+/// Fails the test on any git operation
+struct NoGit;
+
+impl RepoManager for NoGit {
+    fn prepare(&self, _: &Path, _: &str, _: bool) -> anyhow::Result<()> {
+        panic!("prepare called with --no-git");
+    }
+
+    fn commit_and_push(&self, _: &Path, _: &str) -> anyhow::Result<bool> {
+        panic!("commit_and_push called with --no-git");
+    }
+}
+
+#[tokio::test]
+async fn no_git_writes_package_without_git_operations() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.no_git = true;
+    // No SSH key is needed without git
+    config.ssh_key_path = fixture.dir.path().join("missing").display().to_string();
+    let package_dir = config.package_work_dir();
+
+    let outcome = App::with_backends(config, Box::new(FakeArchiver::new()), Box::new(NoGit))
+        .unwrap()
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Skipped { reason } if reason == "no git"));
+    let pkgbuild = std::fs::read_to_string(package_dir.join("PKGBUILD")).unwrap();
+    assert!(pkgbuild.contains(&format!("pkgver={VERSION}")));
+    assert!(fixture.committed(Path::new("PKGBUILD")).is_none());
+}
+
+#[tokio::test]
+async fn tracking_file_is_committed() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.write_tracking_file = true;
+
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    let committed = fixture
+        .committed(Path::new(TRACKING_FILE_NAME))
+        .expect("tracking file should be committed");
+    let tracking: TrackingFile = serde_json::from_str(&committed).unwrap();
+    assert_eq!(
+        tracking,
+        TrackingFile {
+            original_url: "http://example.com/keydb_eng.zip".to_string(),
+            archive_url: format!(
+                "https://web.archive.org/web/{VERSION}/http://example.com/keydb_eng.zip"
+            ),
+            version: VERSION.to_string(),
+            sha256: hex(&Sha256::digest(common::keydb_zip())),
+        }
+    );
+}
+
+#[tokio::test]
+async fn tracking_file_is_off_by_default() {
+    let fixture = Fixture::new();
+
+    fixture
+        .app(fixture.config())
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(fixture.committed(Path::new(TRACKING_FILE_NAME)).is_none());
+}
+
+#[tokio::test]
+async fn recent_successful_run_skips_until_min_interval_passed() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.min_interval = Some(std::time::Duration::from_secs(3600));
+    let state_path = config.run_state_path();
+
+    RunState {
+        last_success: Some(Utc::now() - chrono::Duration::minutes(10)),
+        ..RunState::default()
+    }
+    .save(&state_path)
+    .unwrap();
+    let outcome = fixture
+        .app(config.clone())
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Skipped { .. }), "{outcome:?}");
+    assert_eq!(fixture.committed(Path::new("PKGBUILD")), None);
+
+    RunState {
+        last_success: Some(Utc::now() - chrono::Duration::hours(2)),
+        ..RunState::default()
+    }
+    .save(&state_path)
+    .unwrap();
+    let outcome = fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Updated { .. }), "{outcome:?}");
+    let state = RunState::load(&state_path).unwrap();
+    assert!(Utc::now() - state.last_success.unwrap() < chrono::Duration::minutes(1));
+}
+
+#[tokio::test]
+async fn skipped_run_does_not_count_as_success() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.max_archive_age = Some(Duration::from_secs(60));
+    let state_path = config.run_state_path();
+
+    let outcome = fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, RunOutcome::Skipped { .. }), "{outcome:?}");
+    assert_eq!(
+        RunState::load(&state_path).and_then(|state| state.last_success),
+        None
+    );
+}
+
+#[tokio::test]
+async fn history_entry_records_phase_timings() {
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    let history_path = fixture.dir.path().join("history.jsonl");
+    config.history_path = Some(history_path.display().to_string());
+
+    fixture
+        .app(config)
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    let entries = History::new(&history_path, 10).load_recent(1).unwrap();
+    let timings = entries[0].timings;
+    assert!(!timings.archive.is_zero(), "{timings:?}");
+    assert!(!timings.clone.is_zero(), "{timings:?}");
+    assert!(!timings.diff.is_zero(), "{timings:?}");
+    assert!(!timings.push.is_zero(), "{timings:?}");
+}
+
+/// [`FakeArchiver`] recording which archive strategy `App` asked for
+#[derive(Clone, Default)]
+struct StrategyArchiver {
+    calls: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl StrategyArchiver {
+    async fn record(&self, strategy: &'static str, url: &str) -> anyhow::Result<ArchiveResult> {
+        self.calls.lock().unwrap().push(strategy);
+        FakeArchiver::new().archive_and_download(url).await
+    }
+}
+
+#[async_trait]
+impl Archiver for StrategyArchiver {
+    async fn archive_and_download(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        self.record("create-then-fallback", url).await
+    }
+
+    async fn archive_fresh(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        self.record("create-only", url).await
+    }
+
+    async fn archive_existing(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        self.record("existing-only", url).await
+    }
+}
+
+#[tokio::test]
+async fn each_archive_mode_selects_its_strategy() {
+    for (mode, strategy) in [
+        (ArchiveMode::CreateThenFallback, "create-then-fallback"),
+        (ArchiveMode::CreateOnly, "create-only"),
+        (ArchiveMode::ExistingOnly, "existing-only"),
+    ] {
+        let fixture = Fixture::new();
+        let config = Config {
+            archive_mode: mode,
+            ..fixture.config()
+        };
+        let archiver = StrategyArchiver::default();
+
+        App::with_backends(
+            config,
+            Box::new(archiver.clone()),
+            Box::new(fixture.repo.clone()),
+        )
+        .unwrap()
+        .run(&CancellationToken::new())
+        .await
+        .unwrap();
+
+        assert_eq!(*archiver.calls.lock().unwrap(), vec![strategy], "{mode:?}");
+    }
+}
+
+#[test]
+fn archive_age_window_is_enforced() {
+    let now = Utc::now();
+    let day = Duration::from_secs(24 * 60 * 60);
+
+    assert!(check_archive_age(now - chrono::Duration::days(10), now, None, Some(day * 7)).is_err());
+    assert!(check_archive_age(now - chrono::Duration::hours(1), now, None, Some(day * 7)).is_ok());
+    assert!(check_archive_age(now - chrono::Duration::hours(1), now, Some(day), None).is_err());
+    assert!(
+        check_archive_age(
+            now - chrono::Duration::days(2),
+            now,
+            Some(day),
+            Some(day * 7)
+        )
+        .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn archive_outside_age_window_is_not_committed() {
+    let fixture = Fixture::new();
+    let config = Config {
+        max_archive_age: Some(Duration::from_secs(60 * 60)),
+        ..fixture.config()
+    };
+
+    let outcome = fixture.run(config, FakeArchiver::new()).await.unwrap();
+
+    assert!(
+        matches!(&outcome, RunOutcome::Skipped { reason } if reason.contains("older than the maximum")),
+        "{outcome:?}"
+    );
+    assert!(fixture.repo.commits().is_empty());
+    assert!(!fixture.pkgbuild_path().exists());
+}
+
+#[tokio::test]
+async fn run_outcomes_report_the_versions() {
+    let fixture = Fixture::new();
+
+    let created = fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let up_to_date = fixture
+        .run(fixture.config(), FakeArchiver::new())
+        .await
+        .unwrap();
+    let mut archiver = FakeArchiver::new().with_version("20240201000000");
+    archiver.content = common::keydb_zip_with(b"; KEYDB 2\n");
+    let updated = fixture.run(fixture.config(), archiver).await.unwrap();
+
+    assert_eq!(
+        created,
+        RunOutcome::Updated {
+            old: None,
+            new: VERSION.to_string()
+        }
+    );
+    assert_eq!(up_to_date, RunOutcome::UpToDate);
+    assert_eq!(
+        updated,
+        RunOutcome::Updated {
+            old: Some(VERSION.to_string()),
+            new: "20240201000000".to_string()
+        }
+    );
+}
+
+#[test]
+fn exit_code_distinguishes_updates_from_noops() {
+    let updated = RunOutcome::Updated {
+        old: None,
+        new: VERSION.to_string(),
+    };
+    let skipped = RunOutcome::Skipped {
+        reason: "dry run".to_string(),
+    };
+
+    assert_eq!(exit_code(&[RunOutcome::UpToDate, updated], 100), 0);
+    assert_eq!(exit_code(&[RunOutcome::UpToDate, skipped], 100), 100);
+    assert_eq!(exit_code(&[RunOutcome::UpToDate], 0), 0);
+    assert_eq!(exit_code(&[], 100), 100);
+}
@@ -0,0 +1,1274 @@
+mod common;
+
+use async_trait::async_trait;
+use aur_aacs_keydb::{
+    AppError, ArchiveError, ArchiveProgress, ArchiveResult, Archiver, Config, MultiArchiver,
+    WebArchiveClient,
+    archive::{
+        ArchiveResponse, ArchiveSnapshot, ArchiveSource, ArchiveStatus, CdxEntry, Download,
+        normalize_url, parse_cdx, parse_job_status, parse_retry_after, parse_wayback_timestamp,
+        validate_source, validate_zip, verify_download,
+    },
+    checksum::{ChecksumKind, MultiHasher},
+    config::{ArchiveMode, SourceFormat},
+    download_cache::{DownloadCache, wayback_digest},
+    rate_limit::RateLimiter,
+    run_state::RunState,
+};
+use chrono::{TimeZone, Utc};
+use reqwest::header::AUTHORIZATION;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+const ORIGINAL_URL: &str = "http://example.com/keydb_eng.zip";
+const SNAPSHOT: &str = "20240101000000";
+
+#[test]
+fn html_is_rejected_by_validate_zip() {
+    let html = b"<!DOCTYPE html>\n<html><body>Not found</body></html>";
+
+    let error = validate_zip(html, Some("text/html")).unwrap_err();
+
+    assert!(matches!(
+        error,
+        AppError::InvalidArchive { ref content_type, ref head, .. }
+            if content_type == "text/html" && head.starts_with("3c 21 44 4f")
+    ));
+    assert!(validate_zip(&common::keydb_zip(), Some("application/zip")).is_ok());
+}
+
+#[test]
+fn truncated_zip_is_rejected_by_validate_zip() {
+    let zip = common::keydb_zip();
+
+    assert!(validate_zip(&zip[..zip.len() / 2], Some("application/zip")).is_err());
+}
+
+#[test]
+fn wayback_timestamp_parses_to_utc() {
+    assert_eq!(
+        parse_wayback_timestamp("20240102030405").unwrap(),
+        Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()
+    );
+}
+
+#[test]
+fn malformed_wayback_timestamps_are_rejected() {
+    for timestamp in [
+        "",
+        "2024010203040",
+        "202401020304056",
+        "2024-01-02T03:04",
+        "20241302030405",
+    ] {
+        assert!(parse_wayback_timestamp(timestamp).is_err(), "{timestamp}");
+    }
+}
+
+fn download(content_type: &str, content_length: Option<u64>) -> Download {
+    let bytes = common::keydb_zip();
+    Download {
+        size: bytes.len() as u64,
+        bytes,
+        sha256: String::new(),
+        digests: HashMap::new(),
+        content_type: Some(content_type.to_string()),
+        content_length,
+    }
+}
+
+#[test]
+fn complete_zip_download_is_verified() {
+    let download = download("application/zip", Some(common::keydb_zip().len() as u64));
+
+    verify_download(&download, SourceFormat::Zip).unwrap();
+}
+
+#[test]
+fn content_length_mismatch_is_a_truncated_download() {
+    let size = common::keydb_zip().len() as u64;
+    let download = download("application/zip", Some(size + 100));
+
+    assert!(matches!(
+        verify_download(&download, SourceFormat::Zip),
+        Err(AppError::TruncatedDownload { expected, received })
+            if expected == size + 100 && received == size
+    ));
+}
+
+#[test]
+fn unexpected_content_type_is_rejected() {
+    let download = download("text/plain; charset=utf-8", None);
+
+    assert!(matches!(
+        verify_download(&download, SourceFormat::Zip),
+        Err(AppError::UnexpectedContentType { content_type })
+            if content_type == "text/plain; charset=utf-8"
+    ));
+}
+
+#[test]
+fn html_page_error_names_the_expected_format() {
+    let page = b"<!DOCTYPE html><html><body>Maintenance</body></html>".to_vec();
+    let download = Download {
+        size: page.len() as u64,
+        bytes: page,
+        content_type: None,
+        content_length: None,
+        ..download("application/gzip", None)
+    };
+
+    let error = verify_download(&download, SourceFormat::TarGz).unwrap_err();
+
+    assert!(matches!(
+        error,
+        AppError::HtmlPage {
+            expected: SourceFormat::TarGz,
+            ..
+        }
+    ));
+    assert!(
+        error
+            .to_string()
+            .contains("not the expected gzip-compressed tar archive"),
+        "{error}"
+    );
+}
+
+#[test]
+fn retry_after_seconds_are_parsed() {
+    assert_eq!(
+        parse_retry_after(" 120 ", Utc::now()),
+        Some(Duration::from_secs(120))
+    );
+}
+
+#[test]
+fn retry_after_http_date_is_relative_to_now() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        parse_retry_after("Mon, 01 Jan 2024 12:01:30 GMT", now),
+        Some(Duration::from_secs(90))
+    );
+    assert_eq!(
+        parse_retry_after("Mon, 01 Jan 2024 11:00:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+}
+
+#[test]
+fn malformed_retry_after_is_ignored() {
+    assert_eq!(parse_retry_after("soon", Utc::now()), None);
+    assert_eq!(parse_retry_after("-5", Utc::now()), None);
+}
+
+#[test]
+fn bare_wayback_host_uses_https() {
+    let client = WebArchiveClient::new().with_host("wayback.example.org/");
+
+    assert_eq!(
+        client.save_url(ORIGINAL_URL),
+        format!("https://wayback.example.org/save/{ORIGINAL_URL}")
+    );
+    assert_eq!(
+        client.snapshot_url(Some(SNAPSHOT), ORIGINAL_URL),
+        format!("https://wayback.example.org/web/{SNAPSHOT}/{ORIGINAL_URL}")
+    );
+    assert_eq!(
+        client.availability_url(ORIGINAL_URL).unwrap().as_str(),
+        "https://wayback.example.org/wayback/available?url=http%3A%2F%2Fexample.com%2Fkeydb_eng.zip"
+    );
+}
+
+#[test]
+fn wayback_host_with_scheme_and_port_is_used_verbatim() {
+    let client = WebArchiveClient::new().with_host("http://127.0.0.1:8080");
+
+    assert_eq!(
+        client.save_url(ORIGINAL_URL),
+        format!("http://127.0.0.1:8080/save/{ORIGINAL_URL}")
+    );
+    assert_eq!(
+        client.snapshot_url(None, ORIGINAL_URL),
+        format!("http://127.0.0.1:8080/web/{ORIGINAL_URL}")
+    );
+}
+
+/// A Wayback host whose availability API reports the snapshot, counting its lookups
+fn serve_available(lookups: Arc<AtomicUsize>) -> impl Fn(&common::Request) -> common::Response {
+    move |request| {
+        if request.path.starts_with("/wayback/available") {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            common::Response::new(200).body(format!(
+                r#"{{"archived_snapshots":{{"closest":{{"available":true,"status":"200","url":"http://127.0.0.1:9/web/{SNAPSHOT}/{ORIGINAL_URL}","timestamp":"{SNAPSHOT}"}}}}}}"#
+            ))
+        } else {
+            common::Response::new(404)
+        }
+    }
+}
+
+#[tokio::test]
+async fn repeated_lookup_is_served_from_cache() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start(serve_available(lookups.clone())).await;
+    let client = WebArchiveClient::new().with_host(&server.base_url);
+
+    let first = client.check_archived(ORIGINAL_URL).await.unwrap().unwrap();
+    let second = client.check_archived(ORIGINAL_URL).await.unwrap().unwrap();
+
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    assert_eq!(second.timestamp, first.timestamp);
+
+    client.invalidate_snapshot(ORIGINAL_URL);
+    client.check_archived(ORIGINAL_URL).await.unwrap();
+    assert_eq!(lookups.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn existing_snapshot_lookup_reuses_cached_availability() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start(serve_available(lookups.clone())).await;
+    let client = WebArchiveClient::new().with_host(&server.base_url);
+
+    client.check_archived(ORIGINAL_URL).await.unwrap();
+    // The download fails, the lookup before it must not hit the API again
+    assert!(client.archive_existing(ORIGINAL_URL).await.is_err());
+
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn old_closest_snapshot_is_rejected() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start(serve_available(lookups)).await;
+    let client = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_max_snapshot_age(Some(Duration::from_secs(30 * 86400)));
+
+    assert!(client.check_archived(ORIGINAL_URL).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn existing_only_never_saves_when_snapshot_is_too_old() {
+    let saves = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let saves = saves.clone();
+        let serve = serve_available(Arc::new(AtomicUsize::new(0)));
+        move |request| {
+            if request.path.starts_with("/save/") {
+                saves.fetch_add(1, Ordering::SeqCst);
+            }
+            serve(request)
+        }
+    })
+    .await;
+    let client = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_max_snapshot_age(Some(Duration::from_secs(30 * 86400)));
+
+    let result = client
+        .archive_with_mode(ORIGINAL_URL, ArchiveMode::ExistingOnly)
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(saves.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn url_is_normalized_before_archiving() {
+    assert_eq!(
+        normalize_url(" HTTP://Example.COM/keydb eng.zip?lang=en us#top ").unwrap(),
+        "http://example.com/keydb%20eng.zip?lang=en%20us"
+    );
+}
+
+#[test]
+fn normalized_url_is_stable() {
+    let normalized = normalize_url("http://example.com/keydb eng.zip").unwrap();
+
+    assert_eq!(normalize_url(&normalized).unwrap(), normalized);
+}
+
+#[test]
+fn relative_and_non_http_urls_are_rejected() {
+    assert!(normalize_url("keydb_eng.zip").is_err());
+    assert!(normalize_url("ftp://example.com/keydb_eng.zip").is_err());
+}
+
+#[tokio::test]
+async fn multi_chunk_body_is_hashed_while_streaming() {
+    let body: Vec<u8> = (0..3 * 1024 * 1024).map(|i: u32| (i % 251) as u8).collect();
+    let expected = Sha256::digest(&body)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let server = common::MockServer::start({
+        let body = body.clone();
+        move |_| {
+            common::Response::new(200)
+                .header("Content-Type", "application/zip")
+                .body(body.clone())
+        }
+    })
+    .await;
+
+    let download = WebArchiveClient::new()
+        .hash_url(&format!("{}/keydb_eng.zip", server.base_url))
+        .await
+        .unwrap();
+
+    assert_eq!(download.sha256, expected);
+    assert_eq!(download.size, body.len() as u64);
+    assert_eq!(download.content_length, Some(body.len() as u64));
+    assert!(download.bytes.is_empty());
+}
+
+fn client_with_origin_headers() -> WebArchiveClient {
+    let mut config = Config::new();
+    config.wayback_host = "web.archive.org".to_string();
+    config.origin_headers.insert(
+        "Authorization".to_string(),
+        "Basic dXNlcjpwYXNz".to_string(),
+    );
+    WebArchiveClient::from_config(&config).unwrap()
+}
+
+#[test]
+fn origin_headers_are_sent_to_origin() {
+    let request = client_with_origin_headers()
+        .get(ORIGINAL_URL)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        request.headers().get(AUTHORIZATION).unwrap(),
+        "Basic dXNlcjpwYXNz"
+    );
+}
+
+#[test]
+fn origin_headers_are_not_sent_to_wayback() {
+    let request = client_with_origin_headers()
+        .get(&format!(
+            "https://web.archive.org/web/{SNAPSHOT}/{ORIGINAL_URL}"
+        ))
+        .build()
+        .unwrap();
+
+    assert!(request.headers().get(AUTHORIZATION).is_none());
+}
+
+#[test]
+fn origin_headers_are_redacted() {
+    let mut config = Config::new();
+    config
+        .origin_headers
+        .insert("Cookie".to_string(), "session=secret".to_string());
+
+    let logged = format!("{:?}", config.origin_header_map().unwrap());
+
+    assert!(!logged.contains("secret"));
+}
+
+/// Succeeds with its own name as the archive URL after `delay`
+struct DelayedArchiver {
+    name: &'static str,
+    delay: Duration,
+}
+
+#[async_trait]
+impl Archiver for DelayedArchiver {
+    async fn archive_and_download(&self, url: &str) -> anyhow::Result<ArchiveResult> {
+        tokio::time::sleep(self.delay).await;
+        Ok(ArchiveResult {
+            original_url: url.to_string(),
+            archive_url: self.name.to_string(),
+            timestamp: Utc::now(),
+            sha256: String::new(),
+            version: String::new(),
+            source: ArchiveSource::FreshlyArchived,
+            capture_status: None,
+            digests: HashMap::new(),
+            content: Vec::new(),
+            content_type: None,
+        })
+    }
+}
+
+struct FailingArchiver;
+
+#[async_trait]
+impl Archiver for FailingArchiver {
+    async fn archive_and_download(&self, _url: &str) -> anyhow::Result<ArchiveResult> {
+        anyhow::bail!("unavailable")
+    }
+}
+
+fn provider(archiver: impl Archiver + 'static) -> (String, Arc<dyn Archiver>) {
+    (String::from("provider"), Arc::new(archiver))
+}
+
+#[tokio::test]
+async fn multi_archiver_uses_fastest_provider() {
+    let archiver = MultiArchiver::new(vec![
+        provider(DelayedArchiver {
+            name: "slow",
+            delay: Duration::from_secs(5),
+        }),
+        provider(DelayedArchiver {
+            name: "fast",
+            delay: Duration::from_millis(10),
+        }),
+    ]);
+
+    let result = archiver.archive_and_download(ORIGINAL_URL).await.unwrap();
+
+    assert_eq!(result.archive_url, "fast");
+}
+
+#[tokio::test]
+async fn multi_archiver_skips_failed_provider() {
+    let archiver = MultiArchiver::new(vec![
+        provider(FailingArchiver),
+        provider(DelayedArchiver {
+            name: "slow",
+            delay: Duration::from_millis(50),
+        }),
+    ]);
+
+    let result = archiver.archive_and_download(ORIGINAL_URL).await.unwrap();
+
+    assert_eq!(result.archive_url, "slow");
+}
+
+#[tokio::test]
+async fn multi_archiver_fails_when_every_provider_fails() {
+    let archiver = MultiArchiver::new(vec![provider(FailingArchiver), provider(FailingArchiver)]);
+
+    assert!(archiver.archive_and_download(ORIGINAL_URL).await.is_err());
+}
+
+/// The snapshot of [`ORIGINAL_URL`] and the redirect to it from the latest snapshot page
+fn serve_snapshot(request: &common::Request) -> Option<common::Response> {
+    let snapshot_path = format!("/web/{SNAPSHOT}/{ORIGINAL_URL}");
+    if request.path == snapshot_path {
+        return Some(match request.method.as_str() {
+            "HEAD" => common::Response::new(200),
+            _ => common::Response::new(200)
+                .header("Content-Type", "application/zip")
+                .body(common::keydb_zip()),
+        });
+    }
+    if request.path == format!("/web/{ORIGINAL_URL}") {
+        return Some(common::Response::new(302).header("Location", snapshot_path));
+    }
+    None
+}
+
+/// A Wayback host whose save jobs succeed right away
+fn serve_fresh(request: &common::Request) -> common::Response {
+    if request.path.starts_with("/save/status/") {
+        common::Response::new(200).body(format!(
+            r#"{{"status":"success","timestamp":"{SNAPSHOT}","original_url":"{ORIGINAL_URL}"}}"#
+        ))
+    } else if request.path.starts_with("/save/") {
+        common::Response::new(200).body(r#"{"job_id":"job"}"#)
+    } else {
+        serve_snapshot(request).unwrap_or_else(|| common::Response::new(404))
+    }
+}
+
+#[tokio::test]
+async fn archive_and_download_marks_fresh_snapshot() {
+    let server = common::MockServer::start(serve_fresh).await;
+
+    let result = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    assert_eq!(result.source, ArchiveSource::FreshlyArchived);
+    assert_eq!(result.version, SNAPSHOT);
+}
+
+#[tokio::test]
+async fn archive_progress_reports_each_stage() {
+    let server = common::MockServer::start(serve_fresh).await;
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_progress(sender)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    let mut events = Vec::new();
+    while let Ok(event) = receiver.try_recv() {
+        events.push(event);
+    }
+    let size = common::keydb_zip().len() as u64;
+    assert_eq!(events[0], ArchiveProgress::SubmittingSave);
+    assert_eq!(
+        events[1],
+        ArchiveProgress::WaitingForSnapshot { attempt: 1 }
+    );
+    assert!(
+        events[2..events.len() - 1]
+            .iter()
+            .all(|event| matches!(event, ArchiveProgress::Downloading { total: Some(total), .. } if *total == size))
+    );
+    assert_eq!(
+        events[events.len() - 2],
+        ArchiveProgress::Downloading {
+            bytes: size,
+            total: Some(size)
+        }
+    );
+    assert_eq!(events.last(), Some(&ArchiveProgress::Done));
+}
+
+#[tokio::test]
+async fn archive_and_download_marks_existing_fallback() {
+    let server = common::MockServer::start(|request| {
+        if request.path.starts_with("/save/") {
+            common::Response::new(503)
+        } else {
+            serve_snapshot(request).unwrap_or_else(|| common::Response::new(404))
+        }
+    })
+    .await;
+
+    let result = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    assert_eq!(result.source, ArchiveSource::ExistingFallback);
+    assert_eq!(result.version, SNAPSHOT);
+}
+
+#[tokio::test]
+async fn pinned_timestamp_downloads_that_snapshot() {
+    const PINNED: &str = "20230615120000";
+    let server = common::MockServer::start(|request| {
+        if request.path == format!("/web/{PINNED}id_/{ORIGINAL_URL}") {
+            common::Response::new(200)
+                .header("Content-Type", "application/zip")
+                .body(common::keydb_zip())
+        } else {
+            common::Response::new(404)
+        }
+    })
+    .await;
+    let client = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_snapshot_timestamp(Some(PINNED.to_string()));
+
+    let result = client.archive_and_download(ORIGINAL_URL).await.unwrap();
+
+    let expected_url = format!("{}/web/{PINNED}id_/{ORIGINAL_URL}", server.base_url);
+    assert_eq!(client.raw_snapshot_url(PINNED, ORIGINAL_URL), expected_url);
+    assert_eq!(result.archive_url, expected_url);
+    assert_eq!(result.version, PINNED);
+    assert_eq!(result.timestamp, parse_wayback_timestamp(PINNED).unwrap());
+    assert_eq!(result.source, ArchiveSource::Pinned);
+}
+
+#[tokio::test]
+async fn malformed_pinned_timestamp_is_rejected() {
+    let result = WebArchiveClient::new()
+        .with_host("http://127.0.0.1:9")
+        .with_snapshot_timestamp(Some("2023-06-15".to_string()))
+        .archive_and_download(ORIGINAL_URL)
+        .await;
+
+    assert!(result.is_err());
+}
+
+fn snapshot(available: bool, timestamp: &str) -> ArchiveSnapshot {
+    ArchiveSnapshot {
+        available,
+        url: format!("https://web.archive.org/web/{timestamp}/{ORIGINAL_URL}"),
+        timestamp: timestamp.to_string(),
+        status: Some(200),
+    }
+}
+
+#[test]
+fn available_snapshot_is_usable() {
+    assert!(snapshot(true, SNAPSHOT).is_usable());
+}
+
+#[test]
+fn unavailable_snapshot_is_unusable() {
+    assert!(!snapshot(false, SNAPSHOT).is_usable());
+}
+
+#[test]
+fn snapshot_with_malformed_timestamp_is_unusable() {
+    assert!(!snapshot(true, "not-a-timestamp").is_usable());
+}
+
+#[tokio::test]
+async fn not_modified_download_reuses_cached_hash() {
+    let served = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let served = served.clone();
+        move |request| {
+            if request.header("If-None-Match") == Some("\"v1\"") {
+                common::Response::new(304).header("ETag", "\"v1\"")
+            } else {
+                served.fetch_add(1, Ordering::SeqCst);
+                common::Response::new(200)
+                    .header("Content-Type", "application/zip")
+                    .header("ETag", "\"v1\"")
+                    .body(common::keydb_zip())
+            }
+        }
+    })
+    .await;
+    let cache_dir = tempfile::tempdir().unwrap();
+    let state_path = cache_dir.path().join("state.json");
+    let client = WebArchiveClient::new()
+        .with_run_state(Some(state_path.clone()))
+        .with_download_cache(Some(DownloadCache::new(cache_dir.path())));
+    let url = format!("{}/keydb_eng.zip", server.base_url);
+
+    let first = client.download_and_hash(&url).await.unwrap();
+    let second = client.download_and_hash(&url).await.unwrap();
+    let hashed = client.hash_url(&url).await.unwrap();
+
+    assert_eq!(served.load(Ordering::SeqCst), 1);
+    assert_eq!(second.sha256, first.sha256);
+    assert_eq!(second.bytes, common::keydb_zip());
+    assert_eq!(hashed.sha256, first.sha256);
+    let state = RunState::load(&state_path).unwrap();
+    assert_eq!(state.downloads[&url].etag.as_deref(), Some("\"v1\""));
+    assert_eq!(state.downloads[&url].sha256, first.sha256);
+}
+
+#[tokio::test]
+async fn validators_without_download_cache_only_skip_hashing_downloads() {
+    let served = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let served = served.clone();
+        move |request| {
+            if request.header("If-None-Match") == Some("\"v1\"") {
+                common::Response::new(304).header("ETag", "\"v1\"")
+            } else {
+                served.fetch_add(1, Ordering::SeqCst);
+                common::Response::new(200)
+                    .header("Content-Type", "application/zip")
+                    .header("ETag", "\"v1\"")
+                    .body(common::keydb_zip())
+            }
+        }
+    })
+    .await;
+    let state_dir = tempfile::tempdir().unwrap();
+    let client = WebArchiveClient::new().with_run_state(Some(state_dir.path().join("state.json")));
+    let url = format!("{}/keydb_eng.zip", server.base_url);
+
+    let first = client.download_and_hash(&url).await.unwrap();
+    let hashed = client.hash_url(&url).await.unwrap();
+    let second = client.download_and_hash(&url).await.unwrap();
+
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+    assert_eq!(hashed.sha256, first.sha256);
+    assert_eq!(second.bytes, common::keydb_zip());
+    assert!(!state_dir.path().join("download-cache").exists());
+}
+
+#[tokio::test]
+async fn html_page_is_rejected() {
+    let server = common::MockServer::start(|request| {
+        let snapshot_path = format!("/web/{SNAPSHOT}/{ORIGINAL_URL}");
+        if request.path == snapshot_path {
+            common::Response::new(200)
+                .header("Content-Type", "application/octet-stream")
+                .body("\n<!DOCTYPE html>\n<html><body>Maintenance</body></html>")
+        } else if request.path == format!("/web/{ORIGINAL_URL}") {
+            common::Response::new(302).header("Location", snapshot_path)
+        } else {
+            common::Response::new(503)
+        }
+    })
+    .await;
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    let error = AppError::from_archive(error);
+    assert!(matches!(
+        &error,
+        AppError::HtmlPage { expected: SourceFormat::Zip, head } if head.starts_with("<!DOCTYPE html>")
+    ));
+    assert!(
+        error.to_string().contains("not the expected ZIP archive"),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn download_is_retried_after_reset() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let attempts = attempts.clone();
+        move |_| {
+            let response = common::Response::new(200)
+                .header("Content-Type", "application/zip")
+                .body(common::keydb_zip());
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => response.cut_after(10),
+                _ => response,
+            }
+        }
+    })
+    .await;
+
+    let download = WebArchiveClient::new()
+        .with_download_retries(1)
+        .download_and_hash(&format!("{}/keydb_eng.zip", server.base_url))
+        .await
+        .unwrap();
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    assert_eq!(download.bytes, common::keydb_zip());
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let attempts = attempts.clone();
+        move |_| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            common::Response::new(404)
+        }
+    })
+    .await;
+
+    let result = WebArchiveClient::new()
+        .with_download_retries(3)
+        .download_and_hash(&format!("{}/keydb_eng.zip", server.base_url))
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn snapshot_with_server_error_capture_is_unusable() {
+    let snapshot = ArchiveSnapshot {
+        status: Some(503),
+        ..snapshot(true, SNAPSHOT)
+    };
+
+    assert!(snapshot.is_suspect());
+    assert!(!snapshot.is_usable());
+}
+
+#[test]
+fn availability_response_includes_capture_status() {
+    let response: ArchiveResponse = serde_json::from_str(&format!(
+        r#"{{"url":"{ORIGINAL_URL}","archived_snapshots":{{"closest":{{"status":"200","available":true,"url":"http://web.archive.org/web/{SNAPSHOT}/{ORIGINAL_URL}","timestamp":"{SNAPSHOT}"}}}}}}"#
+    ))
+    .unwrap();
+
+    assert_eq!(response.archived_snapshots["closest"].status, Some(200));
+}
+
+#[test]
+fn availability_response_without_status_still_parses() {
+    let response: ArchiveResponse = serde_json::from_str(&format!(
+        r#"{{"archived_snapshots":{{"closest":{{"available":true,"url":"u","timestamp":"{SNAPSHOT}"}}}}}}"#
+    ))
+    .unwrap();
+
+    assert_eq!(response.archived_snapshots["closest"].status, None);
+}
+
+#[test]
+fn job_status_includes_capture_status() {
+    let status = parse_job_status(&format!(
+        r#"{{"status":"success","http_status":200,"timestamp":"{SNAPSHOT}","original_url":"{ORIGINAL_URL}"}}"#
+    ))
+    .unwrap();
+
+    assert_eq!(
+        status,
+        ArchiveStatus::Success {
+            url: ORIGINAL_URL.to_string(),
+            timestamp: SNAPSHOT.to_string(),
+            http_status: Some(200),
+        }
+    );
+}
+
+#[test]
+fn job_status_with_server_error_capture_is_failed() {
+    let status = parse_job_status(&format!(
+        r#"{{"status":"success","http_status":503,"timestamp":"{SNAPSHOT}","original_url":"{ORIGINAL_URL}"}}"#
+    ))
+    .unwrap();
+
+    assert!(matches!(status, ArchiveStatus::Failed { reason } if reason.contains("503")));
+}
+
+#[tokio::test]
+async fn snapshot_is_polled_until_available() {
+    let polls = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let polls = polls.clone();
+        move |request| {
+            if request.path.starts_with("/save/status/")
+                && polls.fetch_add(1, Ordering::SeqCst) == 0
+            {
+                common::Response::new(200).body(r#"{"status":"pending"}"#)
+            } else {
+                serve_fresh(request)
+            }
+        }
+    })
+    .await;
+
+    let snapshot = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_snapshot_polling(Duration::from_millis(10), Duration::from_secs(5))
+        .archive_url(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    assert_eq!(polls.load(Ordering::SeqCst), 2);
+    assert_eq!(snapshot.timestamp, SNAPSHOT);
+}
+
+#[tokio::test]
+async fn snapshot_polling_gives_up_after_max_wait() {
+    let server = common::MockServer::start(|request| {
+        if request.path.starts_with("/save/status/") {
+            common::Response::new(200).body(r#"{"status":"pending"}"#)
+        } else {
+            serve_fresh(request)
+        }
+    })
+    .await;
+
+    let result = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_snapshot_polling(Duration::from_millis(10), Duration::from_millis(100))
+        .archive_url(ORIGINAL_URL)
+        .await;
+
+    assert!(result.is_err());
+}
+
+fn archive_error(error: anyhow::Error) -> ArchiveError {
+    error.downcast::<ArchiveError>().unwrap()
+}
+
+#[tokio::test]
+async fn rate_limited_save_is_reported() {
+    let server = common::MockServer::start(|_| common::Response::new(429)).await;
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_rate_limit_budget(Duration::ZERO)
+        .archive_url(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        archive_error(error),
+        ArchiveError::RateLimited { .. }
+    ));
+}
+
+#[tokio::test]
+async fn failed_save_job_is_reported() {
+    let server = common::MockServer::start(|request| {
+        if request.path.starts_with("/save/status/") {
+            common::Response::new(200).body(r#"{"status":"error","message":"Cannot resolve host"}"#)
+        } else {
+            serve_fresh(request)
+        }
+    })
+    .await;
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_url(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        archive_error(error),
+        ArchiveError::SaveFailed { reason, .. } if reason == "Cannot resolve host"
+    ));
+}
+
+#[tokio::test]
+async fn missing_snapshot_is_reported() {
+    let server = common::MockServer::start(|_| common::Response::new(503)).await;
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        archive_error(error),
+        ArchiveError::NoSnapshot { .. }
+    ));
+}
+
+#[tokio::test]
+async fn failed_download_is_reported() {
+    let server = common::MockServer::start(|_| common::Response::new(404)).await;
+
+    let Err(error) = WebArchiveClient::new()
+        .download_and_hash(&format!("{}/keydb_eng.zip", server.base_url))
+        .await
+    else {
+        panic!("download of a missing file succeeded");
+    };
+
+    let error = archive_error(error);
+    assert!(!error.is_retryable());
+    assert!(matches!(error, ArchiveError::DownloadFailed { .. }));
+}
+
+#[tokio::test]
+async fn invalid_content_is_reported() {
+    let server = common::MockServer::start(|request| {
+        let snapshot_path = format!("/web/{SNAPSHOT}/{ORIGINAL_URL}");
+        if request.path == snapshot_path {
+            common::Response::new(200)
+                .header("Content-Type", "text/html")
+                .body("<html></html>")
+        } else if request.path == format!("/web/{ORIGINAL_URL}") {
+            common::Response::new(302).header("Location", snapshot_path)
+        } else {
+            common::Response::new(503)
+        }
+    })
+    .await;
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        archive_error(error),
+        ArchiveError::InvalidContent { source, .. }
+            if matches!(*source, AppError::UnexpectedContentType { .. })
+    ));
+}
+
+/// A one-block tar archive with a `ustar` header
+fn tar_bytes() -> Vec<u8> {
+    let mut tar = vec![0u8; 1024];
+    tar[..9].copy_from_slice(b"keydb.cfg");
+    tar[257..263].copy_from_slice(b"ustar\0");
+    tar
+}
+
+#[test]
+fn zip_source_is_validated() {
+    let zip = common::keydb_zip();
+    assert!(validate_source(&zip, Some("application/zip"), SourceFormat::Zip).is_ok());
+
+    let error = validate_source(&tar_bytes(), None, SourceFormat::Zip).unwrap_err();
+    assert!(error.to_string().contains("ZIP archive"));
+}
+
+#[test]
+fn tar_source_is_validated() {
+    assert!(validate_source(&tar_bytes(), None, SourceFormat::Tar).is_ok());
+    assert!(validate_source(&common::keydb_zip(), None, SourceFormat::Tar).is_err());
+}
+
+#[test]
+fn tar_gz_source_is_validated() {
+    let gzip = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03";
+    assert!(validate_source(gzip, Some("application/gzip"), SourceFormat::TarGz).is_ok());
+
+    let error = validate_source(&common::keydb_zip(), None, SourceFormat::TarGz).unwrap_err();
+    assert!(
+        matches!(error, AppError::InvalidArchive { expected, .. } if expected.contains("gzip"))
+    );
+}
+
+#[test]
+fn raw_source_is_not_checked() {
+    assert!(validate_source(b"; KEYDB\n", Some("text/plain"), SourceFormat::Raw).is_ok());
+}
+
+#[tokio::test]
+async fn gzip_content_type_is_accepted_for_tar_gz_source() {
+    let server = common::MockServer::start(|request| {
+        let snapshot_path = format!("/web/{SNAPSHOT}/{ORIGINAL_URL}");
+        if request.path == snapshot_path {
+            common::Response::new(200)
+                .header("Content-Type", "application/gzip")
+                .body(b"\x1f\x8b\x08\x00".to_vec())
+        } else if request.path == format!("/web/{ORIGINAL_URL}") {
+            common::Response::new(302).header("Location", snapshot_path)
+        } else {
+            common::Response::new(503)
+        }
+    })
+    .await;
+    let client = WebArchiveClient::new().with_host(&server.base_url);
+
+    let error = client.archive_and_download(ORIGINAL_URL).await.unwrap_err();
+    assert!(matches!(
+        AppError::from_archive(error),
+        AppError::UnexpectedContentType { .. }
+    ));
+
+    let result = client
+        .with_source_format(SourceFormat::TarGz)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap();
+    assert_eq!(result.content_type.as_deref(), Some("application/gzip"));
+}
+
+#[tokio::test]
+async fn rate_limiter_spaces_out_a_burst() {
+    let limiter = RateLimiter::per_minute(600);
+    let start = Instant::now();
+
+    for _ in 0..4 {
+        limiter.acquire().await;
+    }
+
+    // The first request goes out right away, the other three wait one interval each
+    assert!(start.elapsed() >= limiter.interval() * 3);
+}
+
+#[tokio::test]
+async fn rate_limiter_is_shared_by_clients_from_one_config() {
+    let server = common::MockServer::start(|_: &common::Request| {
+        common::Response::new(200).body(r#"{"archived_snapshots":{}}"#)
+    })
+    .await;
+    let mut config = Config::new();
+    config.wayback_host = server.base_url.clone();
+    config.wayback_rate_limiter = Some(Arc::new(RateLimiter::per_minute(600)));
+    let interval = Duration::from_millis(100);
+    let first = WebArchiveClient::from_config(&config.clone()).unwrap();
+    let second = WebArchiveClient::from_config(&config.clone()).unwrap();
+    let start = Instant::now();
+
+    let results = tokio::join!(
+        first.refresh_archived(ORIGINAL_URL),
+        first.refresh_archived(ORIGINAL_URL),
+        second.refresh_archived(ORIGINAL_URL),
+        second.refresh_archived(ORIGINAL_URL),
+    );
+
+    assert!(results.0.unwrap().is_none());
+    assert!(results.3.unwrap().is_none());
+    assert!(start.elapsed() >= interval * 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn archive_budget_stops_polling() {
+    let server = common::MockServer::start(|request| {
+        if request.path.starts_with("/save/status/") {
+            common::Response::new(200).body(r#"{"status":"pending"}"#)
+        } else if request.path.starts_with("/wayback/available") {
+            common::Response::new(200).body(r#"{"archived_snapshots":{}}"#)
+        } else {
+            serve_fresh(request)
+        }
+    })
+    .await;
+    let budget = Duration::from_secs(10);
+    let start = tokio::time::Instant::now();
+
+    let error = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .with_snapshot_polling(Duration::from_secs(3), Duration::from_secs(3600))
+        .with_archive_budget(Some(budget))
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(AppError::from_archive(error), AppError::Timeout(b) if b == budget));
+    // The paused clock only moves through the sleeps, which never overrun the budget
+    assert!(start.elapsed() <= budget);
+}
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const ABC_B2: &str = "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923";
+
+#[test]
+fn multi_hasher_computes_every_digest_in_one_pass() {
+    let mut hasher = MultiHasher::new(&[ChecksumKind::B2, ChecksumKind::Sha256]);
+    hasher.update(b"a");
+    hasher.update(b"bc");
+
+    let digests = hasher.finalize();
+
+    assert_eq!(digests.len(), 2);
+    assert_eq!(digests[&ChecksumKind::Sha256], ABC_SHA256);
+    assert_eq!(digests[&ChecksumKind::B2], ABC_B2);
+}
+
+#[tokio::test]
+async fn download_computes_configured_digests() {
+    let server =
+        common::MockServer::start(|_| common::Response::new(200).body(b"abc".to_vec())).await;
+
+    let download = WebArchiveClient::new()
+        .with_checksums(&[ChecksumKind::B2])
+        .download_and_hash(&format!("{}/keydb_eng.zip", server.base_url))
+        .await
+        .unwrap();
+
+    assert_eq!(download.sha256, ABC_SHA256);
+    assert_eq!(download.digests[&ChecksumKind::Sha256], ABC_SHA256);
+    assert_eq!(download.digests[&ChecksumKind::B2], ABC_B2);
+    assert!(!download.digests.contains_key(&ChecksumKind::Md5));
+}
+
+const CDX_RESPONSE: &str = r#"[
+    ["urlkey","timestamp","original","mimetype","statuscode","digest","length"],
+    ["net,bplaced,fvonline-db)/export/keydb_eng.zip","20240101000000","http://fvonline-db.bplaced.net/export/keydb_eng.zip","application/zip","200","3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ","1048576"],
+    ["net,bplaced,fvonline-db)/export/keydb_eng.zip","20240102000000","http://fvonline-db.bplaced.net/export/keydb_eng.zip","text/html","503","LQ5DZ2OGHXVPX5V2HXRBVVXL7X5A4AU4","512"],
+    ["net,bplaced,fvonline-db)/export/keydb_eng.zip","20240103000000","http://fvonline-db.bplaced.net/export/keydb_eng.zip","warc/revisit","-","3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ","-"]
+]"#;
+
+#[test]
+fn cdx_response_is_parsed() {
+    let entries = parse_cdx(CDX_RESPONSE).unwrap();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+        entries[0],
+        CdxEntry {
+            timestamp: "20240101000000".to_string(),
+            original: "http://fvonline-db.bplaced.net/export/keydb_eng.zip".to_string(),
+            mimetype: "application/zip".to_string(),
+            status: Some(200),
+            digest: "3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ".to_string(),
+            length: Some(1048576),
+        }
+    );
+    assert!(entries[1].is_suspect());
+    assert_eq!((entries[2].status, entries[2].length), (None, None));
+    assert!(parse_cdx("[]").unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn cdx_lists_captures_of_url() {
+    let server = common::MockServer::start(|request| {
+        assert!(request.path.starts_with("/cdx/search/cdx?"));
+        assert!(request.path.contains("output=json"));
+        common::Response::new(200).body(CDX_RESPONSE)
+    })
+    .await;
+
+    let entries = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .cdx("http://fvonline-db.bplaced.net/export/keydb_eng.zip")
+        .await
+        .unwrap();
+
+    assert_eq!(entries.len(), 3);
+}
+
+#[test]
+fn wayback_digest_is_base32_sha1() {
+    assert_eq!(wayback_digest(b"abc"), "VGMT4NSHA2AWVOR6EVYXQUGCNSONBWE5");
+}
+
+#[tokio::test]
+async fn unchanged_cdx_digest_skips_snapshot_download() {
+    let downloads = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let downloads = downloads.clone();
+        move |request| {
+            if request.path.starts_with("/save/") {
+                common::Response::new(503)
+            } else if request.path.starts_with("/cdx/search/cdx?") {
+                assert!(request.path.contains(&format!("from={SNAPSHOT}")));
+                common::Response::new(200).body(format!(
+                    r#"[["urlkey","timestamp","original","mimetype","statuscode","digest","length"],
+                        ["com,example)/keydb_eng.zip","{SNAPSHOT}","{ORIGINAL_URL}","application/zip","200","{}","100"]]"#,
+                    wayback_digest(&common::keydb_zip())
+                ))
+            } else {
+                if request.method == "GET" && request.path.starts_with(&format!("/web/{SNAPSHOT}/")) {
+                    downloads.fetch_add(1, Ordering::SeqCst);
+                }
+                serve_snapshot(request)
+                    .unwrap_or_else(|| common::Response::new(404))
+            }
+        }
+    })
+    .await;
+    let cache_dir = tempfile::tempdir().unwrap();
+    let client = || {
+        WebArchiveClient::new()
+            .with_host(&server.base_url)
+            .with_download_cache(Some(DownloadCache::new(cache_dir.path())))
+    };
+
+    let first = client().archive_and_download(ORIGINAL_URL).await.unwrap();
+    let second = client().archive_and_download(ORIGINAL_URL).await.unwrap();
+
+    assert_eq!(downloads.load(Ordering::SeqCst), 1);
+    assert_eq!(second.source, ArchiveSource::ExistingFallback);
+    assert_eq!(second.sha256, first.sha256);
+    assert_eq!(second.content, common::keydb_zip());
+}
+
+#[tokio::test]
+async fn existing_snapshot_skips_cdx_lookup_without_download_cache() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let server = common::MockServer::start({
+        let lookups = lookups.clone();
+        move |request| {
+            if request.path.starts_with("/save/") {
+                common::Response::new(503)
+            } else if request.path.starts_with("/cdx/search/cdx?") {
+                lookups.fetch_add(1, Ordering::SeqCst);
+                common::Response::new(500)
+            } else {
+                serve_snapshot(request).unwrap_or_else(|| common::Response::new(404))
+            }
+        }
+    })
+    .await;
+
+    let result = WebArchiveClient::new()
+        .with_host(&server.base_url)
+        .archive_and_download(ORIGINAL_URL)
+        .await
+        .unwrap();
+
+    assert_eq!(result.source, ArchiveSource::ExistingFallback);
+    assert_eq!(lookups.load(Ordering::SeqCst), 0);
+}
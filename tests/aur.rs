@@ -0,0 +1,677 @@
+use aur_aacs_keydb::{
+    AppError, AurPackageManager,
+    aur::{InstallTarget, PkgRelease, check_srcinfo_sync, escape_source_url, vercmp},
+    checksum::ChecksumKind,
+};
+use std::{cmp::Ordering, collections::HashMap};
+
+const SHA256: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+const ORIGINAL_URL: &str = "http://example.com/keydb_eng.zip";
+const SOURCE: &str = r#"source=("keydb_eng.zip::https://web.archive.org/web/${pkgver}/http://example.com/keydb_eng.zip")"#;
+const NEW_SHA256: &str = "fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210";
+
+fn manager() -> AurPackageManager {
+    AurPackageManager::new(
+        "aacs-keydb-daily".to_string(),
+        "keydb_eng-${pkgver}.zip".to_string(),
+    )
+}
+
+fn pkgbuild(version: &str) -> String {
+    manager()
+        .render_pkgbuild_template(
+            aur_aacs_keydb::aur::DEFAULT_PKGBUILD_TEMPLATE,
+            ORIGINAL_URL,
+            version,
+            SHA256,
+        )
+        .unwrap()
+}
+
+fn srcinfo_with(version: &str, release: PkgRelease) -> String {
+    manager()
+        .generate_srcinfo(
+            std::path::Path::new("PKGBUILD"),
+            version,
+            release,
+            SHA256,
+            "https://web.archive.org/web/20240101000000/http://example.com/keydb_eng.zip",
+        )
+        .unwrap()
+}
+
+fn srcinfo(version: &str) -> String {
+    srcinfo_with(
+        version,
+        PkgRelease {
+            epoch: None,
+            pkgrel: 1,
+        },
+    )
+}
+
+/// Update a PKGBUILD at `20240101000000`, pkgrel 3, to `new_version`
+fn update_from_pkgrel_3(new_version: &str) -> (PkgRelease, String) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    manager()
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, content.replace("pkgrel=1", "pkgrel=3")).unwrap();
+
+    let release = manager()
+        .update_pkgbuild(
+            &path,
+            ORIGINAL_URL,
+            "20240101000000",
+            new_version,
+            NEW_SHA256,
+            false,
+        )
+        .unwrap();
+    (release, std::fs::read_to_string(&path).unwrap())
+}
+
+#[test]
+fn pkgrel_is_bumped_for_same_version() {
+    let (release, updated) = update_from_pkgrel_3("20240101000000");
+
+    assert_eq!(release.pkgrel, 4);
+    assert!(updated.contains("pkgrel=4\n"));
+    assert!(updated.contains(&format!("sha256sums=('{NEW_SHA256}')")));
+}
+
+#[test]
+fn pkgrel_is_reset_for_new_version() {
+    let (release, updated) = update_from_pkgrel_3("20240201000000");
+
+    assert_eq!(release.pkgrel, 1);
+    assert_eq!(release.epoch, None);
+    assert!(updated.contains("pkgver=20240201000000\n"));
+    assert!(updated.contains("pkgrel=1\n"));
+}
+
+#[test]
+fn epoch_is_added_then_incremented() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    manager()
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+    let update = |old: &str, new: &str| {
+        manager()
+            .update_pkgbuild(&path, ORIGINAL_URL, old, new, SHA256, true)
+            .unwrap()
+    };
+
+    let release = update("20240101000000", "2023");
+    assert_eq!(release.epoch, Some(1));
+    assert!(
+        std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("epoch=1\n")
+    );
+
+    let release = update("2023", "2022");
+    assert_eq!(release.epoch, Some(2));
+    let updated = std::fs::read_to_string(&path).unwrap();
+    assert!(updated.contains("epoch=2\n"));
+    assert!(!updated.contains("epoch=1"));
+    assert!(updated.contains("pkgver=2022\n"));
+}
+
+#[test]
+fn vercmp_orders_versions_like_pacman() {
+    assert_eq!(
+        vercmp("20240102000000", "20240101000000"),
+        Ordering::Greater
+    );
+    assert_eq!(vercmp("2023", "20240101000000"), Ordering::Less);
+    assert_eq!(vercmp("1.10", "1.9"), Ordering::Greater);
+    assert_eq!(vercmp("20240101000000", "20240101000000"), Ordering::Equal);
+}
+
+#[test]
+fn missing_pkgver_is_reported() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(&path, "pkgname=aacs-keydb-daily\npkgrel=1\n").unwrap();
+
+    let error = manager().extract_current_version(&path).unwrap_err();
+
+    assert!(error.to_string().contains("pkgver"), "{error}");
+}
+
+#[test]
+fn update_pkgbuild_preserves_hand_added_fields() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    manager()
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(
+        &path,
+        format!(
+            "# Maintainer: someone\n{content}optdepends=('libaacs: playback support')\n\nprepare() {{\n  echo preparing\n}}\n"
+        ),
+    )
+    .unwrap();
+
+    manager()
+        .update_pkgbuild(
+            &path,
+            ORIGINAL_URL,
+            "20240101000000",
+            "20240102000000",
+            NEW_SHA256,
+            false,
+        )
+        .unwrap();
+
+    let updated = std::fs::read_to_string(&path).unwrap();
+    assert!(updated.starts_with("# Maintainer: someone\n"));
+    assert!(updated.contains("optdepends=('libaacs: playback support')\n"));
+    assert!(updated.contains("prepare() {\n  echo preparing\n}\n"));
+    assert!(updated.contains("pkgver=20240102000000\n"));
+    assert!(updated.contains(&format!("sha256sums=('{NEW_SHA256}')")));
+}
+
+#[test]
+fn update_pkgbuild_inserts_missing_fields() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(
+        &path,
+        format!("# Maintainer: someone\npkgname=aacs-keydb-daily\npkgver=20240101000000\narch=('any')\n{SOURCE}\n"),
+    )
+    .unwrap();
+
+    manager()
+        .update_pkgbuild(
+            &path,
+            ORIGINAL_URL,
+            "20240101000000",
+            "20240102000000",
+            NEW_SHA256,
+            false,
+        )
+        .unwrap();
+
+    let updated = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        updated.starts_with(&format!(
+            "# Maintainer: someone\npkgname=aacs-keydb-daily\npkgver=20240102000000\npkgrel=1\narch=('any')\n{SOURCE}\nsha256sums=('{NEW_SHA256}')\n"
+        )),
+        "{updated}"
+    );
+}
+
+#[test]
+fn custom_source_filename_reaches_pkgbuild_and_srcinfo() {
+    let manager = AurPackageManager::new(
+        "aacs-keydb-daily".to_string(),
+        "KEYDB-${pkgver}-eng.zip".to_string(),
+    );
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    manager
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+    let srcinfo = manager
+        .generate_srcinfo(
+            &path,
+            "20240101000000",
+            PkgRelease {
+                epoch: None,
+                pkgrel: 1,
+            },
+            SHA256,
+            "https://web.archive.org/web/20240101000000/http://example.com/keydb_eng.zip",
+        )
+        .unwrap();
+
+    let pkgbuild = std::fs::read_to_string(&path).unwrap();
+    assert!(pkgbuild.contains(r#"source=("KEYDB-${pkgver}-eng.zip::"#));
+    assert!(srcinfo.contains("\tsource = KEYDB-20240101000000-eng.zip::"));
+}
+
+#[test]
+fn custom_template_placeholders_are_substituted() {
+    let template = "pkgname={pkgname}\npkgver={pkgver}\npkgrel=1\nsource=(\"{source_filename}::{url}\")\nsha256sums=('{sha256}')\n# keep ${pkgver} and {unknown}\n";
+
+    let rendered = manager()
+        .render_pkgbuild_template(template, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+
+    assert_eq!(
+        rendered,
+        format!(
+            "pkgname=aacs-keydb-daily\npkgver=20240101000000\npkgrel=1\nsource=(\"keydb_eng-${{pkgver}}.zip::http://example.com/keydb_eng.zip\")\nsha256sums=('{SHA256}')\n# keep ${{pkgver}} and {{unknown}}\n"
+        )
+    );
+}
+
+#[test]
+fn template_missing_required_fields_is_rejected() {
+    let error = manager()
+        .render_pkgbuild_template(
+            "pkgname={pkgname}\npkgver={pkgver}\n",
+            ORIGINAL_URL,
+            "20240101000000",
+            SHA256,
+        )
+        .unwrap_err();
+
+    assert!(error.to_string().contains("pkgrel, source, sha256sums"));
+}
+
+#[test]
+fn initial_pkgbuild_is_rendered_from_template_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let template_path = dir.path().join("PKGBUILD.in");
+    std::fs::write(
+        &template_path,
+        "# Custom\npkgname={pkgname}\npkgver={pkgver}\npkgrel=1\nsource=(\"{url}\")\nsha256sums=('{sha256}')\n",
+    )
+    .unwrap();
+    let path = dir.path().join("PKGBUILD");
+
+    manager()
+        .with_pkgbuild_template(Some(template_path))
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+
+    let pkgbuild = std::fs::read_to_string(&path).unwrap();
+    assert!(pkgbuild.starts_with("# Custom\npkgname=aacs-keydb-daily\n"));
+    assert!(pkgbuild.contains(&format!("sha256sums=('{SHA256}')")));
+}
+
+/// `sha256sums` read back from a PKGBUILD whose checksum entry is `sha256`
+fn extract_sha256(sha256: &str) -> anyhow::Result<Option<String>> {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    manager()
+        .create_initial_pkgbuild(&path, ORIGINAL_URL, "20240101000000", SHA256)
+        .unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, content.replace(SHA256, sha256)).unwrap();
+    manager().extract_current_sha256(&path)
+}
+
+#[test]
+fn valid_sha256_is_extracted() {
+    assert_eq!(extract_sha256(SHA256).unwrap().as_deref(), Some(SHA256));
+}
+
+#[test]
+fn skip_checksum_has_nothing_to_compare() {
+    assert_eq!(extract_sha256("SKIP").unwrap(), None);
+}
+
+#[test]
+fn malformed_sha256_is_rejected() {
+    assert!(extract_sha256("0123456789").is_err());
+    assert!(extract_sha256(&SHA256.to_uppercase()).is_err());
+}
+
+#[test]
+fn srcinfo_sync_accepts_generated_files() {
+    check_srcinfo_sync(&pkgbuild("20240101000000"), &srcinfo("20240101000000")).unwrap();
+}
+
+#[test]
+fn srcinfo_sync_rejects_version_mismatch() {
+    let result = check_srcinfo_sync(&pkgbuild("20240101000000"), &srcinfo("20240102000000"));
+
+    assert!(matches!(
+        result,
+        Err(AppError::SrcinfoMismatch {
+            field: "pkgver",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn srcinfo_sync_rejects_checksum_mismatch() {
+    let pkgbuild = pkgbuild("20240101000000").replace(SHA256, "SKIP");
+
+    let result = check_srcinfo_sync(&pkgbuild, &srcinfo("20240101000000"));
+
+    assert!(matches!(
+        result,
+        Err(AppError::SrcinfoMismatch {
+            field: "sha256sums",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn srcinfo_sync_rejects_missing_epoch() {
+    let pkgbuild = pkgbuild("20240101000000").replace("pkgrel=1\n", "pkgrel=1\nepoch=1\n");
+
+    let result = check_srcinfo_sync(&pkgbuild, &srcinfo("20240101000000"));
+
+    assert!(matches!(
+        result,
+        Err(AppError::SrcinfoMismatch { field: "epoch", .. })
+    ));
+}
+
+#[test]
+fn srcinfo_sync_accepts_matching_epoch() {
+    let pkgbuild = pkgbuild("20240101000000").replace("pkgrel=1\n", "pkgrel=1\nepoch=1\n");
+    let srcinfo = srcinfo_with(
+        "20240101000000",
+        PkgRelease {
+            epoch: Some(1),
+            pkgrel: 1,
+        },
+    );
+
+    check_srcinfo_sync(&pkgbuild, &srcinfo).unwrap();
+}
+
+#[test]
+fn update_pkgbuild_rewrites_source_url() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(&path, pkgbuild("20240101000000")).unwrap();
+
+    manager()
+        .update_pkgbuild(
+            &path,
+            "https://mirror.example.org/keydb_eng.zip",
+            "20240101000000",
+            "20240102000000",
+            SHA256,
+            false,
+        )
+        .unwrap();
+
+    let updated = std::fs::read_to_string(&path).unwrap();
+    assert!(updated.contains(
+        r#"source=("keydb_eng-${pkgver}.zip::https://web.archive.org/web/${pkgver}/https://mirror.example.org/keydb_eng.zip")"#
+    ));
+    assert!(updated.contains("pkgver=20240102000000"));
+}
+
+#[test]
+fn update_pkgbuild_rejects_unrelated_source() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    let content = pkgbuild("20240101000000").replace(
+        "https://web.archive.org/web/${pkgver}/http://example.com/keydb_eng.zip",
+        "https://elsewhere.example.org/keydb.zip",
+    );
+    std::fs::write(&path, content).unwrap();
+
+    let result = manager().update_pkgbuild(
+        &path,
+        ORIGINAL_URL,
+        "20240101000000",
+        "20240102000000",
+        SHA256,
+        false,
+    );
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn split_package_srcinfo_has_a_section_per_name() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let pkgbuild_path = dir.path().join("PKGBUILD");
+    let pkgbuild = pkgbuild("20240101000000").replace(
+        "pkgname=aacs-keydb-daily\n",
+        "pkgbase=aacs-keydb\npkgname=('aacs-keydb-daily'\n         'aacs-keydb-daily-cfg')\n",
+    );
+    std::fs::write(&pkgbuild_path, &pkgbuild).unwrap();
+
+    let srcinfo = manager()
+        .generate_srcinfo(
+            &pkgbuild_path,
+            "20240101000000",
+            PkgRelease {
+                epoch: None,
+                pkgrel: 1,
+            },
+            SHA256,
+            "https://web.archive.org/web/20240101000000/http://example.com/keydb_eng.zip",
+        )
+        .unwrap();
+
+    assert!(srcinfo.starts_with("pkgbase = aacs-keydb\n"));
+    assert!(srcinfo.ends_with("\npkgname = aacs-keydb-daily\n\npkgname = aacs-keydb-daily-cfg\n"));
+    check_srcinfo_sync(&pkgbuild, &srcinfo).unwrap();
+}
+
+#[test]
+fn source_url_spaces_and_parentheses_are_encoded() {
+    assert_eq!(
+        escape_source_url("http://example.com/key db (eng).zip").unwrap(),
+        "http://example.com/key%20db%20%28eng%29.zip"
+    );
+}
+
+#[test]
+fn source_url_escaping_is_idempotent() {
+    let once = escape_source_url("http://example.com/a b/$(id).zip?x=\"1\"").unwrap();
+    assert_eq!(escape_source_url(&once).unwrap(), once);
+}
+
+#[test]
+fn source_url_with_control_character_is_rejected() {
+    assert!(escape_source_url("http://example.com/a\nb.zip").is_err());
+}
+
+#[test]
+fn rendered_pkgbuild_source_is_escaped() {
+    let pkgbuild = manager()
+        .render_pkgbuild_template(
+            aur_aacs_keydb::aur::DEFAULT_PKGBUILD_TEMPLATE,
+            "http://example.com/keydb (eng).zip",
+            "20240101000000",
+            SHA256,
+        )
+        .unwrap();
+
+    assert!(pkgbuild.contains("/web/${pkgver}/http://example.com/keydb%20%28eng%29.zip\")"));
+}
+
+#[test]
+fn srcinfo_source_is_escaped() {
+    let srcinfo = manager()
+        .generate_srcinfo(
+            std::path::Path::new("PKGBUILD"),
+            "20240101000000",
+            PkgRelease {
+                epoch: None,
+                pkgrel: 1,
+            },
+            SHA256,
+            "https://web.archive.org/web/20240101000000/http://example.com/keydb (eng).zip",
+        )
+        .unwrap();
+
+    assert!(srcinfo.contains(
+        "\tsource = keydb_eng-20240101000000.zip::https://web.archive.org/web/20240101000000/http://example.com/keydb%20%28eng%29.zip\n"
+    ));
+}
+
+/// Update a PKGBUILD at `20240101000000`, pkgrel 3, to the same version
+fn update_same_version(manager: AurPackageManager) -> (PkgRelease, String) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(
+        &path,
+        pkgbuild("20240101000000").replace("pkgrel=1", "pkgrel=3"),
+    )
+    .unwrap();
+
+    let release = manager
+        .update_pkgbuild(
+            &path,
+            "http://example.com/keydb_eng.zip",
+            "20240101000000",
+            "20240101000000",
+            SHA256,
+            false,
+        )
+        .unwrap();
+    (release, std::fs::read_to_string(&path).unwrap())
+}
+
+#[test]
+fn explicit_pkgrel_is_written_verbatim() {
+    let (release, updated) = update_same_version(manager().with_pkgrel(Some(7)));
+
+    assert_eq!(release.pkgrel, 7);
+    assert!(updated.contains("pkgrel=7\n"));
+}
+
+#[test]
+fn pkgrel_is_bumped_without_override() {
+    let (release, updated) = update_same_version(manager().with_pkgrel(None));
+
+    assert_eq!(release.pkgrel, 4);
+    assert!(updated.contains("pkgrel=4\n"));
+}
+
+#[test]
+fn explicit_pkgrel_is_used_for_new_package() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+
+    let release = manager()
+        .with_pkgrel(Some(2))
+        .create_initial_pkgbuild(
+            &path,
+            "http://example.com/keydb_eng.zip",
+            "20240101000000",
+            SHA256,
+        )
+        .unwrap();
+
+    assert_eq!(release.pkgrel, 2);
+    let pkgbuild = std::fs::read_to_string(&path).unwrap();
+    assert!(pkgbuild.contains("pkgrel=2\n"));
+    assert!(!pkgbuild.contains("pkgrel=1"));
+}
+
+#[test]
+fn default_install_target_renders_current_paths() {
+    let pkgbuild = pkgbuild("20240101000000");
+
+    assert!(pkgbuild.contains("install -d \"${pkgdir}/etc/xdg/aacs\""));
+    assert!(
+        pkgbuild.contains(
+            "install -Dm644 \"${srcdir}/keydb.cfg\" \"${pkgdir}/etc/xdg/aacs/KEYDB.cfg\""
+        )
+    );
+}
+
+#[test]
+fn custom_install_target_is_rendered() {
+    let pkgbuild = manager()
+        .with_install_target(InstallTarget {
+            source: "KEYDB.cfg".to_string(),
+            dest: "/usr/share/aacs/keydb.cfg".to_string(),
+            mode: "0600".to_string(),
+        })
+        .render_pkgbuild_template(
+            aur_aacs_keydb::aur::DEFAULT_PKGBUILD_TEMPLATE,
+            "http://example.com/keydb_eng.zip",
+            "20240101000000",
+            SHA256,
+        )
+        .unwrap();
+
+    assert!(pkgbuild.contains("install -d \"${pkgdir}/usr/share/aacs\""));
+    assert!(pkgbuild.contains(
+        "install -Dm0600 \"${srcdir}/KEYDB.cfg\" \"${pkgdir}/usr/share/aacs/keydb.cfg\""
+    ));
+}
+
+#[test]
+fn wayback_host_placeholder_is_substituted() {
+    let default = pkgbuild("20240101000000");
+    let mirrored = manager()
+        .with_wayback_host("http://wayback.example.org/")
+        .render_pkgbuild_template(
+            aur_aacs_keydb::aur::DEFAULT_PKGBUILD_TEMPLATE,
+            ORIGINAL_URL,
+            "20240101000000",
+            SHA256,
+        )
+        .unwrap();
+
+    assert!(default.contains("::https://web.archive.org/web/${pkgver}/"));
+    assert!(mirrored.contains("::https://wayback.example.org/web/${pkgver}/"));
+}
+
+#[test]
+fn relative_install_destination_is_rejected() {
+    let target = InstallTarget {
+        dest: "etc/KEYDB.cfg".to_string(),
+        ..InstallTarget::default()
+    };
+
+    assert!(target.validate().unwrap_err().contains("absolute"));
+    assert!(InstallTarget::default().validate().is_ok());
+}
+
+#[test]
+fn declared_extra_checksums_are_written_to_pkgbuild_and_srcinfo() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(
+        &path,
+        pkgbuild("20240101000000").replace(
+            &format!("sha256sums=('{SHA256}')"),
+            &format!("sha256sums=('{SHA256}')\nb2sums=('SKIP')"),
+        ),
+    )
+    .unwrap();
+    let digests = HashMap::from([
+        (ChecksumKind::Sha256, SHA256.to_string()),
+        (ChecksumKind::B2, "b2digest".to_string()),
+        (ChecksumKind::Md5, "md5digest".to_string()),
+    ]);
+
+    manager().update_checksums(&path, &digests).unwrap();
+    let pkgbuild = std::fs::read_to_string(&path).unwrap();
+    let srcinfo = manager()
+        .generate_srcinfo(
+            &path,
+            "20240101000000",
+            PkgRelease {
+                epoch: None,
+                pkgrel: 1,
+            },
+            SHA256,
+            "https://web.archive.org/web/20240101000000/http://example.com/keydb_eng.zip",
+        )
+        .unwrap();
+
+    assert!(pkgbuild.contains("b2sums=('b2digest')"));
+    assert!(!pkgbuild.contains("md5sums"));
+    assert!(srcinfo.contains("\tb2sums = b2digest\n"));
+    check_srcinfo_sync(&pkgbuild, &srcinfo).unwrap();
+}
+
+#[test]
+fn declared_checksum_without_digest_is_an_error() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("PKGBUILD");
+    std::fs::write(&path, "md5sums=('SKIP')\n").unwrap();
+
+    let error = manager()
+        .update_checksums(&path, &HashMap::new())
+        .unwrap_err();
+
+    assert!(error.to_string().contains("CHECKSUMS"));
+}
@@ -0,0 +1,50 @@
+use aur_aacs_keydb::batch::run_bounded;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+#[tokio::test]
+async fn run_bounded_respects_limit() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    let results = run_bounded((0..10).collect(), 3, |item: u64| {
+        let running = Arc::clone(&running);
+        let peak = Arc::clone(&peak);
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20 + (item % 3) * 10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            item * 2
+        }
+    })
+    .await;
+
+    assert_eq!(peak.load(Ordering::SeqCst), 3);
+    assert_eq!(results, (0..10).map(|item| item * 2).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn run_bounded_treats_zero_as_sequential() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    run_bounded((0..4).collect(), 0, |_: u32| {
+        let running = Arc::clone(&running);
+        let peak = Arc::clone(&peak);
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        }
+    })
+    .await;
+
+    assert_eq!(peak.load(Ordering::SeqCst), 1);
+}
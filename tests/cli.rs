@@ -0,0 +1,57 @@
+//! The cli module belongs to the binary, so it is compiled into this test directly.
+#[path = "../src/cli.rs"]
+mod cli;
+
+use aur_aacs_keydb::Config;
+use clap::Parser;
+use cli::{Cli, level_for};
+use tracing::Level;
+
+fn level(args: &[&str]) -> Level {
+    Cli::try_parse_from(std::iter::once("aur-aacs-keydb").chain(args.iter().copied()))
+        .unwrap()
+        .level()
+}
+
+#[test]
+fn force_flag_overrides_config() {
+    let mut config = Config::new();
+
+    Cli::parse_from(["aur-aacs-keydb", "--force"]).apply(&mut config);
+
+    assert!(config.force);
+}
+
+#[test]
+fn config_is_unchanged_without_flags() {
+    let mut config = Config::new();
+
+    Cli::parse_from(["aur-aacs-keydb"]).apply(&mut config);
+
+    assert!(!config.force);
+}
+
+#[test]
+fn verbosity_counts_map_to_levels() {
+    assert_eq!(level_for(0, 0), Level::INFO);
+    assert_eq!(level_for(1, 0), Level::DEBUG);
+    assert_eq!(level_for(2, 0), Level::TRACE);
+    assert_eq!(level_for(5, 0), Level::TRACE);
+    assert_eq!(level_for(0, 1), Level::WARN);
+    assert_eq!(level_for(0, 2), Level::ERROR);
+    assert_eq!(level_for(0, 5), Level::ERROR);
+}
+
+#[test]
+fn flags_select_the_log_level() {
+    assert_eq!(level(&[]), Level::INFO);
+    assert_eq!(level(&["-vv"]), Level::TRACE);
+    assert_eq!(level(&["-q"]), Level::WARN);
+    assert_eq!(level(&["--log-level", "debug"]), Level::DEBUG);
+}
+
+#[test]
+fn conflicting_verbosity_flags_are_rejected() {
+    assert!(Cli::try_parse_from(["aur-aacs-keydb", "-v", "-q"]).is_err());
+    assert!(Cli::try_parse_from(["aur-aacs-keydb", "-v", "--log-level", "warn"]).is_err());
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use aur_aacs_keydb::{AppError, Config, config::check_ssh_key};
+use tempfile::TempDir;
+
+#[test]
+fn invalid_ca_bundle_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("ca.pem");
+    std::fs::write(&path, "not a certificate").unwrap();
+    let mut config = Config::new();
+    config.ca_bundle_path = Some(path);
+
+    assert!(config.http_client().is_err());
+}
+
+#[test]
+fn missing_ca_bundle_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let mut config = Config::new();
+    config.ca_bundle_path = Some(dir.path().join("missing.pem"));
+
+    assert!(config.http_client().is_err());
+}
+
+#[test]
+fn unwritable_work_dir_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+    let mut config = Config::new();
+    config.git_https_token = Some("token".to_string());
+    config.work_dir = file.join("work").display().to_string();
+
+    let error = config.check_work_dir_writable().unwrap_err();
+
+    assert!(error.to_string().contains("is not writable"));
+}
+
+#[test]
+fn existing_snapshots_have_no_age_limit_by_default() {
+    assert_eq!(Config::new().max_snapshot_age, None);
+}
+
+#[test]
+fn validate_does_not_create_work_dir() {
+    let dir = TempDir::new().unwrap();
+    let mut config = Config::new();
+    config.git_https_token = Some("token".to_string());
+    config.work_dir = dir.path().join("work").display().to_string();
+
+    config.validate().unwrap();
+
+    assert!(!dir.path().join("work").exists());
+}
+
+fn ssh_key_error(path: &std::path::Path) -> String {
+    match check_ssh_key(path) {
+        Err(AppError::InvalidSshKey { reason, .. }) => reason,
+        other => panic!("expected an invalid SSH key error, got {other:?}"),
+    }
+}
+
+#[test]
+fn ssh_key_directory_is_rejected() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(ssh_key_error(dir.path()).contains("is a directory"));
+}
+
+#[test]
+fn missing_ssh_key_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("id_ed25519");
+
+    assert!(ssh_key_error(&path).contains("does not exist"));
+
+    #[cfg(unix)]
+    {
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert!(ssh_key_error(&link).contains("broken symlink"));
+    }
+}
+
+#[test]
+fn public_key_is_rejected_as_ssh_key() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("id_ed25519.pub");
+    std::fs::write(&path, "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA user@host\n").unwrap();
+
+    assert!(ssh_key_error(&path).contains("looks like a public key"));
+}
+
+#[test]
+fn private_key_file_passes_validation() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("id_ed25519");
+    std::fs::write(&path, common::PRIVATE_KEY).unwrap();
+    let mut config = Config::new();
+    config.git_https_token = None;
+    config.ssh_key_path = path.display().to_string();
+    config.work_dir = dir.path().join("work").display().to_string();
+
+    check_ssh_key(&path).unwrap();
+    config.validate().unwrap();
+}
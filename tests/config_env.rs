@@ -0,0 +1,43 @@
+//! `Config` read from environment variables. Kept in its own test binary with a single
+//! test, since changing the environment would race with the other config tests.
+
+use aur_aacs_keydb::{
+    Config,
+    config::{ArchiveMode, OriginMismatchPolicy, SourceFormat},
+};
+
+#[test]
+fn unknown_choice_values_are_rejected() {
+    for (name, valid, typo, accepted) in [
+        ("SOURCE_FORMAT", "tar.gz", "targz", SourceFormat::NAMES),
+        (
+            "ARCHIVE_MODE",
+            "existing-only",
+            "existing_only",
+            ArchiveMode::NAMES,
+        ),
+        (
+            "ORIGIN_MISMATCH",
+            "rearchive",
+            "re-archive",
+            OriginMismatchPolicy::NAMES,
+        ),
+    ] {
+        // SAFETY: this binary runs no other test that reads the environment
+        unsafe { std::env::set_var(name, typo) };
+        let error = Config::from_env().unwrap_err().to_string();
+        assert!(
+            error.contains(&format!("Unknown {name} '{typo}'")),
+            "{error}"
+        );
+        assert!(error.contains(&accepted.join(", ")), "{error}");
+
+        unsafe { std::env::set_var(name, valid) };
+        Config::from_env().unwrap();
+        unsafe { std::env::remove_var(name) };
+    }
+
+    unsafe { std::env::set_var("ARCHIVE_MODE", "existing-only") };
+    assert_eq!(Config::new().archive_mode, ArchiveMode::ExistingOnly);
+    unsafe { std::env::remove_var("ARCHIVE_MODE") };
+}
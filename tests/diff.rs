@@ -0,0 +1,36 @@
+use aur_aacs_keydb::diff::{colorize, unified_diff};
+
+const OLD: &str =
+    "pkgname=aacs-keydb-daily\npkgver=20240101000000\npkgrel=1\nsha256sums=('aaaa')\n";
+const NEW: &str =
+    "pkgname=aacs-keydb-daily\npkgver=20240201000000\npkgrel=1\nsha256sums=('bbbb')\n";
+
+#[test]
+fn hunk_contains_changed_version_and_checksum() {
+    let diff = unified_diff("PKGBUILD", OLD, NEW);
+
+    assert!(
+        diff.starts_with("--- a/PKGBUILD\n+++ b/PKGBUILD\n"),
+        "{diff}"
+    );
+    assert!(diff.contains("@@ "), "{diff}");
+    assert!(diff.contains("-pkgver=20240101000000\n"), "{diff}");
+    assert!(diff.contains("+pkgver=20240201000000\n"), "{diff}");
+    assert!(diff.contains("-sha256sums=('aaaa')\n"), "{diff}");
+    assert!(diff.contains("+sha256sums=('bbbb')\n"), "{diff}");
+    assert!(diff.contains(" pkgrel=1\n"), "{diff}");
+}
+
+#[test]
+fn identical_files_have_no_hunks() {
+    assert_eq!(unified_diff("PKGBUILD", OLD, OLD), "");
+}
+
+#[test]
+fn colorize_marks_changes_but_not_headers() {
+    let colored = colorize(&unified_diff("PKGBUILD", OLD, NEW));
+
+    assert!(colored.contains("--- a/PKGBUILD\n"));
+    assert!(colored.contains("\x1b[31m-pkgver=20240101000000\x1b[0m"));
+    assert!(colored.contains("\x1b[32m+pkgver=20240201000000\x1b[0m"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use aur_aacs_keydb::{
+    Config,
+    doctor::{check_original_url, check_ssh_key, check_tool, check_work_dir},
+};
+
+#[test]
+fn ssh_key_is_not_needed_with_a_token() {
+    let config = Config {
+        git_https_token: Some("token".to_string()),
+        ..Config::new()
+    };
+
+    let result = check_ssh_key(&config);
+
+    assert!(result.passed());
+    assert!(!result.critical);
+}
+
+#[test]
+fn missing_ssh_key_is_a_critical_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        git_https_token: None,
+        ssh_key_path: dir.path().join("id_ed25519").display().to_string(),
+        ..Config::new()
+    };
+
+    let result = check_ssh_key(&config);
+
+    assert!(!result.passed());
+    assert!(result.critical);
+    assert!(
+        result.to_string().starts_with("[FAIL] SSH key: "),
+        "{result}"
+    );
+}
+
+#[test]
+fn unwritable_work_dir_is_a_critical_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+    let config = Config {
+        work_dir: file.join("work").display().to_string(),
+        ..Config::new()
+    };
+
+    let result = check_work_dir(&config);
+
+    assert!(!result.passed());
+    assert!(result.critical);
+}
+
+#[test]
+fn missing_tool_is_only_a_warning() {
+    let result = check_tool("aur-aacs-keydb-no-such-tool");
+
+    assert!(!result.passed());
+    assert!(!result.critical);
+    assert_eq!(
+        result.to_string(),
+        "[WARN] aur-aacs-keydb-no-such-tool: not found on PATH"
+    );
+}
+
+#[tokio::test]
+async fn original_url_answering_404_fails() {
+    let server = common::MockServer::start(|_| common::Response::new(404)).await;
+    let config = Config {
+        original_url: format!("{}/keydb_eng.zip", server.base_url),
+        ..Config::new()
+    };
+
+    let result = check_original_url(&config).await;
+
+    assert!(!result.passed());
+    assert!(result.critical);
+}
+
+#[tokio::test]
+async fn responding_original_url_passes() {
+    let server = common::MockServer::start(|_| common::Response::new(200)).await;
+    let config = Config {
+        original_url: format!("{}/keydb_eng.zip", server.base_url),
+        ..Config::new()
+    };
+
+    assert!(check_original_url(&config).await.passed());
+}
@@ -0,0 +1,113 @@
+use aur_aacs_keydb::{AppError, ArchiveError};
+use std::io;
+
+#[test]
+fn transient_io_errors_are_retryable() {
+    for kind in [
+        io::ErrorKind::ConnectionReset,
+        io::ErrorKind::TimedOut,
+        io::ErrorKind::UnexpectedEof,
+    ] {
+        assert!(
+            AppError::Io(io::Error::from(kind)).is_retryable(),
+            "{kind:?}"
+        );
+    }
+    assert!(!AppError::Io(io::Error::from(io::ErrorKind::PermissionDenied)).is_retryable());
+}
+
+#[test]
+fn network_git_errors_are_retryable_but_auth_is_not() {
+    let network = git2::Error::new(
+        git2::ErrorCode::GenericError,
+        git2::ErrorClass::Net,
+        "connection reset",
+    );
+    let auth = git2::Error::new(
+        git2::ErrorCode::Auth,
+        git2::ErrorClass::Net,
+        "authentication required",
+    );
+    let config = git2::Error::new(
+        git2::ErrorCode::NotFound,
+        git2::ErrorClass::Config,
+        "missing key",
+    );
+
+    assert!(AppError::Git(network).is_retryable());
+    assert!(!AppError::Git(auth).is_retryable());
+    assert!(!AppError::Git(config).is_retryable());
+}
+
+#[test]
+fn transient_cause_inside_archive_error_is_retryable() {
+    let wrapped = anyhow::Error::from(io::Error::from(io::ErrorKind::ConnectionReset))
+        .context("Downloading snapshot");
+
+    assert!(AppError::Archive(wrapped).is_retryable());
+    assert!(!AppError::Archive(anyhow::anyhow!("Invalid URL format")).is_retryable());
+}
+
+#[test]
+fn truncated_download_is_retryable() {
+    assert!(
+        AppError::TruncatedDownload {
+            expected: 10,
+            received: 5,
+        }
+        .is_retryable()
+    );
+}
+
+#[test]
+fn archive_failures_are_classified() {
+    let url = "http://example.com/keydb_eng.zip".to_string();
+
+    assert!(AppError::from(ArchiveError::RateLimited { url: url.clone() }).is_retryable());
+    let save_failed = ArchiveError::SaveFailed {
+        url: url.clone(),
+        reason: "blocked".to_string(),
+    };
+    assert!(!AppError::from(save_failed).is_retryable());
+    assert!(!AppError::from(ArchiveError::NoSnapshot { url: url.clone() }).is_retryable());
+    assert!(
+        AppError::from(ArchiveError::invalid_content(
+            &url,
+            AppError::TruncatedDownload {
+                expected: 10,
+                received: 5,
+            },
+        ))
+        .is_retryable()
+    );
+}
+
+#[tokio::test]
+async fn refused_connection_is_retryable() {
+    let error = reqwest::get("http://127.0.0.1:9/").await.unwrap_err();
+
+    assert!(AppError::Http(error).is_retryable());
+}
+
+#[test]
+fn permanent_errors_are_not_retryable() {
+    let errors = [
+        AppError::SshAuthFailed,
+        AppError::InvalidArchive {
+            expected: "ZIP archive",
+            content_type: "text/html".to_string(),
+            head: "3c 68 74 6d".to_string(),
+        },
+        AppError::UnexpectedContentType {
+            content_type: "text/plain".to_string(),
+        },
+        AppError::Namcap {
+            stdout: String::new(),
+            stderr: String::new(),
+        },
+    ];
+
+    for error in errors {
+        assert!(!error.is_retryable(), "{error}");
+    }
+}
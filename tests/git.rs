@@ -0,0 +1,426 @@
+//! End-to-end tests for `GitHelper` against a local bare repository standing in for the
+//! AUR remote, addressed through a `file://` URL so no SSH is involved.
+
+use aur_aacs_keydb::{
+    AppError, GitHelper,
+    git::{AuthMethod, auth_method, is_missing_repository},
+};
+use git2::{Repository, Signature};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+const PACKAGE: &str = "test-package";
+
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    /// A bare `remotes/test-package.git` with one commit on master
+    fn new() -> Self {
+        let fixture = Self::empty();
+        let seed = fixture.dir.path().join("seed");
+        let repo = Repository::clone(&fixture.remote_url(), &seed).unwrap();
+        fs::write(seed.join("PKGBUILD"), "pkgver=1\n").unwrap();
+        commit_all(&repo, "Initial commit");
+        push(&repo);
+        fixture
+    }
+
+    /// A bare `remotes/test-package.git` without any commits
+    fn empty() -> Self {
+        let dir = TempDir::new().unwrap();
+        Repository::init_bare(dir.path().join("remotes").join(format!("{PACKAGE}.git"))).unwrap();
+        Self { dir }
+    }
+
+    fn remote_base(&self) -> String {
+        format!("file://{}", self.dir.path().join("remotes").display())
+    }
+
+    fn remote_url(&self) -> String {
+        format!("{}/{PACKAGE}.git", self.remote_base())
+    }
+
+    fn helper(&self) -> GitHelper {
+        GitHelper::new(
+            String::new(),
+            None,
+            Some("Test".to_string()),
+            Some("test@example.com".to_string()),
+        )
+        .with_remote_base(self.remote_base())
+    }
+
+    fn work_path(&self) -> std::path::PathBuf {
+        self.dir.path().join("work").join(PACKAGE)
+    }
+
+    /// Push a commit to the remote from a separate clone, as another maintainer would
+    fn push_from_elsewhere(&self, content: &str, message: &str) {
+        let other = TempDir::new_in(self.dir.path()).unwrap();
+        let repo = Repository::clone(&self.remote_url(), other.path()).unwrap();
+        fs::write(other.path().join("PKGBUILD"), content).unwrap();
+        commit_all(&repo, message);
+        push(&repo);
+    }
+
+    fn remote_head_message(&self) -> String {
+        let remote = Repository::open_bare(
+            self.dir
+                .path()
+                .join("remotes")
+                .join(format!("{PACKAGE}.git")),
+        )
+        .unwrap();
+        let commit = remote
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        commit.message().unwrap().to_string()
+    }
+}
+
+fn commit_all(repo: &Repository, message: &str) {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Other", "other@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(
+        Some("refs/heads/master"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap();
+}
+
+fn push(repo: &Repository) {
+    repo.find_remote("origin")
+        .unwrap()
+        .push(&["refs/heads/master:refs/heads/master"], None)
+        .unwrap();
+}
+
+fn read_pkgbuild(path: &Path) -> String {
+    fs::read_to_string(path.join("PKGBUILD")).unwrap()
+}
+
+#[test]
+fn prepare_clones_missing_repository() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+
+    fixture
+        .helper()
+        .prepare_aur_repo(&path, PACKAGE, true)
+        .unwrap();
+
+    assert_eq!(read_pkgbuild(&path), "pkgver=1\n");
+}
+
+#[test]
+fn prepare_fast_forwards_existing_repository() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fixture.push_from_elsewhere("pkgver=2\n", "Update to 2");
+    helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    assert_eq!(read_pkgbuild(&path), "pkgver=2\n");
+}
+
+#[test]
+fn prepare_resets_diverged_repository() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fs::write(path.join("PKGBUILD"), "pkgver=local\n").unwrap();
+    commit_all(&repo, "Local only");
+    fixture.push_from_elsewhere("pkgver=2\n", "Update to 2");
+    helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    assert_eq!(read_pkgbuild(&path), "pkgver=2\n");
+}
+
+#[test]
+fn prepare_discards_changes_left_by_an_interrupted_run() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fs::write(path.join("PKGBUILD"), "pkgver=partial\n").unwrap();
+    fs::write(path.join("keydb_eng.zip.part"), "partial").unwrap();
+    helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    assert_eq!(read_pkgbuild(&path), "pkgver=1\n");
+    assert!(!path.join("keydb_eng.zip.part").exists());
+    assert!(repo.statuses(None).unwrap().is_empty());
+}
+
+#[test]
+fn prepare_reclones_broken_repository() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("leftover"), "").unwrap();
+
+    fixture
+        .helper()
+        .prepare_aur_repo(&path, PACKAGE, true)
+        .unwrap();
+
+    assert_eq!(read_pkgbuild(&path), "pkgver=1\n");
+    assert!(!path.join("leftover").exists());
+}
+
+#[test]
+fn prepare_refuses_to_delete_broken_repository() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    fs::create_dir_all(&path).unwrap();
+
+    assert!(
+        fixture
+            .helper()
+            .prepare_aur_repo(&path, PACKAGE, false)
+            .is_err()
+    );
+}
+
+#[test]
+fn commit_and_push_updates_remote() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fs::write(path.join("PKGBUILD"), "pkgver=2\n").unwrap();
+    assert!(helper.commit_and_push(&repo, "Update to 2").unwrap());
+
+    assert_eq!(fixture.remote_head_message(), "Update to 2");
+}
+
+#[test]
+fn commit_and_push_skips_unchanged_tree() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    assert!(!helper.commit_and_push(&repo, "Nothing").unwrap());
+
+    assert_eq!(fixture.remote_head_message(), "Initial commit");
+}
+
+#[test]
+fn commit_and_push_creates_master_on_empty_remote() {
+    let fixture = Fixture::empty();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    // Updating the clone of a remote without commits must not fail either
+    helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+    fs::write(path.join("PKGBUILD"), "pkgver=1\n").unwrap();
+    assert!(helper.commit_and_push(&repo, "Initial upload").unwrap());
+
+    assert_eq!(fixture.remote_head_message(), "Initial upload");
+}
+
+#[test]
+fn repo_url_uses_custom_aur_remote() {
+    let helper = GitHelper::new(String::new(), None, None, None).with_aur_remote(
+        "git",
+        "aur.staging.example.org",
+        2222,
+    );
+
+    assert_eq!(
+        helper.repo_url(PACKAGE),
+        "ssh://git@aur.staging.example.org:2222/test-package.git"
+    );
+}
+
+#[test]
+fn token_is_only_used_for_https_remotes() {
+    let https = "https://aur.archlinux.org/test-package.git";
+    let ssh = "ssh://aur@aur.archlinux.org/test-package.git";
+
+    assert_eq!(auth_method(https, true), AuthMethod::HttpsToken);
+    assert_eq!(auth_method(https, false), AuthMethod::SshKey);
+    assert_eq!(auth_method(ssh, true), AuthMethod::SshKey);
+    assert_eq!(auth_method(ssh, false), AuthMethod::SshKey);
+}
+
+#[test]
+fn repo_url_uses_https_with_token() {
+    let helper = GitHelper::new(String::new(), Some("token".to_string()), None, None);
+
+    assert_eq!(
+        helper.repo_url(PACKAGE),
+        "https://aur.archlinux.org/test-package.git"
+    );
+}
+
+#[test]
+fn repo_url_omits_default_ssh_port() {
+    let helper = GitHelper::new(String::new(), None, None, None);
+
+    assert_eq!(
+        helper.repo_url(PACKAGE),
+        "ssh://aur@aur.archlinux.org/test-package.git"
+    );
+}
+
+#[test]
+fn commit_and_push_rolls_back_rejected_push() {
+    let fixture = Fixture::new();
+    let path = fixture.work_path();
+    let helper = fixture.helper();
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+    let before = repo.head().unwrap().peel_to_commit().unwrap().id();
+
+    // The remote moves on, so pushing the local commit is not a fast-forward
+    fixture.push_from_elsewhere("pkgver=2\n", "Update to 2");
+    fs::write(path.join("PKGBUILD"), "pkgver=3\n").unwrap();
+    assert!(helper.commit_and_push(&repo, "Update to 3").is_err());
+
+    let after = repo.head().unwrap().peel_to_commit().unwrap().id();
+    assert_eq!(after, before);
+    assert_eq!(fixture.remote_head_message(), "Update to 2");
+    assert_eq!(read_pkgbuild(&path), "pkgver=1\n");
+    assert!(repo.statuses(None).unwrap().is_empty());
+}
+
+#[test]
+fn rejected_initial_commit_leaves_an_empty_work_tree() {
+    let fixture = Fixture::empty();
+    let path = fixture.work_path();
+    let missing = fixture.dir.path().join("missing.git");
+    let helper = fixture
+        .helper()
+        .with_push_remote("fork", Some(format!("file://{}", missing.display())));
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fs::write(path.join("PKGBUILD"), "pkgver=1\n").unwrap();
+    assert!(helper.commit_and_push(&repo, "Initial commit").is_err());
+
+    assert!(repo.head().is_err());
+    assert!(!path.join("PKGBUILD").exists());
+}
+
+#[test]
+fn commit_and_push_uses_configured_push_remote() {
+    let fixture = Fixture::new();
+    let fork = fixture.dir.path().join("fork.git");
+    Repository::init_bare(&fork).unwrap();
+    let path = fixture.work_path();
+    let helper = fixture
+        .helper()
+        .with_push_remote("fork", Some(format!("file://{}", fork.display())));
+    let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+
+    fs::write(path.join("PKGBUILD"), "pkgver=2\n").unwrap();
+    assert!(helper.commit_and_push(&repo, "Update to 2").unwrap());
+
+    let fork = Repository::open_bare(&fork).unwrap();
+    let fork_head = fork
+        .find_reference("refs/heads/master")
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    assert_eq!(fork_head.message(), Some("Update to 2"));
+    assert_eq!(fixture.remote_head_message(), "Initial commit");
+}
+
+#[test]
+fn diverged_push_remote_does_not_replace_aur_history() {
+    let fixture = Fixture::new();
+    let fork = fixture.dir.path().join("fork.git");
+    git2::build::RepoBuilder::new()
+        .bare(true)
+        .clone(&fixture.remote_url(), &fork)
+        .unwrap();
+    let fork_clone = fixture.dir.path().join("fork-clone");
+    let fork_repo = Repository::clone(&format!("file://{}", fork.display()), &fork_clone).unwrap();
+    fs::write(fork_clone.join("PKGBUILD"), "pkgver=0\n").unwrap();
+    commit_all(&fork_repo, "Fork commit");
+    push(&fork_repo);
+    fixture.push_from_elsewhere("pkgver=2\n", "Update to 2");
+    let path = fixture.work_path();
+    let helper = fixture
+        .helper()
+        .with_push_remote("fork", Some(format!("file://{}", fork.display())));
+
+    // Both a fresh clone and an update of the existing checkout follow the AUR
+    for _ in 0..2 {
+        let repo = helper.prepare_aur_repo(&path, PACKAGE, true).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("Update to 2"));
+        assert_eq!(read_pkgbuild(&path), "pkgver=2\n");
+    }
+}
+
+#[test]
+fn missing_remote_repository_is_package_not_found() {
+    let fixture = Fixture::empty();
+    let path = fixture.work_path();
+
+    let Err(error) = fixture
+        .helper()
+        .prepare_aur_repo(&path, "renamed-package", true)
+    else {
+        panic!("cloning a missing repository should fail");
+    };
+
+    assert!(
+        matches!(
+            error.downcast_ref::<AppError>(),
+            Some(AppError::PackageNotFound { name }) if name == "renamed-package"
+        ),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn git_not_found_errors_mean_a_missing_repository() {
+    let missing = git2::Error::new(
+        git2::ErrorCode::NotFound,
+        git2::ErrorClass::Ssh,
+        "repository not found",
+    );
+    let refused = git2::Error::new(
+        git2::ErrorCode::GenericError,
+        git2::ErrorClass::Ssh,
+        "fatal: 'aur/x.git' does not appear to be a git repository",
+    );
+    let missing_ref = git2::Error::new(
+        git2::ErrorCode::NotFound,
+        git2::ErrorClass::Reference,
+        "reference 'refs/heads/master' not found",
+    );
+    let auth = git2::Error::new(
+        git2::ErrorCode::Auth,
+        git2::ErrorClass::Ssh,
+        "authentication required",
+    );
+
+    assert!(is_missing_repository(&missing));
+    assert!(is_missing_repository(&refused));
+    assert!(!is_missing_repository(&missing_ref));
+    assert!(!is_missing_repository(&auth));
+}
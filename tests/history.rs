@@ -0,0 +1,70 @@
+use aur_aacs_keydb::{
+    ArchiveResult,
+    archive::ArchiveSource,
+    history::{History, HistoryEntry, PhaseTimings},
+};
+use chrono::Utc;
+use std::collections::HashMap;
+
+fn entry(version: &str) -> HistoryEntry {
+    HistoryEntry {
+        package: "aacs-keydb-daily".to_string(),
+        run_at: Utc::now(),
+        archive: ArchiveResult {
+            original_url: "http://example.com/keydb_eng.zip".to_string(),
+            archive_url: format!(
+                "https://web.archive.org/web/{version}/http://example.com/keydb_eng.zip"
+            ),
+            timestamp: Utc::now(),
+            sha256: "0".repeat(64),
+            version: version.to_string(),
+            source: ArchiveSource::FreshlyArchived,
+            capture_status: None,
+            digests: HashMap::new(),
+            content: Vec::new(),
+            content_type: None,
+        },
+        timings: PhaseTimings::default(),
+    }
+}
+
+fn versions(entries: &[HistoryEntry]) -> Vec<&str> {
+    entries
+        .iter()
+        .map(|entry| entry.archive.version.as_str())
+        .collect()
+}
+
+#[test]
+fn appending_beyond_the_limit_evicts_the_oldest_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.jsonl"), 3);
+
+    for version in ["1", "2", "3", "4", "5"] {
+        history.append(&entry(version)).unwrap();
+    }
+
+    assert_eq!(versions(&history.load_recent(10).unwrap()), ["3", "4", "5"]);
+    let content = std::fs::read_to_string(dir.path().join("history.jsonl")).unwrap();
+    assert_eq!(content.lines().count(), 3);
+}
+
+#[test]
+fn load_recent_returns_the_newest_entries_oldest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.jsonl"), 10);
+
+    for version in ["1", "2", "3"] {
+        history.append(&entry(version)).unwrap();
+    }
+
+    assert_eq!(versions(&history.load_recent(2).unwrap()), ["2", "3"]);
+}
+
+#[test]
+fn missing_history_file_is_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.jsonl"), 3);
+
+    assert!(history.load_recent(5).unwrap().is_empty());
+}
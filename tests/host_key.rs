@@ -0,0 +1,122 @@
+use aur_aacs_keydb::{
+    AppError,
+    host_key::{HostKeyPolicy, fingerprint, known_keys},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use hmac::{Hmac, KeyInit, Mac};
+use sha1::Sha1;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+const AUR_KEY: &[u8] = b"\0\0\0\x0bssh-ed25519\0\0\0\x20aur-host-key-of-thirty-two-bytes";
+const OTHER_KEY: &[u8] = b"\0\0\0\x0bssh-ed25519\0\0\0\x20an-attacker-key-of-32-bytes-long";
+
+fn entry(hosts: &str, key: &[u8]) -> String {
+    format!("{hosts} ssh-ed25519 {}\n", STANDARD.encode(key))
+}
+
+/// A `|1|salt|hash` host pattern as written by `ssh-keygen -H`
+fn hashed(name: &str) -> String {
+    let salt = b"twenty-byte-salt-123";
+    let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+    mac.update(name.as_bytes());
+    format!(
+        "|1|{}|{}",
+        STANDARD.encode(salt),
+        STANDARD.encode(mac.finalize().into_bytes())
+    )
+}
+
+fn policy(dir: &TempDir, known_hosts: &str, strict: bool) -> HostKeyPolicy {
+    let path = dir.path().join("known_hosts");
+    std::fs::write(&path, known_hosts).unwrap();
+    HostKeyPolicy {
+        known_hosts: Some(path),
+        fingerprint: None,
+        strict,
+    }
+}
+
+#[test]
+fn known_host_key_is_accepted() {
+    let dir = TempDir::new().unwrap();
+    let policy = policy(
+        &dir,
+        &entry("aur.archlinux.org,95.216.144.15", AUR_KEY),
+        true,
+    );
+
+    policy.verify("aur.archlinux.org", 22, AUR_KEY).unwrap();
+}
+
+#[test]
+fn changed_host_key_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let policy = policy(&dir, &entry("aur.archlinux.org", AUR_KEY), false);
+
+    let error = policy
+        .verify("aur.archlinux.org", 22, OTHER_KEY)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        AppError::HostKeyMismatch { host, fingerprint: fp }
+            if host == "aur.archlinux.org" && fp == fingerprint(OTHER_KEY)
+    ));
+}
+
+#[test]
+fn unknown_host_is_rejected_only_when_strict() {
+    let dir = TempDir::new().unwrap();
+    let known_hosts = entry("github.com", OTHER_KEY);
+
+    let error = policy(&dir, &known_hosts, true)
+        .verify("aur.archlinux.org", 22, AUR_KEY)
+        .unwrap_err();
+    assert!(matches!(error, AppError::UnknownHostKey { .. }));
+
+    policy(&dir, &known_hosts, false)
+        .verify("aur.archlinux.org", 22, AUR_KEY)
+        .unwrap();
+}
+
+#[test]
+fn hashed_and_bracketed_entries_are_matched() {
+    let known_hosts = format!(
+        "# comment\n{}{}",
+        entry(&hashed("aur.archlinux.org"), AUR_KEY),
+        entry("[aur.example.org]:2222", OTHER_KEY)
+    );
+
+    assert_eq!(
+        known_keys(&known_hosts, "aur.archlinux.org", 22),
+        vec![AUR_KEY.to_vec()]
+    );
+    assert_eq!(
+        known_keys(&known_hosts, "aur.example.org", 2222),
+        vec![OTHER_KEY.to_vec()]
+    );
+    assert!(known_keys(&known_hosts, "aur.example.org", 22).is_empty());
+}
+
+#[test]
+fn wildcard_and_negated_patterns_are_honoured() {
+    let known_hosts = entry("*.archlinux.org,!evil.archlinux.org", AUR_KEY);
+
+    assert_eq!(known_keys(&known_hosts, "aur.archlinux.org", 22).len(), 1);
+    assert!(known_keys(&known_hosts, "evil.archlinux.org", 22).is_empty());
+}
+
+#[test]
+fn pinned_fingerprint_overrides_known_hosts() {
+    let policy = HostKeyPolicy {
+        known_hosts: Some(PathBuf::from("/nonexistent/known_hosts")),
+        fingerprint: Some(fingerprint(AUR_KEY)),
+        strict: true,
+    };
+
+    policy.verify("aur.archlinux.org", 22, AUR_KEY).unwrap();
+    assert!(matches!(
+        policy.verify("aur.archlinux.org", 22, OTHER_KEY),
+        Err(AppError::HostKeyMismatch { .. })
+    ));
+}
@@ -0,0 +1,40 @@
+use aur_aacs_keydb::{AppError, lock::WorkDirLock};
+use chrono::Utc;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn stale_lock_from_dead_process_is_reclaimed() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("package.lock");
+    let mut child = Command::new("true").spawn().unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+    std::fs::write(&path, format!("{dead_pid} {}\n", Utc::now().to_rfc3339())).unwrap();
+
+    let lock = WorkDirLock::acquire(&path).unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.starts_with(&format!("{} ", std::process::id())));
+    lock.release();
+    assert!(!path.exists());
+}
+
+#[test]
+fn lock_held_by_running_process_is_kept() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("package.lock");
+    let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    std::fs::write(
+        &path,
+        format!("{} {}\n", child.id(), Utc::now().to_rfc3339()),
+    )
+    .unwrap();
+
+    let result = WorkDirLock::acquire(&path);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(matches!(result, Err(AppError::Locked { .. })));
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use aur_aacs_keydb::{
+    AppError, Config,
+    manifest::{PackageSpec, load, parse},
+};
+
+const MANIFEST: &str = r#"[{"name": "a", "original_url": "https://example.com/a.zip"}]"#;
+
+#[test]
+fn json_and_toml_manifests_parse() {
+    let json =
+        parse(r#"{"packages": [{"name": "a", "original_url": "https://example.com/a.zip"}]}"#)
+            .unwrap();
+    let toml = parse("[[packages]]\nname = \"a\"\noriginal_url = \"https://example.com/a.zip\"\n")
+        .unwrap();
+
+    assert_eq!(json, parse(MANIFEST).unwrap());
+    assert_eq!(json, toml);
+    assert_eq!(json[0].name, "a");
+}
+
+#[test]
+fn every_manifest_problem_is_reported() {
+    let json = r#"[
+        {"name": "", "original_url": "ftp://example.com/a.zip"},
+        {"name": "b", "original_url": "https://example.com/b.zip", "mirror": "x"},
+        {"name": "c", "original_url": 42},
+        {"name": "d", "original_url": "https://example.com/d.zip", "source_filename_template": "d.zip"},
+        {"name": "d", "original_url": "https://example.com/d.zip"}
+    ]"#;
+
+    let Err(AppError::InvalidManifest { problems }) = parse(json) else {
+        panic!("manifest should be rejected");
+    };
+
+    assert_eq!(problems.len(), 6, "{problems:#?}");
+    assert!(problems[0].starts_with("packages[0] ():") && problems[0].contains("name is empty"));
+    assert!(problems[1].contains("unsupported scheme 'ftp'"));
+    assert!(
+        problems[2].starts_with("packages[1]:") && problems[2].contains("unknown field `mirror`")
+    );
+    assert!(problems[3].starts_with("packages[2]:") && problems[3].contains("invalid type"));
+    assert!(problems[4].contains("no ${pkgver} placeholder"));
+    assert!(problems[5].contains("duplicate package name 'd'"));
+}
+
+#[test]
+fn unknown_top_level_keys_are_reported() {
+    let toml = r#"
+        concurrency = 2
+
+        [[packages]]
+        name = "a"
+        original_url = "https://example.com/a.zip"
+    "#;
+
+    let error = parse(toml).unwrap_err();
+
+    assert!(error.to_string().contains("unknown key 'concurrency'"));
+}
+
+#[test]
+fn unparsable_manifest_is_an_error() {
+    assert!(parse("not a manifest").is_err());
+}
+
+#[test]
+fn spec_overrides_package_fields() {
+    let spec = PackageSpec {
+        name: "a".to_string(),
+        original_url: "https://example.com/a.zip".to_string(),
+        source_filename_template: Some("a-${pkgver}.zip".to_string()),
+    };
+
+    let config = spec.apply(&Config::new());
+
+    assert_eq!(config.package_name, "a");
+    assert_eq!(config.original_url, "https://example.com/a.zip");
+    assert_eq!(config.source_filename_template, "a-${pkgver}.zip");
+}
+
+#[tokio::test]
+async fn fetched_manifest_is_cached() {
+    let server = common::MockServer::start(|_| common::Response::new(200).body(MANIFEST)).await;
+    let dir = tempfile::tempdir().unwrap();
+    let cache_path = dir.path().join("cache").join("manifest");
+
+    let specs = load(
+        &reqwest::Client::new(),
+        &format!("{}/manifest.json", server.base_url),
+        &cache_path,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(specs[0].name, "a");
+    assert_eq!(std::fs::read_to_string(&cache_path).unwrap(), MANIFEST);
+}
+
+#[tokio::test]
+async fn cached_manifest_is_used_when_fetch_fails() {
+    let server = common::MockServer::start(|_| common::Response::new(503)).await;
+    let dir = tempfile::tempdir().unwrap();
+    let cache_path = dir.path().join("manifest");
+    std::fs::write(&cache_path, MANIFEST).unwrap();
+
+    let specs = load(
+        &reqwest::Client::new(),
+        &format!("{}/manifest.json", server.base_url),
+        &cache_path,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(specs.len(), 1);
+    assert_eq!(specs[0].original_url, "https://example.com/a.zip");
+}
+
+#[tokio::test]
+async fn failed_fetch_without_cache_is_an_error() {
+    let server = common::MockServer::start(|_| common::Response::new(503)).await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let result = load(
+        &reqwest::Client::new(),
+        &format!("{}/manifest.json", server.base_url),
+        &dir.path().join("manifest"),
+    )
+    .await;
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,24 @@
+use aur_aacs_keydb::retry::backoff_delay;
+use std::time::Duration;
+
+const BASE: Duration = Duration::from_secs(5);
+const MAX: Duration = Duration::from_secs(60);
+
+#[test]
+fn delay_doubles_with_each_attempt() {
+    assert_eq!(backoff_delay(1, BASE, MAX), Duration::from_secs(5));
+    assert_eq!(backoff_delay(2, BASE, MAX), Duration::from_secs(10));
+    assert_eq!(backoff_delay(3, BASE, MAX), Duration::from_secs(20));
+    assert_eq!(backoff_delay(4, BASE, MAX), Duration::from_secs(40));
+}
+
+#[test]
+fn delay_is_capped_at_max() {
+    assert_eq!(backoff_delay(5, BASE, MAX), MAX);
+    assert_eq!(backoff_delay(u32::MAX, BASE, MAX), MAX);
+}
+
+#[test]
+fn attempt_zero_uses_base_delay() {
+    assert_eq!(backoff_delay(0, BASE, MAX), BASE);
+}
@@ -0,0 +1,56 @@
+use aur_aacs_keydb::schedule::Schedule;
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+}
+
+#[test]
+fn cron_schedule_fires_at_next_match() {
+    let schedule = Schedule::parse("0 4 * * *").unwrap();
+
+    assert_eq!(schedule.next_run(at(3, 30), None), Some(at(4, 0)));
+    assert_eq!(
+        schedule.next_run(at(5, 0), Some(at(4, 0))),
+        Some(Utc.with_ymd_and_hms(2024, 1, 2, 4, 0, 0).unwrap())
+    );
+}
+
+#[test]
+fn six_field_cron_expression_is_accepted() {
+    let schedule = Schedule::parse("30 15 */6 * * *").unwrap();
+
+    assert_eq!(
+        schedule.next_run(at(1, 0), None),
+        Some(Utc.with_ymd_and_hms(2024, 1, 1, 6, 15, 30).unwrap())
+    );
+}
+
+#[test]
+fn interval_schedule_runs_immediately_then_after_each_start() {
+    let schedule = Schedule::parse("6h").unwrap();
+
+    assert!(
+        matches!(schedule, Schedule::Every(interval) if interval == Duration::from_secs(21600))
+    );
+    assert_eq!(schedule.next_run(at(1, 0), None), Some(at(1, 0)));
+    assert_eq!(schedule.next_run(at(2, 0), Some(at(1, 0))), Some(at(7, 0)));
+    // A run that overran the interval is followed by the next one right away
+    assert_eq!(schedule.next_run(at(8, 0), Some(at(1, 0))), Some(at(8, 0)));
+}
+
+#[test]
+fn plain_seconds_are_an_interval() {
+    assert!(matches!(
+        Schedule::parse("3600").unwrap(),
+        Schedule::Every(interval) if interval == Duration::from_secs(3600)
+    ));
+}
+
+#[test]
+fn invalid_schedules_are_rejected() {
+    assert!(Schedule::parse("0s").is_err());
+    assert!(Schedule::parse("every day").is_err());
+    assert!(Schedule::parse("61 * * * *").is_err());
+}
@@ -0,0 +1,81 @@
+use aur_aacs_keydb::{AppError, Result, shutdown};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test(start_paused = true)]
+async fn sleep_completes_without_cancellation() {
+    let cancel = CancellationToken::new();
+
+    shutdown::sleep(Duration::from_secs(60), &cancel)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn sleep_returns_early_when_cancelled() {
+    let cancel = CancellationToken::new();
+    let sleeping = tokio::spawn({
+        let cancel = cancel.clone();
+        async move { shutdown::sleep(Duration::from_secs(3600), &cancel).await }
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    cancel.cancel();
+
+    let started = tokio::time::Instant::now();
+    assert!(matches!(sleeping.await.unwrap(), Err(AppError::Cancelled)));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn never_completing_run_times_out() {
+    let cancel = CancellationToken::new();
+
+    let result = shutdown::timeout(
+        Duration::from_secs(5),
+        &cancel,
+        std::future::pending::<Result<()>>(),
+    )
+    .await;
+
+    assert!(
+        matches!(result, Err(AppError::Timeout(duration)) if duration == Duration::from_secs(5))
+    );
+    assert!(cancel.is_cancelled());
+}
+
+#[tokio::test(start_paused = true)]
+async fn timed_out_run_may_wind_down_after_cancellation() {
+    let cancel = CancellationToken::new();
+    let wound_down = Arc::new(AtomicBool::new(false));
+    let run = {
+        let cancel = cancel.clone();
+        let wound_down = wound_down.clone();
+        async move {
+            cancel.cancelled().await;
+            wound_down.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    };
+
+    let result = shutdown::timeout(Duration::from_secs(5), &cancel, run).await;
+
+    assert!(matches!(result, Err(AppError::Timeout(_))));
+    assert!(wound_down.load(Ordering::SeqCst));
+}
+
+#[tokio::test(start_paused = true)]
+async fn run_finishing_in_time_keeps_its_result() {
+    let cancel = CancellationToken::new();
+
+    let result = shutdown::timeout(Duration::from_secs(5), &cancel, async { Ok(42) }).await;
+
+    assert_eq!(result.unwrap(), 42);
+    assert!(!cancel.is_cancelled());
+}
@@ -0,0 +1,81 @@
+use aur_aacs_keydb::{AppError, signature};
+use std::{path::Path, process::Command};
+use tempfile::TempDir;
+
+const PAYLOAD: &[u8] = b"PK\x03\x04 keydb";
+
+fn gpg(home: &Path) -> Command {
+    let mut command = Command::new("gpg");
+    command.arg("--batch").arg("--homedir").arg(home).args([
+        "--pinentry-mode",
+        "loopback",
+        "--passphrase",
+        "",
+    ]);
+    command
+}
+
+/// Sign `PAYLOAD` with a freshly generated key, returning the signature and the path of
+/// the exported public key
+fn sign(dir: &TempDir) -> (Vec<u8>, std::path::PathBuf) {
+    let home = dir.path().join("signer");
+    std::fs::create_dir(&home).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&home, std::fs::Permissions::from_mode(0o700)).unwrap();
+    }
+
+    let status = gpg(&home)
+        .args([
+            "--quick-gen-key",
+            "Test <test@example.com>",
+            "ed25519",
+            "sign",
+            "never",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let pubkey = dir.path().join("pubkey.asc");
+    let status = gpg(&home)
+        .args(["--armor", "--output"])
+        .arg(&pubkey)
+        .args(["--export", "test@example.com"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let payload = dir.path().join("payload");
+    std::fs::write(&payload, PAYLOAD).unwrap();
+    let status = gpg(&home)
+        .arg("--detach-sign")
+        .arg(&payload)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    (
+        std::fs::read(dir.path().join("payload.sig")).unwrap(),
+        pubkey,
+    )
+}
+
+#[test]
+fn valid_signature_is_accepted() {
+    let dir = TempDir::new().unwrap();
+    let (signature, pubkey) = sign(&dir);
+
+    signature::verify(PAYLOAD, &signature, &pubkey).unwrap();
+}
+
+#[test]
+fn tampered_payload_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let (signature, pubkey) = sign(&dir);
+
+    let result = signature::verify(b"PK\x03\x04 tampered", &signature, &pubkey);
+
+    assert!(matches!(result, Err(AppError::SignatureInvalid { .. })));
+}
@@ -0,0 +1,22 @@
+//! Kept apart from the other config tests: it changes `TMPDIR` for the whole process.
+
+use aur_aacs_keydb::Config;
+use std::path::Path;
+
+#[test]
+fn default_work_dir_follows_tmpdir() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    // SAFETY: this is the only test in this binary, nothing reads the environment concurrently
+    unsafe {
+        std::env::remove_var("WORK_DIR");
+        std::env::set_var("TMPDIR", tmpdir.path());
+    }
+
+    let config = Config::new();
+
+    assert_eq!(
+        Path::new(&config.work_dir),
+        tmpdir.path().join("aur-aacs-keydb-daily")
+    );
+    assert!(config.work_dir_is_managed());
+}